@@ -1,7 +1,12 @@
 use serde::{Deserialize, Serialize};
 use chrono::{NaiveDate, NaiveDateTime, Utc, Datelike, Timelike};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::io::{Read, Write};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+use tauri::Manager;
 
 // Calendar file structure
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -10,6 +15,10 @@ pub struct CalendarFile {
     pub path: String,
     pub last_modified: String,
     pub todo_count: usize,
+    pub open_count: usize, // Todos that are neither COMPLETED nor CANCELLED
+    pub display_count: usize, // todo_count, or open_count when completed todos are hidden
+    pub color: Option<String>, // X-APPLE-CALENDAR-COLOR, e.g. #FF2968 or #FF2968FF
+    pub method: Option<String>, // METHOD, e.g. PUBLISH or REQUEST
 }
 
 // Todo structure that matches the frontend
@@ -20,12 +29,290 @@ pub struct Todo {
     pub description: String,
     pub completed: bool,
     pub priority: String,
-    pub category: Option<String>,
+    #[serde(default)]
+    pub categories: Vec<String>, // CATEGORIES, one entry per category
     #[serde(rename = "dueDate")]
     pub due_date: Option<String>, // ISO date string - matches frontend naming
     #[serde(rename = "createdAt")]
     pub created_at: Option<String>, // ISO datetime string - matches frontend naming
     pub calendar_name: String,
+    pub location: Option<String>,
+    pub url: Option<String>,
+    #[serde(rename = "completedAt")]
+    pub completed_at: Option<String>, // ISO datetime string (UTC) of completion
+    #[serde(default)]
+    pub calendar_block: usize, // Index of the source VCALENDAR block within the file
+    pub recurrence_id: Option<String>, // Occurrence date for expanded recurring instances
+    pub rrule: Option<String>, // Raw RRULE value, kept verbatim
+    pub trashed_at: Option<String>, // ISO datetime the todo was moved to the trash
+    #[serde(default)]
+    pub alarms: Vec<Alarm>, // VALARM reminders, in file order
+    #[serde(default = "default_all_day")]
+    pub all_day: bool, // Due on a day (DUE;VALUE=DATE) rather than at a time
+    pub due_time: Option<String>, // UTC HH:MM:SS of a timed DUE, kept so it round-trips
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub comments: Vec<String>, // Every COMMENT line, in order
+    pub class: Option<String>, // PUBLIC, PRIVATE or CONFIDENTIAL
+    pub geo: Option<(f64, f64)>, // (latitude, longitude)
+    pub start_date: Option<String>, // DTSTART: ISO date, or ISO datetime (UTC) if timed
+    pub organizer: Option<Attendee>,
+    #[serde(default)]
+    pub attendees: Vec<Attendee>,
+    pub dtstamp: Option<String>, // ISO datetime (UTC) of the last content change
+    #[serde(default)]
+    pub resources: Vec<String>, // RESOURCES: equipment or people the task needs
+    #[serde(default)]
+    pub summary_params: Vec<(String, String)>, // SUMMARY parameters such as LANGUAGE, kept in order
+    #[serde(default)]
+    pub description_params: Vec<(String, String)>, // DESCRIPTION parameters such as ALTREP
+    pub snooze_until: Option<String>, // ISO datetime (UTC) the todo is hidden until
+    pub due_style: Option<DateStyle>, // How DUE was written in the file
+    pub start_style: Option<DateStyle>, // How DTSTART was written in the file
+    pub duration: Option<String>, // Raw ISO 8601 DURATION, e.g. PT1H30M
+    pub contact: Option<String>, // CONTACT: free text naming who is responsible
+    #[serde(default)]
+    pub contact_params: Vec<(String, String)>, // CONTACT parameters such as ALTREP
+    pub parent_uid: Option<String>, // RELATED-TO (RELTYPE=PARENT): UID of the parent todo
+}
+
+// How a DUE/DTSTART value was serialized, so saving reproduces it instead of
+// churning every file into our preferred form
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DateStyle {
+    ValueDate, // DUE;VALUE=DATE:20250101 (written for new all-day values)
+    BareDate, // DUE:20250101
+    Utc, // DUE:20250101T090000Z (written for new timed values)
+    Floating, // DUE:20250101T090000, local time without a zone
+    Tzid(String), // DUE;TZID=Asia/Tokyo:20250101T090000, local time in the named zone
+}
+
+impl DateStyle {
+    fn detect(value: &str, params: &[(String, String)]) -> Self {
+        if is_date_value(value, params) {
+            if param_value(params, "VALUE").is_some() { DateStyle::ValueDate } else { DateStyle::BareDate }
+        } else if value.trim().ends_with('Z') {
+            DateStyle::Utc
+        } else if let Some(tzid) = param_value(params, "TZID") {
+            DateStyle::Tzid(tzid.to_string())
+        } else {
+            DateStyle::Floating
+        }
+    }
+}
+
+// An ORGANIZER or ATTENDEE: a calendar address (usually mailto:) and its parameters
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Attendee {
+    pub address: String,
+    pub cn: Option<String>, // Display name
+    pub partstat: Option<String>, // e.g. NEEDS-ACTION, ACCEPTED, DECLINED
+    pub role: Option<String>, // e.g. REQ-PARTICIPANT, CHAIR
+    #[serde(default)]
+    pub other_params: Vec<(String, String)>, // Remaining parameters, kept in order
+}
+
+impl Attendee {
+    fn from_property(value: &str, params: Vec<(String, String)>) -> Self {
+        let mut attendee = Attendee {
+            address: value.trim().to_string(),
+            cn: None,
+            partstat: None,
+            role: None,
+            other_params: Vec::new(),
+        };
+        for (key, param) in params {
+            match key.as_str() {
+                "CN" => attendee.cn = Some(param),
+                "PARTSTAT" => attendee.partstat = Some(param),
+                "ROLE" => attendee.role = Some(param),
+                _ => attendee.other_params.push((key, param)),
+            }
+        }
+        attendee
+    }
+    
+    // Content line for this attendee under the given property name
+    fn to_property(&self, name: &str) -> String {
+        let mut line = name.to_string();
+        let known = [("CN", &self.cn), ("PARTSTAT", &self.partstat), ("ROLE", &self.role)];
+        for (key, value) in known.iter().filter_map(|(key, value)| value.as_ref().map(|v| (*key, v))) {
+            line.push_str(&format!(";{}={}", key, quote_param_value(value)));
+        }
+        line.push_str(&format_params(&self.other_params));
+        format!("{}:{}", line, self.address)
+    }
+}
+
+// Render parameters as `;KEY=value` pairs, quoting values where needed
+fn format_params(params: &[(String, String)]) -> String {
+    params
+        .iter()
+        .map(|(key, value)| format!(";{}={}", key, quote_param_value(value)))
+        .collect()
+}
+
+// Parameters of a TEXT property worth keeping (LANGUAGE, ALTREP, X-...); the
+// value is written decoded, so transfer encodings are dropped
+fn text_params(params: &[(String, String)]) -> Vec<(String, String)> {
+    params
+        .iter()
+        .filter(|(key, _)| key != "ENCODING" && key != "CHARSET")
+        .cloned()
+        .collect()
+}
+
+// Quote a parameter value if it contains characters that end a parameter.
+// Values that already contain quotes are multi-valued lists kept as parsed.
+fn quote_param_value(value: &str) -> String {
+    if value.contains('"') {
+        value.to_string()
+    } else if value.contains([':', ';', ',']) {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+// An ATTACH property: either a URI reference or an inline binary
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Attachment {
+    pub uri: Option<String>,
+    pub data: Option<Vec<u8>>, // Decoded from ENCODING=BASE64;VALUE=BINARY
+    pub mime: Option<String>, // FMTTYPE parameter
+}
+
+// Todos without an explicit flag (e.g. created by the frontend) are due on a day
+fn default_all_day() -> bool {
+    true
+}
+
+impl Todo {
+    // A new open todo, created now; append_todo gives it a UID
+    fn new(title: &str, calendar_name: &str) -> Self {
+        Todo {
+            id: String::new(),
+            title: title.to_string(),
+            description: String::new(),
+            completed: false,
+            priority: "medium".to_string(),
+            categories: Vec::new(),
+            due_date: None,
+            created_at: Some(Utc::now().naive_utc().format("%Y-%m-%dT%H:%M:%S").to_string()),
+            calendar_name: calendar_name.to_string(),
+            location: None,
+            url: None,
+            completed_at: None,
+            calendar_block: 0,
+            recurrence_id: None,
+            rrule: None,
+            trashed_at: None,
+            alarms: Vec::new(),
+            all_day: true,
+            due_time: None,
+            attachments: Vec::new(),
+            comments: Vec::new(),
+            class: None,
+            geo: None,
+            start_date: None,
+            organizer: None,
+            attendees: Vec::new(),
+            dtstamp: None,
+            resources: Vec::new(),
+            summary_params: Vec::new(),
+            description_params: Vec::new(),
+            snooze_until: None,
+            due_style: None,
+            start_style: None,
+            duration: None,
+            contact: None,
+            contact_params: Vec::new(),
+            parent_uid: None,
+        }
+    }
+    
+    // Check the todo can be written consistently
+    fn validate(&self) -> Result<(), String> {
+        for alarm in &self.alarms {
+            alarm.validate()?;
+        }
+        if let Some(duration) = self.duration.as_deref().filter(|d| !is_ical_duration(d)) {
+            return Err(format!("Invalid DURATION '{}'", duration));
+        }
+        
+        // DUE may equal DTSTART but not precede it
+        if let (Some(start), Some(due)) = (self.start_date.as_deref(), self.due_date.as_deref()) {
+            let start_is_date = start.len() == 10;
+            let start = parse_iso_datetime(start).ok_or_else(|| format!("Invalid start date: {}", start))?;
+            let due_day = NaiveDate::parse_from_str(due, "%Y-%m-%d").map_err(|e| format!("Invalid due date: {}", e))?;
+            let due = match self.due_time.as_deref() {
+                Some(time) if !self.all_day && !start_is_date => due_day.and_time(
+                    chrono::NaiveTime::parse_from_str(time, "%H:%M:%S").map_err(|e| format!("Invalid due time: {}", e))?,
+                ),
+                _ => due_day.and_time(chrono::NaiveTime::MIN),
+            };
+            let start = if start_is_date || self.all_day { start.date().and_time(chrono::NaiveTime::MIN) } else { start };
+            if due < start {
+                return Err(format!("Todo '{}' is due before it starts", self.title));
+            }
+        }
+        Ok(())
+    }
+    
+    // DTSTART + DURATION, for todos that give a duration instead of a DUE
+    fn due_from_duration(&self) -> Option<NaiveDateTime> {
+        let start = parse_iso_datetime(self.start_date.as_deref()?)?;
+        start.checked_add_signed(parse_ical_duration(self.duration.as_deref()?)?)
+    }
+    
+    // Day the todo is due: its DUE, or else the end of its DURATION
+    fn due_day(&self) -> Option<NaiveDate> {
+        match self.due_date.as_deref() {
+            Some(due) => NaiveDate::parse_from_str(due, "%Y-%m-%d").ok(),
+            None => self.due_from_duration().map(|due| due.date()),
+        }
+    }
+    
+    // DUE line moving the todo to `date`, keeping its time of day and the way
+    // the file wrote it
+    fn due_property(&self, date: NaiveDate) -> String {
+        let time = self
+            .due_time
+            .as_deref()
+            .and_then(|time| chrono::NaiveTime::parse_from_str(time, "%H:%M:%S").ok())
+            .unwrap_or_default();
+        date_property("DUE", date.and_time(time), self.all_day, self.due_style.as_ref())
+    }
+}
+
+// Parse an ISO date (YYYY-MM-DD, as midnight) or datetime (YYYY-MM-DDTHH:MM:SS).
+// RFC 3339 timestamps as sent by JavaScript's toISOString() (with fractional
+// seconds and an offset) are accepted too and converted to UTC.
+fn parse_iso_datetime(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+        .ok()
+        .or_else(|| chrono::DateTime::parse_from_rfc3339(value).ok().map(|dt| dt.naive_utc()))
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok().map(|d| d.and_time(chrono::NaiveTime::MIN)))
+}
+
+// A VALARM reminder nested in a VTODO
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Alarm {
+    pub action: String, // e.g. DISPLAY or AUDIO
+    pub trigger: String, // Duration relative to the todo (e.g. -PT1H) or absolute UTC datetime
+    pub description: Option<String>,
+}
+
+impl Alarm {
+    fn validate(&self) -> Result<(), String> {
+        if is_ical_duration(&self.trigger) || parse_ical_datetime(&self.trigger).is_some() {
+            Ok(())
+        } else {
+            Err(format!("Invalid alarm trigger '{}': expected a duration like -PT1H or a datetime", self.trigger))
+        }
+    }
 }
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
@@ -34,91 +321,360 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+// Environment variable that overrides the calendars directory
+const CALENDARS_DIR_ENV: &str = "TWODO_CALENDARS_DIR";
+
+// Config file stored next to the executable (keeps the app portable)
+const CONFIG_FILE_NAME: &str = "2do-config.json";
+
+// Persisted app configuration: where the calendars live. User preferences are
+// in Settings, which is stored beside the calendars.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct AppConfig {
+    #[serde(default)]
+    calendars_dir: Option<String>,
+    #[serde(default, skip_serializing)]
+    git_commit_on_save: bool, // Read only to move older configs over to Settings
+}
+
+// Where the active calendars directory came from
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CalendarsSource {
+    Env,
+    Setting,
+    Discovered,
+    Fallback, // The discovered location couldn't be created; using a per-user directory
+}
+
+// Calendars directory path together with its source, for display
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CalendarsLocation {
+    pub path: String,
+    pub source: CalendarsSource,
+}
+
 // Get the calendars directory path for display
 #[tauri::command]
-fn get_calendars_path() -> Result<String, String> {
+fn get_calendars_path() -> Result<CalendarsLocation, String> {
+    let (calendars_dir, source) = resolve_calendars_dir()?;
+    Ok(CalendarsLocation {
+        path: calendars_dir.to_string_lossy().to_string(),
+        source,
+    })
+}
+
+// Persist an explicit calendars directory (an empty path clears the override)
+#[tauri::command]
+fn set_calendars_dir(path: String, app: tauri::AppHandle) -> Result<CalendarsLocation, String> {
+    let trimmed = path.trim();
+    let mut config = load_app_config();
+
+    if trimmed.is_empty() {
+        config.calendars_dir = None;
+    } else {
+        let dir = PathBuf::from(trimmed);
+        if !dir.is_absolute() {
+            return Err(format!("Calendars directory must be an absolute path: {}", trimmed));
+        }
+        ensure_calendars_dir(&dir)?;
+        config.calendars_dir = Some(dir.to_string_lossy().to_string());
+    }
+
+    save_app_config(&config)?;
+    forget_known_calendars();
+    
+    // Follow the new directory
+    if load_settings().auto_reload {
+        start_calendar_watcher(&app)?;
+    }
+    get_calendars_path()
+}
+
+// Path of the persisted config file
+fn config_file_path() -> Result<PathBuf, String> {
+    let exe_path = std::env::current_exe()
+        .map_err(|e| format!("Failed to get current executable path: {}", e))?;
+    let app_dir = exe_path.parent().ok_or("Failed to get parent directory")?;
+    Ok(app_dir.join(CONFIG_FILE_NAME))
+}
+
+// Load the persisted config, falling back to defaults if missing or invalid
+fn load_app_config() -> AppConfig {
+    let Ok(path) = config_file_path() else {
+        return AppConfig::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid config file {:?}: {}", path, e);
+            AppConfig::default()
+        }),
+        Err(_) => AppConfig::default(),
+    }
+}
+
+// Move preferences that older versions kept in the config file into Settings
+fn migrate_app_config() -> Result<(), String> {
+    let config = load_app_config();
+    if !config.git_commit_on_save {
+        return Ok(());
+    }
+    let mut settings = load_settings();
+    settings.git_commit_on_save = true;
+    save_settings(settings)?;
+    save_app_config(&config) // Drops the migrated field
+}
+
+// Write the config file
+fn save_app_config(config: &AppConfig) -> Result<(), String> {
+    let path = config_file_path()?;
+    let content = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+    fs::write(&path, content)
+        .map_err(|e| format!("Failed to write config file: {}", e))
+}
+
+// Settings file stored next to the calendars directory
+const SETTINGS_FILE_NAME: &str = "2do-settings.json";
+
+// User preferences
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Settings {
+    pub default_calendar: Option<String>, // Calendar name to open on startup
+    pub default_priority: String,
+    pub show_completed: bool,
+    pub notifications_enabled: bool,
+    pub output_timezone: Option<String>, // IANA zone for timed due dates; None writes UTC
+    pub uid_domain: String, // Suffix of generated UIDs (<uuid>@<domain>)
+    pub auto_reload: bool, // Watch the calendars directory and report external changes
+    pub git_commit_on_save: bool, // Commit saved calendars that live in a git work tree
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            default_calendar: None,
+            default_priority: "medium".to_string(),
+            show_completed: true,
+            notifications_enabled: true,
+            output_timezone: None,
+            uid_domain: DEFAULT_UID_DOMAIN.to_string(),
+            auto_reload: true,
+            git_commit_on_save: false,
+        }
+    }
+}
+
+const DEFAULT_UID_DOMAIN: &str = "2do.local";
+
+// A fresh UID in the RFC 5545 recommended `<unique>@<domain>` form. Callers read
+// the domain from the settings once per operation.
+fn new_uid(domain: &str) -> String {
+    let domain = domain.trim();
+    let domain = if domain.is_empty() { DEFAULT_UID_DOMAIN } else { domain };
+    format!("{}@{}", uuid::Uuid::new_v4(), domain)
+}
+
+// Path of the settings file, beside (not inside) the calendars directory
+fn settings_file_path() -> Result<PathBuf, String> {
     let calendars_dir = get_calendars_dir()?;
-    Ok(calendars_dir.to_string_lossy().to_string())
+    let dir = calendars_dir.parent().unwrap_or(&calendars_dir);
+    Ok(dir.join(SETTINGS_FILE_NAME))
+}
+
+// Load settings, falling back to defaults if the file is missing or invalid
+#[tauri::command]
+fn load_settings() -> Settings {
+    let Ok(path) = settings_file_path() else {
+        return Settings::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid settings file {:?}: {}", path, e);
+            Settings::default()
+        }),
+        Err(_) => Settings::default(),
+    }
+}
+
+// Write the settings file
+#[tauri::command]
+fn save_settings(settings: Settings) -> Result<(), String> {
+    if ical_priority(&settings.default_priority).is_none() {
+        return Err(format!("Invalid default priority: {}", settings.default_priority));
+    }
+    if let Some(zone) = &settings.output_timezone {
+        zone.parse::<chrono_tz::Tz>()
+            .map_err(|e| format!("Invalid output timezone '{}': {}", zone, e))?;
+    }
+    let path = settings_file_path()?;
+    let content = serde_json::to_string_pretty(&settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    fs::write(&path, content)
+        .map_err(|e| format!("Failed to write settings file: {}", e))
+}
+
+// Make sure a configured calendars directory exists (creating it if needed)
+fn ensure_calendars_dir(dir: &Path) -> Result<(), String> {
+    if dir.exists() {
+        if !dir.is_dir() {
+            return Err(format!("Calendars path is not a directory: {:?}", dir));
+        }
+        return Ok(());
+    }
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create calendars directory {:?}: {}", dir, e))
 }
 
-// Get the calendars directory path (local to app for USB portability)
+// Get the calendars directory path
 fn get_calendars_dir() -> Result<PathBuf, String> {
-    // Get the executable path and work backwards to find project root
+    Ok(resolve_calendars_dir()?.0)
+}
+
+// Resolve the calendars directory: env var, then persisted setting, then discovery
+fn resolve_calendars_dir() -> Result<(PathBuf, CalendarsSource), String> {
+    if let Ok(value) = std::env::var(CALENDARS_DIR_ENV) {
+        if !value.trim().is_empty() {
+            let dir = PathBuf::from(value.trim());
+            ensure_calendars_dir(&dir)?;
+            return Ok((dir, CalendarsSource::Env));
+        }
+    }
+
+    if let Some(configured) = load_app_config().calendars_dir {
+        let dir = PathBuf::from(configured);
+        ensure_calendars_dir(&dir)?;
+        return Ok((dir, CalendarsSource::Setting));
+    }
+
+    discover_calendars_dir()
+}
+
+// Cargo build profile directories skipped while walking up from the executable
+const BUILD_PROFILE_DIRS: [&str; 2] = ["debug", "release"];
+
+// App data directory, resolved at startup (fallback for installed apps)
+static APP_DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+// Tauri's app data directory (<platform data dir>/<identifier>), worked out
+// without a running app
+fn app_data_dir(identifier: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(identifier))
+}
+
+// Discover the calendars directory (local to app for USB portability)
+fn discover_calendars_dir() -> Result<(PathBuf, CalendarsSource), String> {
     let exe_path = std::env::current_exe()
         .map_err(|e| format!("Failed to get current executable path: {}", e))?;
-    
-    eprintln!("Executable path: {:?}", exe_path);
-    
-    // For development, the executable is in target/debug/, so go up to project root
-    let mut search_path = exe_path.parent().ok_or("Failed to get parent directory")?.to_path_buf();
-    
-    eprintln!("Starting search from: {:?}", search_path);
-    
-    // Look for calendars directory by going up the directory tree
-    // Skip the debug directory and go straight to project root
-    loop {
-        // Skip the debug directory - go up to target first
-        if search_path.ends_with("debug") {
-            if let Some(parent) = search_path.parent() {
-                search_path = parent.to_path_buf();
-                eprintln!("Skipping debug directory, moving up to: {:?}", search_path);
-                continue;
+    log::debug!("Executable path: {:?}", exe_path);
+
+    let app_dir = exe_path.parent().ok_or("Failed to get parent directory")?;
+
+    if let Some(calendars_dir) = find_calendars_dir_from(app_dir) {
+        return Ok((calendars_dir, CalendarsSource::Discovered));
+    }
+
+    // Portable layout: an (empty) calendars folder shipped next to the executable
+    let portable_dir = app_dir.join("calendars");
+    if portable_dir.is_dir() {
+        log::debug!("Using portable calendars directory at: {:?}", portable_dir);
+        return Ok((portable_dir, CalendarsSource::Discovered));
+    }
+
+    // Create the folder next to the executable. Installed apps usually can't
+    // write there, so they fall back to the app data directory.
+    create_calendars_dir(portable_dir, fallback_calendars_dirs())
+}
+
+// Create the preferred calendars directory or, if that fails (e.g. a read-only
+// install location), the first of `fallbacks` that can be created
+fn create_calendars_dir(preferred: PathBuf, fallbacks: Vec<PathBuf>) -> Result<(PathBuf, CalendarsSource), String> {
+    log::debug!("Creating calendars directory at: {:?}", preferred);
+    let preferred_error = match ensure_calendars_dir(&preferred) {
+        Ok(()) => return Ok((preferred, CalendarsSource::Discovered)),
+        Err(e) => e,
+    };
+    log::warn!("{}; trying a per-user location", preferred_error);
+
+    // Read-only install locations: fall back to a per-user data directory
+    for fallback in fallbacks.into_iter().filter(|dir| *dir != preferred) {
+        match ensure_calendars_dir(&fallback) {
+            Ok(()) => {
+                log::info!("Using fallback calendars directory at: {:?}", fallback);
+                return Ok((fallback, CalendarsSource::Fallback));
             }
+            Err(e) => log::warn!("{}", e),
         }
-        
-        let calendars_dir = search_path.join("calendars");
-        eprintln!("Checking for calendars at: {:?}", calendars_dir);
-        
-        if calendars_dir.exists() {
-            // Check if this calendars directory has any ICS files
-            if has_ics_files(&calendars_dir) {
-                eprintln!("Found calendars directory with ICS files at: {:?}", calendars_dir);
-                return Ok(calendars_dir);
-            } else {
-                eprintln!("Found empty calendars directory at: {:?}, continuing search", calendars_dir);
+    }
+
+    Err(format!(
+        "No writable calendars directory found ({}). Choose a calendars folder in settings or set {}.",
+        preferred_error, CALENDARS_DIR_ENV
+    ))
+}
+
+// Per-user directories to try when the discovered location can't be created,
+// most specific first
+fn fallback_calendars_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(app_data_dir) = APP_DATA_DIR.get() {
+        dirs.push(app_data_dir.join("calendars"));
+    }
+    let user_data_dir = ["APPDATA", "XDG_DATA_HOME"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(PathBuf::from)
+        .find(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")));
+    if let Some(user_data_dir) = user_data_dir {
+        dirs.push(user_data_dir.join("2do").join("calendars"));
+    }
+    dirs
+}
+
+// Walk up from `start` looking for a calendars directory that contains ICS files,
+// skipping cargo build profile directories (target/debug, target/release)
+fn find_calendars_dir_from(start: &Path) -> Option<PathBuf> {
+    let mut search_path = start.to_path_buf();
+    log::debug!("Starting search from: {:?}", search_path);
+
+    loop {
+        let in_build_dir = BUILD_PROFILE_DIRS
+            .iter()
+            .any(|profile| search_path.ends_with(profile));
+
+        if !in_build_dir {
+            let calendars_dir = search_path.join("calendars");
+            log::debug!("Checking for calendars at: {:?}", calendars_dir);
+
+            if calendars_dir.is_dir() {
+                if has_ics_files(&calendars_dir) {
+                    log::debug!("Found calendars directory with ICS files at: {:?}", calendars_dir);
+                    return Some(calendars_dir);
+                }
+                log::debug!("Found empty calendars directory at: {:?}, continuing search", calendars_dir);
             }
-        }
-        
-        // If we can't go up further, break
-        if let Some(parent) = search_path.parent() {
-            search_path = parent.to_path_buf();
-            eprintln!("Moving up to: {:?}", search_path);
         } else {
-            eprintln!("No more parents, stopping search");
-            break;
+            log::debug!("Skipping build directory: {:?}", search_path);
         }
-        
-        // Safety check to avoid infinite loops
-        if search_path.components().count() < 3 {
-            eprintln!("Reached root directory, stopping search");
-            break;
+
+        // Safety check to avoid walking up to the filesystem root
+        let parent = search_path.parent()?;
+        if parent.components().count() < 3 {
+            log::debug!("Reached root directory, stopping search");
+            return None;
         }
+        search_path = parent.to_path_buf();
     }
-    
-    // If we get here, we didn't find any calendars directory with ICS files
-    // Create it next to the executable as a fallback
-    let app_dir = exe_path.parent().ok_or("Failed to get parent directory")?;
-    let calendars_dir = app_dir.join("calendars");
-    
-    eprintln!("Creating calendars directory at: {:?}", calendars_dir);
-    
-    // Create directory if it doesn't exist
-    if !calendars_dir.exists() {
-        fs::create_dir_all(&calendars_dir)
-            .map_err(|e| format!("Failed to create calendars directory: {}", e))?;
-    }
-    
-    Ok(calendars_dir)
 }
 
 // Helper function to check if a directory contains ICS files
 fn has_ics_files(dir: &PathBuf) -> bool {
     if let Ok(entries) = fs::read_dir(dir) {
         for entry in entries.flatten() {
-            if let Some(ext) = entry.path().extension() {
-                if ext == "ics" {
-                    return true;
-                }
+            if is_calendar_file(&entry.path()) {
+                return true;
             }
         }
     }
@@ -165,22 +721,7 @@ async fn create_calendar(name: String) -> Result<CalendarFile, String> {
         base = "untitled".to_string();
     }
 
-    // Ensure unique filename by appending numeric suffix if needed
-    let mut candidate = calendars_dir.join(format!("{}.ics", base));
-    if candidate.exists() {
-        let mut idx: u32 = 1;
-        loop {
-            let alt = calendars_dir.join(format!("{}-{}.ics", base, idx));
-            if !alt.exists() {
-                candidate = alt;
-                break;
-            }
-            idx += 1;
-            if idx > 1000 {
-                return Err("Failed to create unique calendar filename".to_string());
-            }
-        }
-    }
+    let candidate = unique_calendar_path(&calendars_dir, &base)?;
 
     // Minimal VCALENDAR skeleton
     let mut content = String::new();
@@ -192,6 +733,7 @@ async fn create_calendar(name: String) -> Result<CalendarFile, String> {
 
     fs::write(&candidate, content)
         .map_err(|e| format!("Failed to create calendar file: {}", e))?;
+    remember_calendar(&candidate);
 
     let metadata = fs::metadata(&candidate)
         .map_err(|e| format!("Failed to read file metadata: {}", e))?;
@@ -212,9 +754,81 @@ async fn create_calendar(name: String) -> Result<CalendarFile, String> {
         path: candidate.to_string_lossy().to_string(),
         last_modified: last_modified.to_string(),
         todo_count: 0,
+        open_count: 0,
+        display_count: 0,
+        color: None,
+        method: None,
     })
 }
 
+// `<base>.ics` in `dir`, or `<base>-<n>.ics` with the first free numeric suffix
+fn unique_calendar_path(dir: &Path, base: &str) -> Result<PathBuf, String> {
+    let candidate = dir.join(format!("{}.ics", base));
+    if !candidate.exists() {
+        return Ok(candidate);
+    }
+    for idx in 1..=1000 {
+        let alt = dir.join(format!("{}-{}.ics", base, idx));
+        if !alt.exists() {
+            return Ok(alt);
+        }
+    }
+    Err("Failed to create unique calendar filename".to_string())
+}
+
+// Copy an external .ics file into the calendars directory. The file must
+// contain at least one VTODO; it is stored as UTF-8 under a sanitized name.
+#[tauri::command]
+async fn import_ics(source_path: String, name: Option<String>) -> Result<CalendarFile, String> {
+    let source = PathBuf::from(&source_path);
+    let content = read_calendar_file(&source)?;
+    if todo_counts(&content).0 == 0 {
+        return Err(format!("No VTODO components found in {}", source_path));
+    }
+    
+    let name = name
+        .filter(|n| !n.trim().is_empty())
+        .unwrap_or_else(|| calendar_stem(&source));
+    let target = unique_calendar_path(&get_calendars_dir()?, &sanitize_filename(&name))?;
+    write_calendar_file(&target, &content)?;
+    remember_calendar(&target);
+    log::info!("Imported {:?} as {:?}", source, target);
+    
+    calendar_file_info(&target, load_settings().show_completed)
+}
+
+// Read todos from a calendar the user picked outside the calendars directory
+// (e.g. a synced Nextcloud or Dropbox folder). Unlike the managed commands this
+// is not confined to the calendars directory, so it only ever reads; the file
+// must be a calendar that parses.
+#[tauri::command]
+async fn load_external_calendar(path: String) -> Result<TodosWithMeta, String> {
+    let requested = PathBuf::from(path.trim());
+    if !requested.is_absolute() {
+        return Err(format!("External calendar path must be absolute: {}", path));
+    }
+    let resolved = fs::canonicalize(&requested)
+        .map_err(|e| format!("Failed to resolve external calendar {}: {}", path, e))?;
+    if !resolved.is_file() || !is_calendar_file(&resolved) {
+        return Err(format!("External calendar must be an .ics or .ics.gz file: {}", path));
+    }
+    
+    let content = read_calendar_file(&resolved)?;
+    let (_, report) = parse_calendar_leniently(&content);
+    if !content.to_ascii_uppercase().contains("BEGIN:VCALENDAR") || report.unterminated_blocks > 0 {
+        return Err(format!("Not a valid iCalendar file: {}", path));
+    }
+    
+    let modified = modified_token(file_modified_time(&resolved)?);
+    let warnings = calendar_warnings(&content);
+    for warning in &warnings {
+        log::warn!("{:?}: {}", resolved, warning);
+    }
+    let todos = parse_todos_from_content(&content, &calendar_stem(&resolved));
+    log::info!("Loaded {} todos from external calendar {:?}", todos.len(), resolved);
+    Ok(TodosWithMeta { todos, modified, method: calendar_method(&content), warnings })
+}
+
 // List all available calendar files
 #[tauri::command]
 async fn list_calendars() -> Result<Vec<CalendarFile>, String> {
@@ -224,36 +838,20 @@ async fn list_calendars() -> Result<Vec<CalendarFile>, String> {
     let entries = fs::read_dir(&calendars_dir)
         .map_err(|e| format!("Failed to read calendars directory: {}", e))?;
     
+    let show_completed = load_settings().show_completed;
+    let mut paths = Vec::new();
     for entry in entries {
         let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
         let path = entry.path();
         
-        if path.extension().and_then(|s| s.to_str()) == Some("ics") {
-            let metadata = entry.metadata()
-                .map_err(|e| format!("Failed to read file metadata: {}", e))?;
-            
-            let name = path.file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("Unknown")
-                .to_string();
-            
-            let last_modified = metadata.modified()
-                .map_err(|e| format!("Failed to get modification time: {}", e))?
-                .duration_since(std::time::UNIX_EPOCH)
-                .map_err(|e| format!("Failed to convert modification time: {}", e))?
-                .as_secs();
-            
-            // Count todos in this calendar
-            let todo_count = count_todos_in_file(&path).unwrap_or(0);
-            
-            calendars.push(CalendarFile {
-                name,
-                path: path.to_string_lossy().to_string(),
-                last_modified: last_modified.to_string(),
-                todo_count,
-            });
+        if is_calendar_file(&path) {
+            calendars.push(calendar_file_info(&path, show_completed)?);
+            paths.push(path);
         }
     }
+    if let Ok(mut known) = KNOWN_CALENDARS.lock() {
+        *known = paths;
+    }
     
     // Sort by last modified (newest first)
     calendars.sort_by(|a, b| b.last_modified.cmp(&a.last_modified));
@@ -261,360 +859,7838 @@ async fn list_calendars() -> Result<Vec<CalendarFile>, String> {
     Ok(calendars)
 }
 
-// Count todos in a calendar file
-fn count_todos_in_file(path: &PathBuf) -> Result<usize, String> {
-    let content = fs::read_to_string(path)
-        .map_err(|e| format!("Failed to read file: {}", e))?;
-    
-    let mut count = 0;
-    let lines: Vec<&str> = content.lines().collect();
-    let mut i = 0;
-    
-    while i < lines.len() {
-        if lines[i].trim() == "BEGIN:VTODO" {
-            count += 1;
-            // Skip to the end of this VTODO block
-            while i < lines.len() && lines[i].trim() != "END:VTODO" {
-                i += 1;
-            }
+// Calendar files found by the last directory scan, so counts can be
+// refreshed without listing the directory again
+static KNOWN_CALENDARS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+// Add a newly created calendar to the known set (once a scan has filled it)
+fn remember_calendar(path: &Path) {
+    if let Ok(mut known) = KNOWN_CALENDARS.lock() {
+        if !known.is_empty() && !known.iter().any(|p| p == path) {
+            known.push(path.to_path_buf());
         }
-        i += 1;
     }
-    
-    Ok(count)
 }
 
-// Load todos from a specific calendar file
+fn forget_known_calendars() {
+    if let Ok(mut known) = KNOWN_CALENDARS.lock() {
+        known.clear();
+    }
+}
+
+// Refresh modification times, counts and colors of the known calendars,
+// falling back to a full scan if none are known yet
 #[tauri::command]
-async fn load_todos_from_calendar(calendar_path: String) -> Result<Vec<Todo>, String> {
-    let content = fs::read_to_string(&calendar_path)
-        .map_err(|e| format!("Failed to read calendar file: {}", e))?;
-    
-    let calendar_name = PathBuf::from(&calendar_path)
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("Unknown")
-        .to_string();
-    
-    let mut todos = Vec::new();
-    let lines: Vec<&str> = content.lines().collect();
-    let mut i = 0;
-    let mut vtodo_count = 0;
-    let mut parsed_count = 0;
+async fn recompute_counts() -> Result<Vec<CalendarFile>, String> {
+    let known = KNOWN_CALENDARS.lock().map(|known| known.clone()).unwrap_or_default();
+    if known.is_empty() {
+        return list_calendars().await;
+    }
     
-    while i < lines.len() {
-        if lines[i].trim() == "BEGIN:VTODO" {
-            vtodo_count += 1;
-            let mut vtodo_lines = Vec::new();
-            i += 1;
-            
-            // Collect all lines for this VTODO block
-            while i < lines.len() && lines[i].trim() != "END:VTODO" {
-                vtodo_lines.push(lines[i]);
-                i += 1;
-            }
-            
-            match parse_vtodo_from_lines(&vtodo_lines, &calendar_name) {
-                Ok(todo) => {
-                    todos.push(todo);
-                    parsed_count += 1;
-                },
-                Err(e) => {
-                    eprintln!("Failed to parse VTODO {}: {}", vtodo_count, e);
-                }
-            }
-        }
-        i += 1;
+    let show_completed = load_settings().show_completed;
+    let mut calendars = Vec::new();
+    for path in known.iter().filter(|path| path.exists()) {
+        calendars.push(calendar_file_info(path, show_completed)?);
     }
+    calendars.sort_by(|a, b| b.last_modified.cmp(&a.last_modified));
+    Ok(calendars)
+}
+
+// Describe one calendar file, counting todos and reading the color in a single read.
+// `show_completed` (the user's setting) picks which count is displayed.
+fn calendar_file_info(path: &Path, show_completed: bool) -> Result<CalendarFile, String> {
+    let last_modified = file_modified_time(path)?
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| format!("Failed to convert modification time: {}", e))?
+        .as_secs();
     
-    eprintln!("Parsed {}/{} VTODOs from calendar '{}'", parsed_count, vtodo_count, calendar_name);
+    let content = read_calendar_file(path).unwrap_or_default();
+    let (todo_count, open_count) = todo_counts(&content);
     
-    Ok(todos)
+    Ok(CalendarFile {
+        name: calendar_stem(path),
+        path: path.to_string_lossy().to_string(),
+        last_modified: last_modified.to_string(),
+        todo_count,
+        open_count,
+        display_count: if show_completed { todo_count } else { open_count },
+        color: calendar_color(&content),
+        method: calendar_method(&content),
+    })
 }
 
-// Parse a VTODO from raw iCalendar lines
-fn parse_vtodo_from_lines(lines: &[&str], calendar_name: &str) -> Result<Todo, String> {
-    let mut id = String::new();
-    let mut title = String::new();
-    let mut description = String::new();
-    let mut completed = false;
-    let mut priority = "medium".to_string();
-    let mut category = None;
-    let mut due_date = None;
-    let mut created_at = None;
+// File-level details of a calendar, beyond what CalendarFile carries
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CalendarMeta {
+    pub path: String,
+    pub size_bytes: u64,
+    pub last_modified: String, // Seconds since the epoch, like CalendarFile
+    pub created: Option<String>, // Seconds since the epoch; not every filesystem records it
+    pub todo_count: usize,
+    pub open_count: usize,
+    pub writable: bool,
+}
+
+// Size, timestamps, counts and writability of a calendar file
+#[tauri::command]
+async fn get_calendar_metadata(path: String) -> Result<CalendarMeta, String> {
+    let path = resolve_calendar_path(&path, false)?;
+    let metadata = fs::metadata(&path).map_err(|e| format!("Failed to read file metadata: {}", e))?;
+    let epoch_secs = |time: SystemTime| {
+        time.duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs().to_string())
+            .map_err(|e| format!("Failed to convert file time: {}", e))
+    };
+    let (todo_count, open_count) = todo_counts(&read_calendar_file(&path)?);
     
-    for line in lines {
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
-        }
-        
-        if let Some(colon_pos) = line.find(':') {
-            let property_name = &line[..colon_pos];
-            let property_value = &line[colon_pos + 1..];
-            
-            // Handle properties with parameters (e.g., DUE;VALUE=DATE)
-            let base_property = if let Some(semicolon_pos) = property_name.find(';') {
-                &property_name[..semicolon_pos]
-            } else {
-                property_name
-            };
-            
-            match base_property {
-                "UID" => id = property_value.to_string(),
-                "SUMMARY" => title = unescape_ical_text(property_value),
-                "DESCRIPTION" => description = unescape_ical_text(property_value),
-                "STATUS" => {
-                    completed = property_value == "COMPLETED";
-                },
-                "PRIORITY" => {
-                    priority = match property_value {
-                        "1" | "2" | "3" => "high",
-                        "4" | "5" | "6" => "medium",
-                        "7" | "8" | "9" => "low",
-                        _ => "medium",
-                    }.to_string();
-                },
-                "CATEGORIES" => {
-                    category = Some(unescape_ical_text(property_value));
-                },
-                "DUE" => {
-                    // Parse iCalendar date format (YYYYMMDD or YYYYMMDDTHHMMSSZ)
-                    if property_value.len() >= 8 {
-                        let date_part = &property_value[0..8];
-                        if let Ok(year) = date_part[0..4].parse::<i32>() {
-                            if let Ok(month) = date_part[4..6].parse::<u32>() {
-                                if let Ok(day) = date_part[6..8].parse::<u32>() {
-                                    if let Some(date) = NaiveDate::from_ymd_opt(year, month, day) {
-                                        due_date = Some(date.format("%Y-%m-%d").to_string());
-                                    }
-                                }
-                            }
-                        }
-                    }
-                },
-                "CREATED" | "DTSTAMP" => {
-                    eprintln!("Parsing {} field: '{}' (len: {})", base_property, property_value, property_value.len());
-                    // Parse iCalendar datetime format (YYYYMMDDTHHMMSSZ)
-                    if property_value.len() >= 15 && property_value.contains('T') {
-                        let date_part = &property_value[0..8];
-                        let time_part = &property_value[9..15];
-                        eprintln!("  Date part: '{}', Time part: '{}'", date_part, time_part);
-                        
-                        if let Ok(year) = date_part[0..4].parse::<i32>() {
-                            if let Ok(month) = date_part[4..6].parse::<u32>() {
-                                if let Ok(day) = date_part[6..8].parse::<u32>() {
-                                    if let Ok(hour) = time_part[0..2].parse::<u32>() {
-                                        if let Ok(minute) = time_part[2..4].parse::<u32>() {
-                                            if let Ok(second) = time_part[4..6].parse::<u32>() {
-                                                if let Some(dt) = NaiveDate::from_ymd_opt(year, month, day)
-                                                    .and_then(|d| d.and_hms_opt(hour, minute, second)) {
-                                                    let formatted = dt.format("%Y-%m-%dT%H:%M:%S").to_string();
-                                                    eprintln!("  Successfully parsed {} to: '{}'", base_property, formatted);
-                                                    created_at = Some(formatted);
-                                                } else {
-                                                    eprintln!("  Failed to create datetime from {}-{}-{} {}:{}:{}", year, month, day, hour, minute, second);
-                                                }
-                                            } else {
-                                                eprintln!("  Failed to parse second: '{}'", &time_part[4..6]);
-                                            }
-                                        } else {
-                                            eprintln!("  Failed to parse minute: '{}'", &time_part[2..4]);
-                                        }
-                                    } else {
-                                        eprintln!("  Failed to parse hour: '{}'", &time_part[0..2]);
-                                    }
-                                } else {
-                                    eprintln!("  Failed to parse day: '{}'", &date_part[6..8]);
-                                }
-                            } else {
-                                eprintln!("  Failed to parse month: '{}'", &date_part[4..6]);
-                            }
-                        } else {
-                            eprintln!("  Failed to parse year: '{}'", &date_part[0..4]);
-                        }
-                    } else if property_value.len() == 8 {
-                        eprintln!("  Parsing as date-only format");
-                        // Handle date-only format (YYYYMMDD)
-                        if let Ok(year) = property_value[0..4].parse::<i32>() {
-                            if let Ok(month) = property_value[4..6].parse::<u32>() {
-                                if let Ok(day) = property_value[6..8].parse::<u32>() {
-                                    if let Some(date) = NaiveDate::from_ymd_opt(year, month, day) {
-                                        let formatted = date.format("%Y-%m-%d").to_string();
-                                        eprintln!("  Successfully parsed {} to: '{}'", base_property, formatted);
-                                        created_at = Some(formatted);
-                                    } else {
-                                        eprintln!("  Failed to create date from {}-{}-{}", year, month, day);
-                                    }
-                                } else {
-                                    eprintln!("  Failed to parse day: '{}'", &property_value[6..8]);
-                                }
-                            } else {
-                                eprintln!("  Failed to parse month: '{}'", &property_value[4..6]);
-                            }
-                        } else {
-                            eprintln!("  Failed to parse year: '{}'", &property_value[0..4]);
-                        }
-                    } else {
-                        eprintln!("  Field length {} is not 8 or >=15, skipping", property_value.len());
-                    }
-                },
-                _ => {} // Ignore other properties
-            }
+    Ok(CalendarMeta {
+        path: path.to_string_lossy().to_string(),
+        size_bytes: metadata.len(),
+        last_modified: epoch_secs(file_modified_time(&path)?)?,
+        created: metadata.created().ok().and_then(|time| epoch_secs(time).ok()),
+        todo_count,
+        open_count,
+        writable: !metadata.permissions().readonly(),
+    })
+}
+
+// Envelope declarations we don't parse as declared: anything but iCalendar 2.0
+// (e.g. vCalendar 1.0) and non-Gregorian calendar scales
+fn calendar_warnings(content: &str) -> Vec<String> {
+    let mut warnings = Vec::new();
+    match envelope_property(content, "VERSION") {
+        Some(version) if version != "2.0" => {
+            warnings.push(format!("Calendar declares VERSION:{}; it is read as iCalendar 2.0", version));
         }
+        None => warnings.push("Calendar has no VERSION; it is read as iCalendar 2.0".to_string()),
+        _ => {}
     }
-    
-    // Generate ID if not present
-    if id.is_empty() {
-        id = uuid::Uuid::new_v4().to_string();
+    if let Some(scale) = envelope_property(content, "CALSCALE").filter(|s| !s.eq_ignore_ascii_case("GREGORIAN")) {
+        warnings.push(format!("Calendar uses CALSCALE:{}; dates are read as Gregorian", scale));
     }
-    
-    // Set default title if empty
-    if title.is_empty() {
-        title = "Untitled Task".to_string();
+    warnings
+}
+
+// Envelope property holding a calendar's display color
+const CALENDAR_COLOR_PROPERTY: &str = "X-APPLE-CALENDAR-COLOR";
+
+// Read a calendar's color
+#[tauri::command]
+async fn get_calendar_color(path: String) -> Result<Option<String>, String> {
+    let path = resolve_calendar_path(&path, false)?;
+    Ok(calendar_color(&read_calendar_file(&path)?))
+}
+
+// Set (or, with an empty string, clear) a calendar's color, leaving todos untouched
+#[tauri::command]
+async fn set_calendar_color(path: String, hex: String, cache: tauri::State<'_, TodoCache>) -> Result<(), String> {
+    let hex = hex.trim().to_ascii_uppercase();
+    if !hex.is_empty() && !is_hex_color(&hex) {
+        return Err(format!("Invalid color '{}': expected #RRGGBB or #RRGGBBAA", hex));
     }
+    let path = resolve_calendar_path(&path, true)?;
     
-    Ok(Todo {
-        id,
-        title,
-        description,
-        completed,
-        priority,
-        category,
-        due_date,
-        created_at,
-        calendar_name: calendar_name.to_string(),
+    modify_calendar_file(&path, &cache, |sections| {
+        for section in sections.iter_mut() {
+            // Drop the old value, including any folded continuation lines
+            let mut removing = false;
+            section.properties.retain(|line| {
+                if !line.starts_with([' ', '\t']) {
+                    removing = property_base_name(line).as_deref() == Some(CALENDAR_COLOR_PROPERTY);
+                }
+                !removing
+            });
+            if !hex.is_empty() {
+                section.properties.push(format!("{}:{}", CALENDAR_COLOR_PROPERTY, hex));
+            }
+        }
+        Ok(())
     })
 }
 
-// Save todos back to a calendar file
+fn is_hex_color(value: &str) -> bool {
+    value
+        .strip_prefix('#')
+        .is_some_and(|digits| matches!(digits.len(), 6 | 8) && digits.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+// Value of a calendar envelope property (one before the first component)
+fn envelope_property(content: &str, name: &str) -> Option<String> {
+    for line in split_lines(content) {
+        if component_marker(line, "BEGIN").is_some_and(|name| !name.eq_ignore_ascii_case("VCALENDAR")) {
+            break;
+        }
+        if property_base_name(line).as_deref() == Some(name) {
+            return split_property_line(line.trim()).map(|(_, value)| value.trim().to_string());
+        }
+    }
+    None
+}
+
+fn calendar_color(content: &str) -> Option<String> {
+    envelope_property(content, CALENDAR_COLOR_PROPERTY)
+}
+
+// iTIP METHOD of a calendar (e.g. REQUEST for an invitation); None for plain
+// calendars. It is kept as-is when the file is saved.
+fn calendar_method(content: &str) -> Option<String> {
+    envelope_property(content, "METHOD").map(|method| method.to_ascii_uppercase())
+}
+
+// Per-calendar count of open todos that are due today or overdue
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DueCalendar {
+    pub name: String,
+    pub path: String,
+    pub due_today: usize,
+    pub overdue: usize,
+}
+
+// Notify about todos due today or overdue across all calendars. Called by the
+// frontend on startup; returns the per-calendar breakdown so it can offer to
+// open the relevant calendar.
 #[tauri::command]
-async fn save_todos_to_calendar(calendar_path: String, todos: Vec<Todo>) -> Result<(), String> {
-    let mut calendar_content = String::new();
-    
-    eprintln!("Saving {} todos to calendar file: {}", todos.len(), calendar_path);
+async fn notify_due_todos(app: tauri::AppHandle) -> Result<Vec<DueCalendar>, String> {
+    if !load_settings().notifications_enabled {
+        return Ok(Vec::new());
+    }
     
-    // Start iCalendar header
-    calendar_content.push_str("BEGIN:VCALENDAR\r\n");
-    calendar_content.push_str("VERSION:2.0\r\n");
-    calendar_content.push_str("PRODID:-//Todo Calendar//Todo Calendar//EN\r\n");
-    calendar_content.push_str("CALSCALE:GREGORIAN\r\n");
+    let today = chrono::Local::now().date_naive();
+    let due = collect_due_todos(&get_calendars_dir()?, today)?;
+    let due_today: usize = due.iter().map(|c| c.due_today).sum();
+    let overdue: usize = due.iter().map(|c| c.overdue).sum();
     
-    // Add each todo as a VTODO
-    for todo in todos {
-        calendar_content.push_str("BEGIN:VTODO\r\n");
-        calendar_content.push_str(&format!("UID:{}\r\n", todo.id));
-        calendar_content.push_str(&format!("SUMMARY:{}\r\n", escape_ical_text(&todo.title)));
-        
-        if !todo.description.is_empty() {
-            calendar_content.push_str(&format!("DESCRIPTION:{}\r\n", escape_ical_text(&todo.description)));
+    if due_today + overdue > 0 {
+        use tauri_plugin_notification::NotificationExt;
+        let body = format!("{} due today, {} overdue", due_today, overdue);
+        if let Err(e) = app.notification().builder().title("2DO").body(body).show() {
+            log::warn!("Failed to show due todos notification: {}", e);
         }
-        
-        // Status
-        if todo.completed {
-            calendar_content.push_str("STATUS:COMPLETED\r\n");
-        } else {
-            calendar_content.push_str("STATUS:NEEDS-ACTION\r\n");
+    }
+    
+    Ok(due)
+}
+
+// Enable or disable the startup due todos notification
+#[tauri::command]
+fn set_due_notifications(enabled: bool) -> Result<(), String> {
+    let mut settings = load_settings();
+    settings.notifications_enabled = enabled;
+    save_settings(settings)
+}
+
+// Count open todos due on or before `today` in every calendar of a directory
+fn collect_due_todos(calendars_dir: &Path, today: NaiveDate) -> Result<Vec<DueCalendar>, String> {
+    let entries = fs::read_dir(calendars_dir)
+        .map_err(|e| format!("Failed to read calendars directory: {}", e))?;
+    let mut due = Vec::new();
+    
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let path = entry.path();
+        if !is_calendar_file(&path) {
+            continue;
         }
         
-        // Priority (convert back to iCalendar format)
-        let priority = match todo.priority.as_str() {
-            "high" => "1",
-            "medium" => "5", 
-            "low" => "9",
-            _ => "5",
+        let todos = match parse_todos_from_file(&path) {
+            Ok(todos) => todos,
+            Err(e) => {
+                log::warn!("Skipping {:?} while collecting due todos: {}", path, e);
+                continue;
+            }
         };
-        calendar_content.push_str(&format!("PRIORITY:{}\r\n", priority));
-        
-        // Category
-        if let Some(category) = &todo.category {
-            calendar_content.push_str(&format!("CATEGORIES:{}\r\n", escape_ical_text(category)));
-        }
         
-        // Due date
-        if let Some(due_date) = &todo.due_date {
-            if let Ok(date) = NaiveDate::parse_from_str(due_date, "%Y-%m-%d") {
-                calendar_content.push_str(&format!(
-                    "DUE:{:04}{:02}{:02}\r\n",
-                    date.year(), date.month(), date.day()
-                ));
+        let mut calendar = DueCalendar {
+            name: calendar_stem(&path),
+            path: path.to_string_lossy().to_string(),
+            due_today: 0,
+            overdue: 0,
+        };
+        for todo in todos.iter().filter(|t| !t.completed && t.trashed_at.is_none()) {
+            let Some(date) = todo.due_day() else {
+                continue;
+            };
+            if date == today {
+                calendar.due_today += 1;
+            } else if date < today {
+                calendar.overdue += 1;
             }
         }
         
-        // Created date
-        if let Some(created_at) = &todo.created_at {
-            if let Ok(date) = NaiveDate::parse_from_str(created_at, "%Y-%m-%d") {
-                calendar_content.push_str(&format!(
-                    "CREATED:{:04}{:02}{:02}\r\n",
-                    date.year(), date.month(), date.day()
-                ));
-            } else if let Ok(dt) = NaiveDateTime::parse_from_str(created_at, "%Y-%m-%dT%H:%M:%S") {
-                calendar_content.push_str(&format!(
-                    "CREATED:{:04}{:02}{:02}T{:02}{:02}{:02}Z\r\n",
-                    dt.year(), dt.month(), dt.day(), dt.hour(), dt.minute(), dt.second()
-                ));
+        if calendar.due_today + calendar.overdue > 0 {
+            due.push(calendar);
+        }
+    }
+    
+    due.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(due)
+}
+
+// Number of open todos due today across all calendars (shown in the tray)
+fn count_due_today() -> Result<usize, String> {
+    let today = chrono::Local::now().date_naive();
+    let due = collect_due_todos(&get_calendars_dir()?, today)?;
+    Ok(due.iter().map(|c| c.due_today).sum())
+}
+
+const TRAY_ID: &str = "main-tray";
+
+// How often the tray tooltip is recomputed in the background
+const TRAY_REFRESH_SECS: u64 = 300;
+
+// Create the tray icon; clicking it shows or hides the main window
+fn setup_tray(app: &tauri::App) -> tauri::Result<()> {
+    use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+    
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("2DO")
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Up, .. } = event {
+                if let Some(window) = tray.app_handle().get_webview_window("main") {
+                    if window.is_visible().unwrap_or(false) {
+                        let _ = window.hide();
+                    } else {
+                        let _ = window.show();
+                        let _ = window.set_focus();
+                    }
+                }
             }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+    
+    let handle = app.handle().clone();
+    std::thread::spawn(move || loop {
+        refresh_tray(&handle);
+        std::thread::sleep(std::time::Duration::from_secs(TRAY_REFRESH_SECS));
+    });
+    Ok(())
+}
+
+// Update the tray tooltip with the number of todos due today
+fn refresh_tray(app: &tauri::AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let tooltip = match count_due_today() {
+        Ok(0) => "2DO: nothing due today".to_string(),
+        Ok(count) => format!("2DO: {} due today", count),
+        Err(e) => {
+            log::warn!("Failed to count todos due today: {}", e);
+            "2DO".to_string()
         }
+    };
+    if let Err(e) = tray.set_tooltip(Some(tooltip)) {
+        log::warn!("Failed to update tray tooltip: {}", e);
+    }
+}
+
+// Event emitted when a calendar file in the calendars directory changes on disk
+const CALENDAR_CHANGED_EVENT: &str = "calendar-changed";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CalendarChanged {
+    pub path: String,
+    pub modified: Option<String>, // Modification token; None if the file was removed
+}
+
+// The running calendars directory watcher, if auto-reload is on; dropping it stops watching
+#[derive(Default)]
+pub struct CalendarWatcher(Mutex<Option<notify::RecommendedWatcher>>);
+
+// Turn watching the calendars directory for external changes on or off, and
+// remember the choice
+#[tauri::command]
+fn set_auto_reload(enabled: bool, app: tauri::AppHandle) -> Result<(), String> {
+    let mut settings = load_settings();
+    settings.auto_reload = enabled;
+    save_settings(settings)?;
+    
+    if enabled {
+        start_calendar_watcher(&app)
+    } else {
+        stop_calendar_watcher(&app);
+        Ok(())
+    }
+}
+
+#[tauri::command]
+fn is_auto_reload_enabled() -> bool {
+    load_settings().auto_reload
+}
+
+impl CalendarWatcher {
+    // (Re)start watching `dir`, passing every calendar file created, modified or
+    // removed in it to `on_change`
+    fn watch(&self, dir: &Path, on_change: impl Fn(CalendarChanged) + Send + 'static) -> Result<(), String> {
+        use notify::Watcher;
         
-        // Timestamp - use a simple approach to avoid formatting issues
-        let now = Utc::now();
-        let timestamp = format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", 
-            now.year(), now.month(), now.day(),
-            now.hour(), now.minute(), now.second());
-        calendar_content.push_str(&format!("DTSTAMP:{}\r\n", timestamp));
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+            let event = match result {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("Calendar watcher error: {}", e);
+                    return;
+                }
+            };
+            if !matches!(
+                event.kind,
+                notify::EventKind::Create(_) | notify::EventKind::Modify(_) | notify::EventKind::Remove(_)
+            ) {
+                return;
+            }
+            for path in event.paths.iter().filter(|path| is_calendar_file(path)) {
+                on_change(CalendarChanged {
+                    path: path.to_string_lossy().to_string(),
+                    modified: file_modified_time(path).ok().map(modified_token),
+                });
+            }
+        })
+        .map_err(|e| format!("Failed to create calendar watcher: {}", e))?;
+        watcher
+            .watch(dir, notify::RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch {:?}: {}", dir, e))?;
         
-        calendar_content.push_str("END:VTODO\r\n");
+        let mut current = self.0.lock().map_err(|_| "Calendar watcher state is poisoned".to_string())?;
+        *current = Some(watcher);
+        log::info!("Watching {:?} for calendar changes", dir);
+        Ok(())
     }
     
-    // End iCalendar
-    calendar_content.push_str("END:VCALENDAR\r\n");
-    
-    // Write to file
-    eprintln!("Writing calendar content ({} bytes) to file", calendar_content.len());
-    fs::write(&calendar_path, calendar_content)
-        .map_err(|e| format!("Failed to write calendar file: {}", e))?;
+    fn stop(&self) {
+        if let Ok(mut current) = self.0.lock() {
+            if current.take().is_some() {
+                log::info!("Stopped watching for calendar changes");
+            }
+        }
+    }
+}
+
+// (Re)start watching the calendars directory, emitting `calendar-changed` for
+// every calendar file created, modified or removed
+fn start_calendar_watcher(app: &tauri::AppHandle) -> Result<(), String> {
+    use tauri::Emitter;
     
-    eprintln!("Successfully saved calendar file");
-    Ok(())
+    let handle = app.clone();
+    app.state::<CalendarWatcher>().watch(&get_calendars_dir()?, move |changed| {
+        if let Err(e) = handle.emit(CALENDAR_CHANGED_EVENT, changed) {
+            log::warn!("Failed to emit calendar change: {}", e);
+        }
+    })
 }
 
-// Helper function to escape text for iCalendar format
-fn escape_ical_text(text: &str) -> String {
-    text.replace("\\", "\\\\")
-        .replace(";", "\\;")
-        .replace(",", "\\,")
-        .replace("\n", "\\n")
-        .replace("\r", "")
-        .replace("\0", "") // Remove null characters
-        .chars()
-        .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
-        .collect()
+fn stop_calendar_watcher(app: &tauri::AppHandle) {
+    app.state::<CalendarWatcher>().stop();
 }
 
-// Helper to unescape iCalendar text per RFC 5545
-// - \n or \N => newline
-// - \; => ;, \, => ,
-// - \\ => \
-fn unescape_ical_text(text: &str) -> String {
-    // First, reduce doubled backslashes to single to normalize over-escaped inputs
-    let mut s = text.to_string();
-    loop {
-        let collapsed = s.replace("\\\\", "\\");
-        if collapsed == s { break; }
-        s = collapsed;
+// Calendar files are plain `.ics` or gzip-compressed `.ics.gz`
+fn is_calendar_file(path: &Path) -> bool {
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+    name.ends_with(".ics") || name.ends_with(".ics.gz")
+}
+
+fn is_gzip_calendar(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|s| s.to_str())
+        .map(|name| name.to_ascii_lowercase().ends_with(".ics.gz"))
+        .unwrap_or(false)
+}
+
+// Calendar display name: the file name without its .ics / .ics.gz extension
+fn calendar_stem(path: &Path) -> String {
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("Unknown");
+    let lower = name.to_ascii_lowercase();
+    let stem_len = if lower.ends_with(".ics.gz") {
+        name.len() - ".ics.gz".len()
+    } else if lower.ends_with(".ics") {
+        name.len() - ".ics".len()
+    } else {
+        name.len()
+    };
+    name[..stem_len].to_string()
+}
+
+// Write calendar text, gzip-compressing it for .ics.gz files
+fn write_calendar_file(path: &Path, content: &str) -> Result<(), String> {
+    let bytes = if is_gzip_calendar(path) {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(content.as_bytes())
+            .map_err(|e| format!("Failed to compress calendar file: {}", e))?;
+        encoder
+            .finish()
+            .map_err(|e| format!("Failed to compress calendar file: {}", e))?
+    } else {
+        content.as_bytes().to_vec()
+    };
+    fs::write(path, bytes).map_err(|e| format!("Failed to write calendar file: {}", e))
+}
+
+// UTF-8 byte order mark written by some Windows tools
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+// Read a calendar file into UTF-8 text, transcoding lines that declare a CHARSET
+fn read_calendar_file(path: &Path) -> Result<String, String> {
+    let mut bytes = fs::read(path)
+        .map_err(|e| format!("Failed to read calendar file: {}", e))?;
+    if is_gzip_calendar(path) {
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut decompressed)
+            .map_err(|e| format!("Failed to decompress calendar file: {}", e))?;
+        bytes = decompressed;
+    }
+    let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(&bytes);
+    let (content, lossy_lines) = decode_calendar_bytes(bytes);
+    if !lossy_lines.is_empty() {
+        log::warn!(
+            "{:?} contains invalid UTF-8 on line(s) {:?}; replacement characters were introduced",
+            path, lossy_lines
+        );
     }
-    s = s.replace("\\n", "\n");
-    s = s.replace("\\N", "\n");
-    s = s.replace("\\;", ";");
-    s = s.replace("\\,", ",");
-    s
+    Ok(content)
 }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![greet, get_calendars_path, list_calendars, load_todos_from_calendar, save_todos_to_calendar, create_calendar])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+// Decode raw calendar bytes line by line. Valid UTF-8 lines are kept as-is;
+// other lines are transcoded using the CHARSET parameter of their property,
+// or converted lossily. Returns the text and the (1-based) lossy line numbers.
+fn decode_calendar_bytes(bytes: &[u8]) -> (String, Vec<usize>) {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return (text.to_string(), Vec::new());
+    }
+
+    let mut lossy_lines = Vec::new();
+
+    let mut content = String::with_capacity(bytes.len());
+    for (index, line) in bytes.split_inclusive(|&b| b == b'\n').enumerate() {
+        if let Ok(text) = std::str::from_utf8(line) {
+            content.push_str(text);
+            continue;
+        }
+
+        // The property name and parameters are ASCII, so look for CHARSET before the colon
+        let head_end = line.iter().position(|&b| b == b':').unwrap_or(line.len());
+        let head = String::from_utf8_lossy(&line[..head_end]);
+        let params = parse_property_params(&head);
+        match param_value(&params, "CHARSET").and_then(|c| encoding_rs::Encoding::for_label(c.as_bytes())) {
+            Some(encoding) => content.push_str(&encoding.decode_without_bom_handling(line).0),
+            None => {
+                lossy_lines.push(index + 1);
+                content.push_str(&String::from_utf8_lossy(line));
+            }
+        }
+    }
+
+    (content, lossy_lines)
+}
+
+// Resolve a frontend-supplied calendar path and verify it lies inside the calendars
+// directory. Relative paths are taken relative to that directory; writes must target
+// an .ics file (which may not exist yet).
+fn resolve_calendar_path(calendar_path: &str, for_write: bool) -> Result<PathBuf, String> {
+    let calendars_dir = fs::canonicalize(get_calendars_dir()?)
+        .map_err(|e| format!("Failed to resolve calendars directory: {}", e))?;
+
+    let requested = Path::new(calendar_path);
+    let requested = if requested.is_absolute() {
+        requested.to_path_buf()
+    } else {
+        calendars_dir.join(requested)
+    };
+
+    let resolved = match fs::canonicalize(&requested) {
+        Ok(resolved) => resolved,
+        Err(_) if for_write => {
+            let file_name = requested.file_name().ok_or("Invalid calendar path")?;
+            let parent = requested.parent().ok_or("Invalid calendar path")?;
+            fs::canonicalize(parent)
+                .map_err(|e| format!("Failed to resolve calendar path: {}", e))?
+                .join(file_name)
+        }
+        Err(e) => return Err(format!("Failed to resolve calendar path: {}", e)),
+    };
+
+    if !resolved.starts_with(&calendars_dir) {
+        return Err(format!("Calendar path is outside the calendars directory: {}", calendar_path));
+    }
+    if for_write && !is_calendar_file(&resolved) {
+        return Err(format!("Calendar path must be an .ics or .ics.gz file: {}", calendar_path));
+    }
+
+    Ok(resolved)
+}
+
+// Total and open VTODO counts, looking only at each block's STATUS line
+fn todo_counts(content: &str) -> (usize, usize) {
+    let mut total = 0;
+    let mut open = 0;
+    for (_, block) in vtodo_blocks(content) {
+        total += 1;
+        let closed = block.iter().any(|line| {
+            split_property_line(line.trim()).is_some_and(|(name, value)| {
+                name.split(';').next().unwrap_or(name).eq_ignore_ascii_case("STATUS")
+                    && matches!(value.trim().to_ascii_uppercase().as_str(), "COMPLETED" | "CANCELLED")
+            })
+        });
+        if !closed {
+            open += 1;
+        }
+    }
+    (total, open)
+}
+
+// In-memory cache of parsed calendars, keyed by path and validated by mtime
+#[derive(Default)]
+pub struct TodoCache(Mutex<HashMap<PathBuf, (SystemTime, Vec<Todo>)>>);
+
+impl TodoCache {
+    // Return the cached todos if the entry matches the given modification time
+    fn get(&self, path: &Path, modified: SystemTime) -> Option<Vec<Todo>> {
+        let entries = self.0.lock().ok()?;
+        match entries.get(path) {
+            Some((cached_modified, todos)) if *cached_modified == modified => Some(todos.clone()),
+            _ => None,
+        }
+    }
+
+    fn insert(&self, path: PathBuf, modified: SystemTime, todos: Vec<Todo>) {
+        if let Ok(mut entries) = self.0.lock() {
+            entries.insert(path, (modified, todos));
+        }
+    }
+
+    fn invalidate(&self, path: &Path) {
+        if let Ok(mut entries) = self.0.lock() {
+            entries.remove(path);
+        }
+    }
+}
+
+// Prefix of the error returned when a save would clobber external changes
+const CONFLICT_ERROR: &str = "Conflict";
+
+// Encode a modification time as an opaque token (nanoseconds since the epoch)
+fn modified_token(modified: SystemTime) -> String {
+    modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos().to_string())
+        .unwrap_or_default()
+}
+
+// Fail with a Conflict error if the file changed since `expected_modified` was taken
+fn check_not_modified(path: &Path, expected_modified: Option<&str>) -> Result<(), String> {
+    let Some(expected) = expected_modified else {
+        return Ok(());
+    };
+    if !path.exists() {
+        return Ok(());
+    }
+    let current = modified_token(file_modified_time(path)?);
+    if current != expected {
+        return Err(format!(
+            "{}: calendar file was modified externally since it was loaded",
+            CONFLICT_ERROR
+        ));
+    }
+    Ok(())
+}
+
+// Prefix of the error returned when another writer holds the calendar lock
+const BUSY_ERROR: &str = "Busy";
+
+// Locks older than this are considered abandoned and may be reclaimed
+const STALE_LOCK_SECS: i64 = 30;
+
+// Advisory `<name>.ics.lock` file held while a calendar is being written.
+// The lock is removed when the guard is dropped.
+struct CalendarLock {
+    path: PathBuf,
+}
+
+impl CalendarLock {
+    fn acquire(calendar_path: &Path) -> Result<Self, String> {
+        let mut lock_name = calendar_path.as_os_str().to_owned();
+        lock_name.push(".lock");
+        let path = PathBuf::from(lock_name);
+
+        for _ in 0..2 {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let contents = format!("{} {}\n", std::process::id(), Utc::now().timestamp());
+                    file.write_all(contents.as_bytes())
+                        .map_err(|e| format!("Failed to write lock file: {}", e))?;
+                    return Ok(CalendarLock { path });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if !lock_is_stale(&path) {
+                        return Err(format!(
+                            "{}: calendar is being written by another process ({:?})",
+                            BUSY_ERROR, path
+                        ));
+                    }
+                    log::warn!("Reclaiming stale lock file: {:?}", path);
+                    let _ = fs::remove_file(&path);
+                }
+                Err(e) => return Err(format!("Failed to create lock file: {}", e)),
+            }
+        }
+
+        Err(format!("{}: failed to acquire calendar lock ({:?})", BUSY_ERROR, path))
+    }
+}
+
+impl Drop for CalendarLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// A lock is stale if its recorded timestamp (or, failing that, its mtime) is too old
+fn lock_is_stale(lock_path: &Path) -> bool {
+    let recorded = fs::read_to_string(lock_path).ok().and_then(|contents| {
+        contents.split_whitespace().nth(1).and_then(|ts| ts.parse::<i64>().ok())
+    });
+    let created = recorded.or_else(|| {
+        file_modified_time(lock_path)
+            .ok()
+            .and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64)
+    });
+    match created {
+        Some(created) => Utc::now().timestamp() - created > STALE_LOCK_SECS,
+        None => true,
+    }
+}
+
+// Get a file's last modification time
+fn file_modified_time(path: &Path) -> Result<SystemTime, String> {
+    fs::metadata(path)
+        .map_err(|e| format!("Failed to read file metadata: {}", e))?
+        .modified()
+        .map_err(|e| format!("Failed to get modification time: {}", e))
+}
+
+// Load todos from a specific calendar file, reusing the cached parse if unchanged
+#[tauri::command]
+async fn load_todos_from_calendar(
+    calendar_path: String,
+    app: tauri::AppHandle,
+    cache: tauri::State<'_, TodoCache>,
+) -> Result<Vec<Todo>, String> {
+    use tauri::Emitter;
+    
+    cached_todos(&calendar_path, &cache, |parsed, fraction| {
+        let progress = LoadProgress { calendar_path: calendar_path.clone(), parsed, fraction };
+        if let Err(e) = app.emit(LOAD_PROGRESS_EVENT, progress) {
+            log::warn!("Failed to emit load progress: {}", e);
+        }
+    })
+}
+
+// Todos of a calendar from the cache, or parsed (reporting progress) if the file changed
+fn cached_todos(calendar_path: &str, cache: &TodoCache, on_progress: impl FnMut(usize, f64)) -> Result<Vec<Todo>, String> {
+    let path = resolve_calendar_path(calendar_path, false)?;
+    let modified = file_modified_time(&path)?;
+    
+    if let Some(todos) = cache.get(&path, modified) {
+        log::debug!("Using cached todos for calendar: {}", calendar_path);
+        return Ok(todos);
+    }
+    
+    let content = read_calendar_file(&path)?;
+    let todos = parse_todos_with_progress(&content, &calendar_stem(&path), on_progress);
+    cache.insert(path, modified, todos.clone());
+    Ok(todos)
+}
+
+// Todos of a calendar as queries see them: trashed todos are left out
+fn visible_todos(calendar_path: &str, cache: &TodoCache) -> Result<Vec<Todo>, String> {
+    let mut todos = cached_todos(calendar_path, cache, |_, _| {})?;
+    todos.retain(|todo| todo.trashed_at.is_none());
+    Ok(todos)
+}
+
+// Event emitted while a large calendar is parsed, every PROGRESS_INTERVAL VTODOs
+const LOAD_PROGRESS_EVENT: &str = "load-progress";
+const PROGRESS_INTERVAL: usize = 500;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct LoadProgress {
+    pub calendar_path: String,
+    pub parsed: usize, // VTODOs parsed so far
+    pub fraction: f64, // Share of the file consumed, 0.0 to 1.0
+}
+
+// Share of `total` bytes covered by `consumed`, clamped to [0, 1]; an empty input is complete
+fn progress_fraction(consumed: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 1.0;
+    }
+    (consumed as f64 / total as f64).min(1.0)
+}
+
+// Events of stream_todos: batches of up to STREAM_BATCH_SIZE todos, then a final count
+const TODOS_BATCH_EVENT: &str = "todos-batch";
+const TODOS_DONE_EVENT: &str = "todos-done";
+const STREAM_BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TodosBatch {
+    pub calendar_path: String,
+    pub offset: usize, // Index of the batch's first todo
+    pub todos: Vec<Todo>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TodosDone {
+    pub calendar_path: String,
+    pub total: usize,
+}
+
+// Deliver a calendar's todos as `todos-batch` events while the file is parsed,
+// followed by `todos-done`, so large calendars can render progressively.
+// Returns the number of todos sent. load_todos_from_calendar stays the simple
+// path for small files.
+#[tauri::command]
+async fn stream_todos(calendar_path: String, app: tauri::AppHandle, cache: tauri::State<'_, TodoCache>) -> Result<usize, String> {
+    use tauri::Emitter;
+    
+    let path = resolve_calendar_path(&calendar_path, false)?;
+    let modified = file_modified_time(&path)?;
+    let emit_batch = |offset: usize, todos: Vec<Todo>| {
+        let batch = TodosBatch { calendar_path: calendar_path.clone(), offset, todos };
+        if let Err(e) = app.emit(TODOS_BATCH_EVENT, batch) {
+            log::warn!("Failed to emit todos batch: {}", e);
+        }
+    };
+    
+    let total = match cache.get(&path, modified) {
+        Some(todos) => for_each_batch(todos.into_iter(), STREAM_BATCH_SIZE, emit_batch),
+        None => {
+            let content = read_calendar_file(&path)?;
+            let calendar_name = calendar_stem(&path);
+            let mut parsed = Vec::new();
+            let todos = vtodo_blocks(&content).filter_map(|(calendar_block, block)| {
+                match parse_vtodo_from_lines(&block, &calendar_name) {
+                    Ok(mut todo) => {
+                        todo.calendar_block = calendar_block;
+                        parsed.push(todo.clone());
+                        Some(todo)
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to parse VTODO: {}", e);
+                        None
+                    }
+                }
+            });
+            let total = for_each_batch(todos, STREAM_BATCH_SIZE, emit_batch);
+            cache.insert(path, modified, parsed);
+            total
+        }
+    };
+    
+    let done = TodosDone { calendar_path: calendar_path.clone(), total };
+    app.emit(TODOS_DONE_EVENT, done).map_err(|e| format!("Failed to emit todos-done: {}", e))?;
+    Ok(total)
+}
+
+// Hand items to `emit` in chunks of `size` (the last may be shorter) along with
+// each chunk's offset, returning the number of items
+fn for_each_batch<T>(items: impl Iterator<Item = T>, size: usize, mut emit: impl FnMut(usize, Vec<T>)) -> usize {
+    let size = size.max(1);
+    let mut batch = Vec::with_capacity(size);
+    let mut total = 0;
+    for item in items {
+        batch.push(item);
+        total += 1;
+        if batch.len() == size {
+            emit(total - size, std::mem::replace(&mut batch, Vec::with_capacity(size)));
+        }
+    }
+    if !batch.is_empty() {
+        emit(total - batch.len(), batch);
+    }
+    total
+}
+
+// Todos together with the file's modification token, for conflict detection on save
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TodosWithMeta {
+    pub todos: Vec<Todo>,
+    pub modified: String,
+    pub method: Option<String>, // METHOD of the calendar, e.g. REQUEST for an invitation
+    #[serde(default)]
+    pub warnings: Vec<String>, // Problems that may make the parse unreliable; loading still succeeds
+}
+
+// Load todos along with the file's modification token
+#[tauri::command]
+async fn load_todos_with_meta(
+    calendar_path: String,
+    app: tauri::AppHandle,
+    cache: tauri::State<'_, TodoCache>,
+) -> Result<TodosWithMeta, String> {
+    let path = resolve_calendar_path(&calendar_path, false)?;
+    let modified = modified_token(file_modified_time(&path)?);
+    let content = read_calendar_file(&path)?;
+    let method = calendar_method(&content);
+    let warnings = calendar_warnings(&content);
+    for warning in &warnings {
+        log::warn!("{:?}: {}", path, warning);
+    }
+    let todos = load_todos_from_calendar(calendar_path, app, cache).await?;
+    Ok(TodosWithMeta { todos, modified, method, warnings })
+}
+
+// One page of todos plus the total number of VTODOs in the file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TodoPage {
+    pub todos: Vec<Todo>,
+    pub total: usize,
+}
+
+// Load a window of todos, only parsing the VTODO blocks inside [offset, offset + limit)
+#[tauri::command]
+async fn load_todos_page(calendar_path: String, offset: usize, limit: usize) -> Result<TodoPage, String> {
+    let path = resolve_calendar_path(&calendar_path, false)?;
+    let content = read_calendar_file(&path)?;
+    let calendar_name = calendar_stem(&path);
+    
+    let end = offset.saturating_add(limit);
+    let mut todos = Vec::new();
+    let mut total = 0;
+    
+    for (calendar_block, block) in vtodo_blocks(&content) {
+        // Blocks outside the window are only counted, never parsed
+        if total >= offset && total < end {
+            match parse_vtodo_from_lines(&block, &calendar_name) {
+                Ok(mut todo) => {
+                    todo.calendar_block = calendar_block;
+                    todos.push(todo);
+                }
+                Err(e) => log::warn!("Failed to parse VTODO {}: {}", total + 1, e),
+            }
+        }
+        total += 1;
+    }
+    
+    Ok(TodoPage { todos, total })
+}
+
+// Optional predicates for filter_todos; every field that is set must match
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TodoFilter {
+    pub completed: Option<bool>,
+    pub priority: Option<String>,
+    pub category: Option<String>,
+    pub due_before: Option<String>,
+    pub due_after: Option<String>,
+    pub text: Option<String>,
+}
+
+impl TodoFilter {
+    // Due dates are date-only (YYYY-MM-DD), so bounds given as datetimes are
+    // truncated to their date. Both bounds are exclusive, and todos without a
+    // due date never match a due bound.
+    fn matches(&self, todo: &Todo) -> Result<bool, String> {
+        if let Some(completed) = self.completed {
+            if todo.completed != completed {
+                return Ok(false);
+            }
+        }
+        
+        if let Some(priority) = &self.priority {
+            if !todo.priority.eq_ignore_ascii_case(priority) {
+                return Ok(false);
+            }
+        }
+        
+        if let Some(category) = &self.category {
+            let has_category = todo_categories(todo).iter().any(|c| c.eq_ignore_ascii_case(category.trim()));
+            if !has_category {
+                return Ok(false);
+            }
+        }
+        
+        if self.due_before.is_some() || self.due_after.is_some() {
+            let Some(due) = todo.due_day() else {
+                return Ok(false);
+            };
+            if let Some(before) = self.due_before.as_deref() {
+                if due >= parse_filter_date(before)? {
+                    return Ok(false);
+                }
+            }
+            if let Some(after) = self.due_after.as_deref() {
+                if due <= parse_filter_date(after)? {
+                    return Ok(false);
+                }
+            }
+        }
+        
+        if let Some(text) = &self.text {
+            let needle = text.to_lowercase();
+            if !todo.title.to_lowercase().contains(&needle) && !todo.description.to_lowercase().contains(&needle) {
+                return Ok(false);
+            }
+        }
+        
+        Ok(true)
+    }
+}
+
+// Parse a filter bound given as YYYY-MM-DD or an ISO datetime, keeping only the date
+fn parse_filter_date(value: &str) -> Result<NaiveDate, String> {
+    let date_part = value.trim().get(0..10).unwrap_or(value);
+    NaiveDate::parse_from_str(date_part, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid filter date '{}': {}", value, e))
+}
+
+// Load the todos of a calendar that match all of the filter's predicates
+#[tauri::command]
+async fn filter_todos(calendar_path: String, filter: TodoFilter, cache: tauri::State<'_, TodoCache>) -> Result<Vec<Todo>, String> {
+    let todos = visible_todos(&calendar_path, &cache)?;
+    let mut matching = Vec::new();
+    for todo in todos {
+        if filter.matches(&todo)? {
+            matching.push(todo);
+        }
+    }
+    Ok(matching)
+}
+
+// Field sort_todos orders by
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    Due,
+    Priority,
+    Created,
+    Title,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+// Load a calendar's todos sorted by `key`. Priority follows iCalendar numbering,
+// so ascending puts high first. Todos missing the key's value (no due date or
+// created date) always sort last, and ties are broken by UID so the order is stable.
+// With `completed_last`, open todos come before completed ones.
+#[tauri::command]
+async fn sort_todos(
+    calendar_path: String,
+    key: SortKey,
+    direction: SortDirection,
+    completed_last: Option<bool>,
+    cache: tauri::State<'_, TodoCache>,
+) -> Result<Vec<Todo>, String> {
+    let mut todos = visible_todos(&calendar_path, &cache)?;
+    sort_todo_list(&mut todos, key, direction);
+    if completed_last.unwrap_or(false) {
+        partition_completed_last(&mut todos);
+    }
+    Ok(todos)
+}
+
+// Move completed todos after open ones, keeping the order within each group
+fn partition_completed_last(todos: &mut [Todo]) {
+    // sort_by_key is stable, and false (open) orders before true (completed)
+    todos.sort_by_key(|todo| todo.completed);
+}
+
+// Sort rank of a priority, most urgent first
+fn priority_rank(priority: &str) -> u8 {
+    match priority {
+        "high" => 1,
+        "low" => 9,
+        _ => 5,
+    }
+}
+
+fn sort_todo_list(todos: &mut [Todo], key: SortKey, direction: SortDirection) {
+    use std::cmp::Ordering;
+    
+    // None sorts after Some regardless of direction
+    fn present_first<T: Ord>(a: Option<T>, b: Option<T>, direction: SortDirection) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) if direction == SortDirection::Desc => b.cmp(&a),
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+    
+    todos.sort_by(|a, b| {
+        let ordering = match key {
+            // All-day todos come before timed ones on the same day
+            SortKey::Due => present_first(
+                a.due_date.as_deref().map(|d| (d, a.due_time.as_deref())),
+                b.due_date.as_deref().map(|d| (d, b.due_time.as_deref())),
+                direction,
+            ),
+            SortKey::Created => present_first(a.created_at.as_deref(), b.created_at.as_deref(), direction),
+            SortKey::Priority => present_first(
+                Some(priority_rank(&a.priority)),
+                Some(priority_rank(&b.priority)),
+                direction,
+            ),
+            SortKey::Title => present_first(
+                Some(a.title.to_lowercase()),
+                Some(b.title.to_lowercase()),
+                direction,
+            ),
+        };
+        ordering.then_with(|| a.id.cmp(&b.id))
+    });
+}
+
+// Open todos due today (local time) in every calendar, most urgent first and
+// then by title. Each todo's calendar_name says where it lives.
+#[tauri::command]
+async fn today_agenda() -> Result<Vec<Todo>, String> {
+    let today = chrono::Local::now().date_naive();
+    let mut agenda = Vec::new();
+    for (path, todos) in parse_calendars_concurrently(calendar_files(&get_calendars_dir()?)?) {
+        match todos {
+            Ok(todos) => agenda.extend(
+                todos
+                    .into_iter()
+                    .filter(|t| !t.completed && t.trashed_at.is_none() && t.due_day() == Some(today)),
+            ),
+            Err(e) => log::warn!("Skipping {:?} while building today's agenda: {}", path, e),
+        }
+    }
+    agenda.sort_by(|a, b| {
+        priority_rank(&a.priority)
+            .cmp(&priority_rank(&b.priority))
+            .then_with(|| a.title.to_lowercase().cmp(&b.title.to_lowercase()))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    Ok(agenda)
+}
+
+// Open todos due between today and today + `days` (inclusive), soonest first.
+// Timed due dates are compared by their date, so a todo due at any time on the
+// last day is included.
+#[tauri::command]
+async fn list_due_within(path: String, days: u32, cache: tauri::State<'_, TodoCache>) -> Result<Vec<Todo>, String> {
+    let today = chrono::Local::now().date_naive();
+    let last = today
+        .checked_add_days(chrono::Days::new(days.into()))
+        .ok_or("Date window is out of range")?;
+    
+    let mut due: Vec<Todo> = visible_todos(&path, &cache)?
+        .into_iter()
+        .filter(|todo| !todo.completed)
+        .filter(|todo| todo.due_day().is_some_and(|d| d >= today && d <= last))
+        .collect();
+    sort_todo_list(&mut due, SortKey::Due, SortDirection::Asc);
+    Ok(due)
+}
+
+// Todos whose parent (RELATED-TO) is not in the same calendar, e.g. because
+// the parent was deleted
+#[tauri::command]
+async fn find_orphans(calendar_path: String, cache: tauri::State<'_, TodoCache>) -> Result<Vec<Todo>, String> {
+    let todos = visible_todos(&calendar_path, &cache)?;
+    let uids: std::collections::HashSet<&str> = todos.iter().map(|todo| todo.id.as_str()).collect();
+    let orphans = todos
+        .iter()
+        .filter(|todo| todo.parent_uid.as_deref().is_some_and(|parent| !uids.contains(parent)))
+        .cloned()
+        .collect();
+    Ok(orphans)
+}
+
+// Render the parent/child tree of a calendar's todos as an indented text
+// outline: two spaces per level, a [x] or [ ] marker, siblings by due date
+#[tauri::command]
+async fn outline_todos(calendar_path: String, cache: tauri::State<'_, TodoCache>) -> Result<String, String> {
+    let mut todos = visible_todos(&calendar_path, &cache)?;
+    sort_todo_list(&mut todos, SortKey::Due, SortDirection::Asc);
+    
+    let uids: std::collections::HashSet<&str> = todos.iter().map(|todo| todo.id.as_str()).collect();
+    let mut children: HashMap<&str, Vec<&Todo>> = HashMap::new();
+    let mut roots = Vec::new();
+    for todo in &todos {
+        // Orphans (and todos claiming to be their own parent) start a tree of their own
+        match todo.parent_uid.as_deref().filter(|parent| uids.contains(parent) && *parent != todo.id) {
+            Some(parent) => children.entry(parent).or_default().push(todo),
+            None => roots.push(todo),
+        }
+    }
+    
+    let mut outline = String::new();
+    let mut visited = std::collections::HashSet::new();
+    for root in roots {
+        write_outline(root, 0, &children, &mut visited, &mut outline);
+    }
+    // Todos in a parent cycle are unreachable from any root; list each cycle from its first member
+    for todo in &todos {
+        write_outline(todo, 0, &children, &mut visited, &mut outline);
+    }
+    Ok(outline)
+}
+
+// Append a todo and its subtree to the outline, skipping todos already written
+fn write_outline<'a>(
+    todo: &'a Todo,
+    depth: usize,
+    children: &HashMap<&str, Vec<&'a Todo>>,
+    visited: &mut std::collections::HashSet<&'a str>,
+    out: &mut String,
+) {
+    if !visited.insert(todo.id.as_str()) {
+        return;
+    }
+    let marker = if todo.completed { "[x]" } else { "[ ]" };
+    out.push_str(&format!("{}{} {}", "  ".repeat(depth), marker, todo.title));
+    if let Some(due) = todo.due_day() {
+        out.push_str(&format!(" (due {})", due.format("%Y-%m-%d")));
+    }
+    out.push('\n');
+    for &child in children.get(todo.id.as_str()).into_iter().flatten() {
+        write_outline(child, depth + 1, children, visited, out);
+    }
+}
+
+// Bucket name for todos without any category
+const UNCATEGORIZED: &str = "Uncategorized";
+
+// Group a calendar's todos by category, ordered by due date within each group.
+// A todo with several categories appears in each of their groups.
+#[tauri::command]
+async fn group_by_category(
+    path: String,
+    cache: tauri::State<'_, TodoCache>,
+) -> Result<std::collections::BTreeMap<String, Vec<Todo>>, String> {
+    let mut groups: std::collections::BTreeMap<String, Vec<Todo>> = std::collections::BTreeMap::new();
+    for todo in visible_todos(&path, &cache)? {
+        let mut categories = todo_categories(&todo);
+        if categories.is_empty() {
+            categories.push(UNCATEGORIZED.to_string());
+        }
+        categories.sort();
+        categories.dedup();
+        for category in categories {
+            groups.entry(category).or_default().push(todo.clone());
+        }
+    }
+    for todos in groups.values_mut() {
+        sort_todo_list(todos, SortKey::Due, SortDirection::Asc);
+    }
+    Ok(groups)
+}
+
+// Rewrite category spellings across a calendar. `mapping` gives canonical
+// names (keys match case-insensitively; an empty name drops the category);
+// any other category takes the first spelling seen in the file, so "Work",
+// "work" and "WORK" collapse into one. Returns the number of todos changed.
+#[tauri::command]
+async fn normalize_categories(
+    calendar_path: String,
+    mapping: HashMap<String, String>,
+    cache: tauri::State<'_, TodoCache>,
+) -> Result<usize, String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    let calendar_name = calendar_stem(&path);
+    let now = format_ical_utc(&Utc::now().naive_utc());
+    
+    modify_calendar_file(&path, &cache, |sections| {
+        let mut canonical: HashMap<String, String> = mapping
+            .iter()
+            .map(|(from, to)| (from.trim().to_lowercase(), to.trim().to_string()))
+            .collect();
+        let mut parsed = Vec::new();
+        for component in sections.iter().flat_map(|s| &s.components).filter(|c| c.name == "VTODO") {
+            let categories = component.to_todo(&calendar_name).map(|todo| todo_categories(&todo)).unwrap_or_default();
+            for category in &categories {
+                canonical.entry(category.to_lowercase()).or_insert_with(|| category.clone());
+            }
+            parsed.push(categories);
+        }
+        
+        let mut changed = 0;
+        let vtodos = sections.iter_mut().flat_map(|s| s.components.iter_mut()).filter(|c| c.name == "VTODO");
+        for (component, categories) in vtodos.zip(parsed) {
+            let mut normalized: Vec<String> = Vec::with_capacity(categories.len());
+            for category in &categories {
+                let name = canonical.get(&category.to_lowercase()).unwrap_or(category);
+                if !name.is_empty() && !normalized.contains(name) {
+                    normalized.push(name.clone());
+                }
+            }
+            if normalized == categories {
+                continue;
+            }
+            
+            set_component_categories(component, &normalized, &now);
+            changed += 1;
+        }
+        Ok(changed)
+    })
+}
+
+// Add and remove categories on several todos in a single write. Names match
+// case-insensitively and are not duplicated. Returns how many todos changed.
+#[tauri::command]
+async fn bulk_tag(
+    calendar_path: String,
+    uids: Vec<String>,
+    add: Vec<String>,
+    remove: Vec<String>,
+    cache: tauri::State<'_, TodoCache>,
+) -> Result<usize, String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    let calendar_name = calendar_stem(&path);
+    let now = format_ical_utc(&Utc::now().naive_utc());
+    let remove: Vec<String> = remove.iter().map(|tag| tag.trim().to_lowercase()).collect();
+    
+    modify_calendar_file(&path, &cache, |sections| {
+        let mut changed = 0;
+        for_each_vtodo(sections, &uids, |component| {
+            let current = component.to_todo(&calendar_name).map(|todo| todo_categories(&todo)).unwrap_or_default();
+            let mut tags: Vec<String> = Vec::with_capacity(current.len() + add.len());
+            for tag in current.iter().chain(&add).map(|tag| tag.trim()) {
+                let folded = tag.to_lowercase();
+                if !tag.is_empty() && !remove.contains(&folded) && !tags.iter().any(|t| t.to_lowercase() == folded) {
+                    tags.push(tag.to_string());
+                }
+            }
+            if tags != current {
+                set_component_categories(component, &tags, &now);
+                changed += 1;
+            }
+        });
+        Ok(changed)
+    })
+}
+
+// Replace a raw VTODO's CATEGORIES (dropping the property when empty) and bump LAST-MODIFIED
+fn set_component_categories(component: &mut CalendarComponent, categories: &[String], now: &str) {
+    if categories.is_empty() {
+        component.remove_property("CATEGORIES");
+    } else {
+        component.set_property("CATEGORIES", categories_property(categories));
+    }
+    component.set_property("LAST-MODIFIED", format!("LAST-MODIFIED:{}", now));
+}
+
+// Every distinct category used in a calendar, sorted (for the tag picker)
+#[tauri::command]
+async fn list_categories(calendar_path: String, cache: tauri::State<'_, TodoCache>) -> Result<Vec<String>, String> {
+    let todos = visible_todos(&calendar_path, &cache)?;
+    Ok(category_counts(&todos).into_keys().collect())
+}
+
+// Number of todos using each category; trashed todos don't count, and a todo
+// listing a category twice counts once
+fn category_counts(todos: &[Todo]) -> std::collections::BTreeMap<String, usize> {
+    let mut counts = std::collections::BTreeMap::new();
+    for todo in todos.iter().filter(|t| t.trashed_at.is_none()) {
+        let mut categories = todo_categories(todo);
+        categories.sort();
+        categories.dedup();
+        for category in categories {
+            *counts.entry(category).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+// Every category used anywhere in the calendars directory with its number of
+// todos, sorted by name (for a global tag cloud). Unreadable calendars are skipped.
+#[tauri::command]
+async fn list_all_categories() -> Result<Vec<(String, usize)>, String> {
+    let mut totals: std::collections::BTreeMap<String, usize> = std::collections::BTreeMap::new();
+    for (path, todos) in parse_calendars_concurrently(calendar_files(&get_calendars_dir()?)?) {
+        let todos = match todos {
+            Ok(todos) => todos,
+            Err(e) => {
+                log::warn!("Skipping {:?} while listing categories: {}", path, e);
+                continue;
+            }
+        };
+        for (category, count) in category_counts(&todos) {
+            *totals.entry(category).or_insert(0) += count;
+        }
+    }
+    Ok(totals.into_iter().collect())
+}
+
+// A todo's categories, trimmed and without empty names
+fn todo_categories(todo: &Todo) -> Vec<String> {
+    todo.categories
+        .iter()
+        .map(|c| c.trim())
+        .filter(|c| !c.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// A CATEGORIES line listing every category, each escaped on its own so a comma
+// inside a name stays part of it
+fn categories_property(categories: &[String]) -> String {
+    let values: Vec<String> = categories.iter().map(|c| escape_ical_text(c)).collect();
+    format!("CATEGORIES:{}", values.join(","))
+}
+
+// A search hit with its relevance score
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScoredTodo {
+    pub todo: Todo,
+    pub score: i64,
+}
+
+// Hits scoring below this are dropped as noise
+const FUZZY_MIN_SCORE: i64 = 20;
+
+// Score given to a query word that is one typo (edit or swap) away from a word
+const TYPO_WORD_SCORE: i64 = 30;
+
+// Fuzzy search over titles and descriptions, best matches first. Title matches
+// count double, so they rank above description-only matches.
+#[tauri::command]
+async fn search_todos_fuzzy(
+    calendar_path: String,
+    query: String,
+    cache: tauri::State<'_, TodoCache>,
+) -> Result<Vec<ScoredTodo>, String> {
+    use fuzzy_matcher::skim::SkimMatcherV2;
+    
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    
+    let matcher = SkimMatcherV2::default().ignore_case();
+    let mut hits: Vec<ScoredTodo> = visible_todos(&calendar_path, &cache)?
+        .into_iter()
+        .filter_map(|todo| {
+            let title = fuzzy_score(&matcher, &todo.title, query) * 2;
+            let description = fuzzy_score(&matcher, &todo.description, query);
+            let score = title.max(description);
+            (score >= FUZZY_MIN_SCORE).then_some(ScoredTodo { todo, score })
+        })
+        .collect();
+    hits.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.todo.id.cmp(&b.todo.id)));
+    Ok(hits)
+}
+
+// Skim score of `query` in `text`, falling back to per-word typo matching
+// (which catches transposed letters the subsequence matcher misses)
+fn fuzzy_score(matcher: &fuzzy_matcher::skim::SkimMatcherV2, text: &str, query: &str) -> i64 {
+    use fuzzy_matcher::FuzzyMatcher;
+    
+    if let Some(score) = matcher.fuzzy_match(text, query) {
+        return score;
+    }
+    let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+    query
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .filter(|q| q.chars().count() >= 3 && words.iter().any(|w| typo_distance(w, q) <= 1))
+        .count() as i64
+        * TYPO_WORD_SCORE
+}
+
+// Optimal string alignment distance: edits plus adjacent transpositions
+fn typo_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+// The calendar files directly inside a directory
+fn calendar_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read calendars directory: {}", e))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let path = entry.path();
+        if is_calendar_file(&path) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+// Parse several calendar files at once, one thread per file; results keep the
+// order of `paths`
+fn parse_calendars_concurrently(paths: Vec<PathBuf>) -> Vec<(PathBuf, Result<Vec<Todo>, String>)> {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = paths
+            .iter()
+            .map(|path| scope.spawn(move || parse_todos_from_file(path)))
+            .collect();
+        paths
+            .iter()
+            .zip(handles)
+            .map(|(path, handle)| {
+                let todos = handle
+                    .join()
+                    .unwrap_or_else(|_| Err(format!("Parsing {:?} panicked", path)));
+                (path.clone(), todos)
+            })
+            .collect()
+    })
+}
+
+// Read and parse all todos from a calendar file
+fn parse_todos_from_file(path: &Path) -> Result<Vec<Todo>, String> {
+    let content = read_calendar_file(path)?;
+    Ok(parse_todos_from_content(&content, &calendar_stem(path)))
+}
+
+// Parse todos from pasted or piped iCalendar text without touching disk
+#[tauri::command]
+fn parse_ics_string(content: String, calendar_name: String) -> Result<Vec<Todo>, String> {
+    let content = content.strip_prefix('\u{FEFF}').unwrap_or(&content);
+    if !content.to_ascii_uppercase().contains("BEGIN:VTODO") {
+        return Err("No VTODO components found in the provided iCalendar content".to_string());
+    }
+    Ok(parse_todos_from_content(content, &calendar_name))
+}
+
+// Extract all VTODOs from iCalendar text
+fn parse_todos_from_content(content: &str, calendar_name: &str) -> Vec<Todo> {
+    parse_todos_with_progress(content, calendar_name, |_, _| {})
+}
+
+// Extract all VTODOs, reporting (VTODOs seen, fraction of bytes consumed)
+// every PROGRESS_INTERVAL VTODOs
+fn parse_todos_with_progress(content: &str, calendar_name: &str, mut on_progress: impl FnMut(usize, f64)) -> Vec<Todo> {
+    let mut todos = Vec::new();
+    let mut vtodo_count = 0;
+    let mut parsed_count = 0;
+    
+    for (calendar_block, vtodo_lines) in vtodo_blocks(content) {
+        vtodo_count += 1;
+        if vtodo_count % PROGRESS_INTERVAL == 0 {
+            // The block's lines borrow from `content`, so their end is the offset reached
+            let consumed = vtodo_lines
+                .last()
+                .map_or(0, |line| line.as_ptr() as usize - content.as_ptr() as usize + line.len());
+            on_progress(vtodo_count, progress_fraction(consumed, content.len()));
+        }
+        match parse_vtodo_from_lines(&vtodo_lines, calendar_name) {
+            Ok(mut todo) => {
+                todo.calendar_block = calendar_block;
+                todos.push(todo);
+                parsed_count += 1;
+            },
+            Err(e) => {
+                log::warn!("Failed to parse VTODO {}: {}", vtodo_count, e);
+            }
+        }
+    }
+    
+    log::info!("Parsed {}/{} VTODOs from calendar '{}'", parsed_count, vtodo_count, calendar_name);
+    
+    todos
+}
+
+// Load a single todo by UID, parsing only the matching VTODO block
+#[tauri::command]
+async fn get_todo_by_id(calendar_path: String, uid: String) -> Result<Option<Todo>, String> {
+    let path = resolve_calendar_path(&calendar_path, false)?;
+    let content = read_calendar_file(&path)?;
+    
+    let calendar_name = calendar_stem(&path);
+    
+    let found = vtodo_blocks(&content).find(|(_, block)| vtodo_block_uid(block) == Some(uid.as_str()));
+    match found {
+        Some((calendar_block, block)) => {
+            let mut todo = parse_vtodo_from_lines(&block, &calendar_name)?;
+            todo.calendar_block = calendar_block;
+            Ok(Some(todo))
+        }
+        None => Ok(None),
+    }
+}
+
+// Size hints for a todo's description
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TodoMetrics {
+    pub words: usize,
+    pub characters: usize, // Unicode characters, including whitespace
+    pub reading_minutes: usize, // At READING_WORDS_PER_MINUTE, rounded up
+}
+
+const READING_WORDS_PER_MINUTE: usize = 200;
+
+// Word and character counts and reading time of a todo's (unfolded, unescaped) description
+#[tauri::command]
+async fn describe_todo(calendar_path: String, uid: String) -> Result<TodoMetrics, String> {
+    let todo = get_todo_by_id(calendar_path, uid.clone())
+        .await?
+        .ok_or_else(|| format!("Todo not found: {}", uid))?;
+    Ok(text_metrics(&todo.description))
+}
+
+fn text_metrics(text: &str) -> TodoMetrics {
+    let words = text.split_whitespace().count();
+    TodoMetrics {
+        words,
+        characters: text.chars().count(),
+        reading_minutes: words.div_ceil(READING_WORDS_PER_MINUTE),
+    }
+}
+
+// Lazily yield each VTODO's lines (between BEGIN:VTODO and END:VTODO) with the
+// index of the VCALENDAR block it sits in. Files may concatenate several
+// VCALENDAR blocks. Only the current block's lines are held at a time; the
+// file itself is still read whole into a String by read_calendar_file.
+fn vtodo_blocks(content: &str) -> impl Iterator<Item = (usize, Vec<&str>)> {
+    let mut lines = split_lines(content);
+    let mut calendar_blocks: usize = 0;
+    std::iter::from_fn(move || {
+        while let Some(line) = lines.next() {
+            match line.trim() {
+                "BEGIN:VCALENDAR" => calendar_blocks += 1,
+                "BEGIN:VTODO" => {
+                    let block = lines.by_ref().take_while(|l| l.trim() != "END:VTODO").collect();
+                    return Some((calendar_blocks.saturating_sub(1), block));
+                }
+                _ => {}
+            }
+        }
+        None
+    })
+}
+
+// Entry points for the allocation benchmark in tests/parser_alloc.rs, which needs
+// its own test binary for its counting allocator. Not part of the app's API.
+#[doc(hidden)]
+pub mod bench {
+    // Total number of lines across all VTODO blocks, as scanned by vtodo_blocks
+    pub fn vtodo_block_lines(content: &str) -> usize {
+        super::vtodo_blocks(content).map(|(_, block)| block.len()).sum()
+    }
+}
+
+// Extract the UID value from a VTODO block's lines
+fn vtodo_block_uid<'a>(lines: &[&'a str]) -> Option<&'a str> {
+    lines.iter().find_map(|line| {
+        let (name, value) = split_property_line(line.trim())?;
+        let base = name.split(';').next().unwrap_or(name);
+        (base == "UID").then_some(value)
+    })
+}
+
+// Set every todo's calendar_name to the stem of the file it belongs to, for
+// in-memory todos whose name went stale (e.g. after the file was renamed)
+#[tauri::command]
+fn fix_calendar_names(calendar_path: String, mut todos: Vec<Todo>) -> Result<Vec<Todo>, String> {
+    let name = calendar_stem(&resolve_calendar_path(&calendar_path, false)?);
+    for todo in &mut todos {
+        todo.calendar_name.clone_from(&name);
+    }
+    Ok(todos)
+}
+
+// Rename a calendar file within the calendars directory. Todos read from it
+// afterwards carry the new stem as their calendar_name.
+#[tauri::command]
+async fn rename_calendar(
+    calendar_path: String,
+    new_name: String,
+    cache: tauri::State<'_, TodoCache>,
+) -> Result<CalendarFile, String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    let base = sanitize_filename(new_name.trim());
+    if base.is_empty() {
+        return Err("Calendar name must not be empty".to_string());
+    }
+    let dir = path.parent().ok_or_else(|| format!("Invalid calendar path: {}", calendar_path))?;
+    let mut target = unique_calendar_path(dir, &base)?;
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        target.set_extension("ics.gz");
+    }
+    rename_calendar_file(&path, &target)?;
+    cache.invalidate(&path);
+    forget_known_calendars();
+    log::info!("Renamed calendar {:?} to {:?}", path, target);
+    
+    calendar_file_info(&target, load_settings().show_completed)
+}
+
+// Move a calendar file to `target` while holding its lock
+fn rename_calendar_file(path: &Path, target: &Path) -> Result<(), String> {
+    if target.exists() {
+        return Err(format!("Calendar already exists: {:?}", target));
+    }
+    let _lock = CalendarLock::acquire(path)?;
+    fs::rename(path, target).map_err(|e| format!("Failed to rename calendar: {}", e))
+}
+
+// Move a todo's raw VTODO (with any VTIMEZONE it references) to another
+// calendar, returning it with the target's calendar_name
+#[tauri::command]
+async fn move_todo(
+    calendar_path: String,
+    uid: String,
+    target_calendar: String,
+    cache: tauri::State<'_, TodoCache>,
+) -> Result<Todo, String> {
+    let source = resolve_calendar_path(&calendar_path, true)?;
+    let target = resolve_calendar_path(&target_calendar, true)?;
+    if source == target {
+        return Err("Todo is already in this calendar".to_string());
+    }
+    
+    let is_todo = |c: &CalendarComponent| c.name == "VTODO" && c.uid().as_deref() == Some(uid.as_str());
+    let (vtodo, timezones) = read_calendar_sections(&source)?
+        .into_iter()
+        .find_map(|section| {
+            let vtodo = section.components.iter().find(|c| is_todo(c))?.clone();
+            let timezones: Vec<CalendarComponent> = section
+                .components
+                .into_iter()
+                .filter(|c| c.name == "VTIMEZONE" && referenced_timezone(c, &vtodo))
+                .collect();
+            Some((vtodo, timezones))
+        })
+        .ok_or_else(|| format!("Todo not found: {}", uid))?;
+    let todo = vtodo.to_todo(&calendar_stem(&target))?;
+    
+    // Write the copy first so a failure never loses the todo
+    modify_calendar_file(&target, &cache, |sections| {
+        for timezone in timezones {
+            let exists = sections[0]
+                .components
+                .iter()
+                .any(|c| c.name == "VTIMEZONE" && c.property("TZID") == timezone.property("TZID"));
+            if !exists {
+                sections[0].components.insert(0, timezone);
+            }
+        }
+        sections[0].components.push(vtodo);
+        Ok(())
+    })?;
+    modify_calendar_file(&source, &cache, |sections| {
+        for section in sections.iter_mut() {
+            section.components.retain(|c| !is_todo(c));
+        }
+        Ok(())
+    })?;
+    
+    Ok(Todo { calendar_block: 0, ..todo })
+}
+
+// Whether `vtodo` has a property in the zone defined by `timezone`
+fn referenced_timezone(timezone: &CalendarComponent, vtodo: &CalendarComponent) -> bool {
+    timezone
+        .property("TZID")
+        .is_some_and(|tzid| vtodo.lines.iter().any(|line| line.contains(&format!("TZID={}", tzid))))
+}
+
+// Append a new todo to a calendar, filling in a UID and creation time if missing
+#[tauri::command]
+async fn add_todo(calendar_path: String, todo: Todo, cache: tauri::State<'_, TodoCache>) -> Result<Todo, String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    append_todo(&path, todo, &cache)
+}
+
+// Shared by add_todo and the `add` CLI command
+fn append_todo(path: &Path, mut todo: Todo, cache: &TodoCache) -> Result<Todo, String> {
+    if todo.id.trim().is_empty() {
+        todo.id = new_uid(&load_settings().uid_domain);
+    }
+    // New todos always carry a full-precision CREATED
+    let created = todo.created_at.as_deref().and_then(parse_iso_datetime).unwrap_or_else(|| Utc::now().naive_utc());
+    todo.created_at = Some(created.format("%Y-%m-%dT%H:%M:%S").to_string());
+    todo.calendar_name = calendar_stem(path);
+    todo.validate()?;
+    
+    modify_calendar_file(path, cache, |sections| {
+        let timezone = output_timezone();
+        sections[0].components.push(vtodo_component(&todo, timezone));
+        add_missing_vtimezones(sections, timezone);
+        Ok(())
+    })?;
+    Ok(todo)
+}
+
+// Copy a VEVENT into `target_calendar` as a new open todo due at the event's
+// start. The event is left in place unless `remove` is set.
+#[tauri::command]
+async fn convert_event_to_todo(
+    calendar_path: String,
+    event_uid: String,
+    target_calendar: String,
+    remove: Option<bool>,
+    cache: tauri::State<'_, TodoCache>,
+) -> Result<Todo, String> {
+    let remove = remove.unwrap_or(false);
+    let source = resolve_calendar_path(&calendar_path, remove)?;
+    let target = resolve_calendar_path(&target_calendar, true)?;
+    
+    let is_event = |c: &CalendarComponent| c.name == "VEVENT" && c.uid().as_deref() == Some(event_uid.as_str());
+    let event = read_calendar_sections(&source)?
+        .into_iter()
+        .flat_map(|section| section.components)
+        .find(is_event)
+        .ok_or_else(|| format!("Event not found: {}", event_uid))?;
+    
+    // VEVENT and VTODO share SUMMARY, DESCRIPTION, CATEGORIES, LOCATION, ...
+    let event = event.to_todo(&calendar_stem(&target))?;
+    let (due_date, due_time) = match event.start_date.as_deref().and_then(|start| start.split_once('T')) {
+        Some((date, time)) => (Some(date.to_string()), Some(time.to_string())),
+        None => (event.start_date.clone(), None),
+    };
+    let todo = Todo {
+        id: String::new(), // Assigned by append_todo
+        completed: false,
+        completed_at: None,
+        created_at: None,
+        dtstamp: None,
+        calendar_block: 0,
+        all_day: due_time.is_none(),
+        due_date,
+        due_time,
+        start_date: None,
+        ..event
+    };
+    let todo = append_todo(&target, todo, &cache)?;
+    
+    if remove {
+        modify_calendar_file(&source, &cache, |sections| {
+            for section in sections.iter_mut() {
+                section.components.retain(|c| !is_event(c));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(todo)
+}
+
+// Duplicate a todo as a fresh, open copy appended to the same calendar
+#[tauri::command]
+async fn duplicate_todo(calendar_path: String, uid: String, cache: tauri::State<'_, TodoCache>) -> Result<Todo, String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    let source = get_todo_by_id(calendar_path, uid.clone())
+        .await?
+        .ok_or_else(|| format!("Todo not found: {}", uid))?;
+    
+    let copy = Todo {
+        id: new_uid(&load_settings().uid_domain),
+        title: format!("{} (copy)", source.title),
+        completed: false,
+        completed_at: None,
+        created_at: Some(Utc::now().naive_utc().format("%Y-%m-%dT%H:%M:%S").to_string()),
+        dtstamp: None,
+        recurrence_id: None, // The copy stands alone, even when made from an occurrence
+        rrule: None, // A one-off copy rather than a second series
+        trashed_at: None,
+        snooze_until: None,
+        ..source
+    };
+    
+    modify_calendar_file(&path, &cache, |sections| {
+        let timezone = output_timezone();
+        sections[0].components.push(vtodo_component(&copy, timezone));
+        add_missing_vtimezones(sections, timezone);
+        Ok(())
+    })?;
+    
+    Ok(copy)
+}
+
+// Export one todo as a standalone calendar: its VTODO verbatim (unknown
+// properties included) plus any VTIMEZONE it references, in a minimal envelope
+#[tauri::command]
+async fn export_todo(calendar_path: String, uid: String) -> Result<String, String> {
+    let path = resolve_calendar_path(&calendar_path, false)?;
+    let sections = read_calendar_sections(&path)?;
+    
+    for section in &sections {
+        let Some(vtodo) = section
+            .components
+            .iter()
+            .find(|c| c.name == "VTODO" && c.uid().as_deref() == Some(uid.as_str()))
+        else {
+            continue;
+        };
+        
+        let mut components: Vec<CalendarComponent> = section
+            .components
+            .iter()
+            .filter(|c| c.name == "VTIMEZONE" && referenced_timezone(c, vtodo))
+            .cloned()
+            .collect();
+        components.push(vtodo.clone());
+        
+        return Ok(render_calendar(&[CalendarSection {
+            properties: Vec::new(),
+            components,
+        }]));
+    }
+    
+    Err(format!("Todo not found: {}", uid))
+}
+
+// Outcome of a bulk edit: how many todos changed and how many UIDs weren't found
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BulkResult {
+    pub changed: usize,
+    pub skipped: usize,
+}
+
+// Mark several todos complete (or incomplete) in a single write
+#[tauri::command]
+async fn bulk_complete(calendar_path: String, uids: Vec<String>, completed: bool, cache: tauri::State<'_, TodoCache>) -> Result<BulkResult, String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    let now = format_ical_utc(&Utc::now().naive_utc());
+    
+    modify_calendar_file(&path, &cache, |sections| {
+        let skipped = count_missing_uids(sections, &uids);
+        let changed = for_each_vtodo(sections, &uids, |component| {
+            set_component_completed(component, completed, &now);
+        });
+        Ok(BulkResult { changed, skipped })
+    })
+}
+
+// Delete several todos in a single write
+#[tauri::command]
+async fn bulk_delete(calendar_path: String, uids: Vec<String>, cache: tauri::State<'_, TodoCache>) -> Result<BulkResult, String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    
+    modify_calendar_file(&path, &cache, |sections| {
+        let skipped = count_missing_uids(sections, &uids);
+        let mut changed = 0;
+        for section in sections.iter_mut() {
+            section.components.retain(|component| {
+                let matches = component.name == "VTODO"
+                    && component.uid().map(|uid| uids.contains(&uid)).unwrap_or(false);
+                if matches {
+                    changed += 1;
+                }
+                !matches
+            });
+        }
+        Ok(BulkResult { changed, skipped })
+    })
+}
+
+// X-property holding the datetime (UTC) a snoozed todo reappears
+const SNOOZE_PROPERTY: &str = "X-2DO-SNOOZE-UNTIL";
+
+// Hide a todo from list_active until `until` (ISO date or datetime, UTC)
+// without touching its due date; None clears the snooze
+#[tauri::command]
+async fn snooze_todo(calendar_path: String, uid: String, until: Option<String>, cache: tauri::State<'_, TodoCache>) -> Result<(), String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    let until = match until.as_deref().map(str::trim).filter(|u| !u.is_empty()) {
+        Some(value) => Some(parse_iso_datetime(value).ok_or_else(|| format!("Invalid snooze time: {}", value))?),
+        None => None,
+    };
+    let now = format_ical_utc(&Utc::now().naive_utc());
+    
+    modify_calendar_file(&path, &cache, |sections| {
+        let component = sections
+            .iter_mut()
+            .flat_map(|section| section.components.iter_mut())
+            .find(|c| c.name == "VTODO" && c.uid().as_deref() == Some(uid.as_str()))
+            .ok_or_else(|| format!("Todo not found: {}", uid))?;
+        match until {
+            Some(until) => component.set_property(SNOOZE_PROPERTY, format!("{}:{}", SNOOZE_PROPERTY, format_ical_utc(&until))),
+            None => component.remove_property(SNOOZE_PROPERTY),
+        }
+        component.set_property("LAST-MODIFIED", format!("LAST-MODIFIED:{}", now));
+        Ok(())
+    })
+}
+
+// Load a calendar's todos, leaving out those snoozed past now
+#[tauri::command]
+async fn list_active(calendar_path: String, cache: tauri::State<'_, TodoCache>) -> Result<Vec<Todo>, String> {
+    let now = Utc::now().naive_utc();
+    Ok(visible_todos(&calendar_path, &cache)?
+        .into_iter()
+        .filter(|todo| !is_snoozed(todo, now))
+        .collect())
+}
+
+fn is_snoozed(todo: &Todo, now: NaiveDateTime) -> bool {
+    todo.snooze_until
+        .as_deref()
+        .and_then(parse_iso_datetime)
+        .is_some_and(|until| until > now)
+}
+
+// Move every open overdue todo to `to` (default: today's local date), keeping
+// the time of timed dues. Returns the number of todos moved.
+#[tauri::command]
+async fn reschedule_overdue(calendar_path: String, to: Option<String>, cache: tauri::State<'_, TodoCache>) -> Result<usize, String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    let calendar_name = calendar_stem(&path);
+    let today = chrono::Local::now().date_naive();
+    let target = match to.as_deref() {
+        Some(date) => NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+            .map_err(|e| format!("Invalid target date '{}': {}", date, e))?,
+        None => today,
+    };
+    let now = format_ical_utc(&Utc::now().naive_utc());
+    
+    modify_calendar_file(&path, &cache, |sections| {
+        let mut changed = 0;
+        let vtodos = sections
+            .iter_mut()
+            .flat_map(|section| section.components.iter_mut())
+            .filter(|component| component.name == "VTODO");
+        for component in vtodos {
+            let mut todo = match component.to_todo(&calendar_name) {
+                Ok(todo) => todo,
+                Err(e) => {
+                    log::warn!("Skipping unparseable VTODO while rescheduling: {}", e);
+                    continue;
+                }
+            };
+            let Some(due) = todo.due_date.as_deref().and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()) else {
+                continue;
+            };
+            if todo.completed || todo.trashed_at.is_some() || due >= today {
+                continue;
+            }
+            
+            // Don't move a due date before the todo's start
+            todo.due_date = Some(target.format("%Y-%m-%d").to_string());
+            if let Err(e) = todo.validate() {
+                log::warn!("Not rescheduling {}: {}", todo.id, e);
+                continue;
+            }
+            
+            component.set_property("DUE", todo.due_property(target));
+            component.set_property("LAST-MODIFIED", format!("LAST-MODIFIED:{}", now));
+            component.set_property("DTSTAMP", format!("DTSTAMP:{}", now));
+            changed += 1;
+        }
+        Ok(changed)
+    })
+}
+
+// Set the priority ("high", "medium" or "low") of several todos in a single
+// write, returning how many were updated
+#[tauri::command]
+async fn bulk_set_priority(calendar_path: String, uids: Vec<String>, priority: String, cache: tauri::State<'_, TodoCache>) -> Result<usize, String> {
+    let value = ical_priority(&priority)
+        .ok_or_else(|| format!("Invalid priority '{}': expected high, medium or low", priority))?;
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    let now = format_ical_utc(&Utc::now().naive_utc());
+    
+    modify_calendar_file(&path, &cache, |sections| {
+        Ok(for_each_vtodo(sections, &uids, |component| {
+            component.set_property("PRIORITY", format!("PRIORITY:{}", value));
+            component.set_property("LAST-MODIFIED", format!("LAST-MODIFIED:{}", now));
+        }))
+    })
+}
+
+// iCalendar PRIORITY for the app's coarse levels
+fn ical_priority(priority: &str) -> Option<u8> {
+    match priority {
+        "high" => Some(1),
+        "medium" => Some(5),
+        "low" => Some(9),
+        _ => None,
+    }
+}
+
+// Set STATUS/COMPLETED on a raw VTODO and bump its modification stamps
+fn set_component_completed(component: &mut CalendarComponent, completed: bool, now: &str) {
+    if completed {
+        component.set_property("STATUS", "STATUS:COMPLETED".to_string());
+        if component.property("COMPLETED").is_none() {
+            component.set_property("COMPLETED", format!("COMPLETED:{}", now));
+        }
+    } else {
+        component.set_property("STATUS", "STATUS:NEEDS-ACTION".to_string());
+        component.remove_property("COMPLETED");
+    }
+    component.set_property("LAST-MODIFIED", format!("LAST-MODIFIED:{}", now));
+    component.set_property("DTSTAMP", format!("DTSTAMP:{}", now));
+}
+
+// Complete a todo; for a recurring todo, also create its next occurrence.
+// Returns the new occurrence, or None if the todo doesn't recur or the series ended.
+#[tauri::command]
+async fn complete_recurring(calendar_path: String, uid: String, cache: tauri::State<'_, TodoCache>) -> Result<Option<Todo>, String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    let calendar_name = calendar_stem(&path);
+    let now = format_ical_utc(&Utc::now().naive_utc());
+    let uid_domain = load_settings().uid_domain;
+    
+    modify_calendar_file(&path, &cache, |sections| {
+        for (block, section) in sections.iter_mut().enumerate() {
+            let Some(index) = section.components.iter().position(|c| {
+                c.name == "VTODO" && c.uid().as_deref() == Some(uid.as_str())
+            }) else {
+                continue;
+            };
+            
+            let current = &mut section.components[index];
+            let todo = current.to_todo(&calendar_name)?;
+            set_component_completed(current, true, &now);
+            
+            let next = match next_occurrence(&todo)? {
+                Some(next) => next,
+                None => return Ok(None),
+            };
+            
+            // The completed instance no longer carries the series
+            current.remove_property("RRULE");
+            
+            // Clone the raw VTODO so unknown properties carry over to the next occurrence
+            let mut next_component = current.clone();
+            let (due, rrule) = next;
+            next_component.set_property("UID", format!("UID:{}", new_uid(&uid_domain)));
+            next_component.set_property("DUE", todo.due_property(due));
+            next_component.set_property("RRULE", format!("RRULE:{}", rrule));
+            
+            // DTSTART moves by as many days as DUE did
+            let shift = todo.due_day().map(|old| due - old).unwrap_or_default();
+            if let Some(start) = todo.start_date.as_deref() {
+                if let Some(start_at) = parse_iso_datetime(start).and_then(|dt| dt.checked_add_signed(shift)) {
+                    let line = date_property("DTSTART", start_at, start.len() == 10, todo.start_style.as_ref());
+                    next_component.set_property("DTSTART", line);
+                }
+            }
+            
+            // A fresh instance: no progress, created now
+            set_component_completed(&mut next_component, false, &now);
+            next_component.remove_property("PERCENT-COMPLETE");
+            next_component.remove_property("LAST-MODIFIED");
+            next_component.set_property("CREATED", format!("CREATED:{}", now));
+            next_component.set_property("DTSTAMP", format!("DTSTAMP:{}", now));
+            
+            let mut next_todo = next_component.to_todo(&calendar_name)?;
+            next_todo.calendar_block = block;
+            section.components.insert(index + 1, next_component);
+            return Ok(Some(next_todo));
+        }
+        Err(format!("Todo not found: {}", uid))
+    })
+}
+
+// Due date and adjusted RRULE of the occurrence after `todo`, or None if the
+// todo doesn't recur (or has no due date) or its series has ended
+fn next_occurrence(todo: &Todo) -> Result<Option<(NaiveDate, String)>, String> {
+    let (Some(rrule), Some(due)) = (todo.rrule.as_deref(), todo.due_date.as_deref()) else {
+        return Ok(None);
+    };
+    let due = NaiveDate::parse_from_str(due, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid due date: {}", e))?;
+    let rule = RecurrenceRule::parse(rrule)?;
+    
+    // COUNT counts the remaining occurrences including this one
+    if rule.count.map(|count| count <= 1).unwrap_or(false) {
+        return Ok(None);
+    }
+    let Some(next_due) = rule.nth_occurrence(due, 1) else {
+        return Ok(None);
+    };
+    if rule.until.map(|until| next_due > until).unwrap_or(false) {
+        return Ok(None);
+    }
+    
+    let next_rrule = match rule.count {
+        Some(count) => rrule
+            .split(';')
+            .map(|part| {
+                if part.to_ascii_uppercase().starts_with("COUNT=") {
+                    format!("COUNT={}", count - 1)
+                } else {
+                    part.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(";"),
+        None => rrule.to_string(),
+    };
+    Ok(Some((next_due, next_rrule)))
+}
+
+// Subdirectories of the calendars directory holding each calendar's archive and trash
+const ARCHIVE_DIR: &str = ".archive";
+const TRASH_DIR: &str = ".trash";
+
+// Move all completed todos into the calendar's `.archive/<name>.ics`, returning how many moved
+#[tauri::command]
+async fn archive_completed(calendar_path: String, cache: tauri::State<'_, TodoCache>) -> Result<usize, String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    let archive_path = companion_calendar_path(&path, ARCHIVE_DIR);
+    
+    modify_calendar_file(&path, &cache, |sections| {
+        let archived = take_vtodos(sections, is_component_completed);
+        if archived.is_empty() {
+            return Ok(0);
+        }
+        
+        // Write the archive before the source so a failure never loses todos
+        ensure_companion_dir(&archive_path)?;
+        modify_calendar_file(&archive_path, &cache, |archive_sections| {
+            archive_sections[0].components.extend(archived.iter().cloned());
+            Ok(())
+        })?;
+        Ok(archived.len())
+    })
+}
+
+// Delete a todo. A soft delete moves it to the calendar's `.trash/<name>.ics`
+// stamped with X-TRASHED-AT so it can be restored later.
+#[tauri::command]
+async fn delete_todo(calendar_path: String, uid: String, soft: bool, cache: tauri::State<'_, TodoCache>) -> Result<(), String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    let trash_path = companion_calendar_path(&path, TRASH_DIR);
+    
+    modify_calendar_file(&path, &cache, |sections| {
+        let mut removed = take_vtodos(sections, |c| c.uid().as_deref() == Some(uid.as_str()));
+        if removed.is_empty() {
+            return Err(format!("Todo not found: {}", uid));
+        }
+        if soft {
+            let now = format_ical_utc(&Utc::now().naive_utc());
+            for component in &mut removed {
+                component.set_property("X-TRASHED-AT", format!("X-TRASHED-AT:{}", now));
+            }
+            ensure_companion_dir(&trash_path)?;
+            modify_calendar_file(&trash_path, &cache, |trash_sections| {
+                trash_sections[0].components.extend(removed);
+                Ok(())
+            })?;
+        }
+        Ok(())
+    })
+}
+
+// List the todos in a calendar's trash
+#[tauri::command]
+async fn list_trash(calendar_path: String) -> Result<Vec<Todo>, String> {
+    let path = resolve_calendar_path(&calendar_path, false)?;
+    let trash_path = companion_calendar_path(&path, TRASH_DIR);
+    if !trash_path.exists() {
+        return Ok(Vec::new());
+    }
+    parse_todos_from_file(&trash_path)
+}
+
+// Move a todo from the trash back into its calendar
+#[tauri::command]
+async fn restore_todo(calendar_path: String, uid: String, cache: tauri::State<'_, TodoCache>) -> Result<(), String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    let trash_path = companion_calendar_path(&path, TRASH_DIR);
+    if !trash_path.exists() {
+        return Err(format!("Todo not found in trash: {}", uid));
+    }
+    
+    modify_calendar_file(&trash_path, &cache, |trash_sections| {
+        let mut restored = take_vtodos(trash_sections, |c| c.uid().as_deref() == Some(uid.as_str()));
+        if restored.is_empty() {
+            return Err(format!("Todo not found in trash: {}", uid));
+        }
+        for component in &mut restored {
+            component.remove_property("X-TRASHED-AT");
+        }
+        modify_calendar_file(&path, &cache, |sections| {
+            sections[0].components.extend(restored);
+            Ok(())
+        })
+    })
+}
+
+// How merge_calendars resolves a VTODO whose UID already exists in the target
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    KeepNewest, // Later LAST-MODIFIED (or DTSTAMP) wins; ties keep the target
+    KeepTarget,
+    KeepSource,
+}
+
+// Outcome of a merge: VTODOs added or replaced, and VTODOs left out
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MergeResult {
+    pub merged: usize,
+    pub skipped: usize,
+}
+
+// Merge the VTODOs of several calendars into a target, deduplicating by UID.
+// Whole components are copied, so unknown properties of the kept version survive.
+#[tauri::command]
+async fn merge_calendars(
+    source_paths: Vec<String>,
+    target_path: String,
+    on_conflict: ConflictPolicy,
+    cache: tauri::State<'_, TodoCache>,
+) -> Result<MergeResult, String> {
+    let target = resolve_calendar_path(&target_path, true)?;
+    
+    let mut incoming = Vec::new();
+    for source_path in &source_paths {
+        let source = resolve_calendar_path(source_path, false)?;
+        if source == target {
+            log::warn!("Skipping merge of {:?} into itself", source);
+            continue;
+        }
+        let sections = read_calendar_sections(&source)?;
+        incoming.extend(
+            sections
+                .into_iter()
+                .flat_map(|section| section.components)
+                .filter(|component| component.name == "VTODO"),
+        );
+    }
+    
+    modify_calendar_file(&target, &cache, |sections| {
+        let mut result = MergeResult { merged: 0, skipped: 0 };
+        for component in incoming {
+            let existing = component.uid().and_then(|uid| {
+                sections.iter().enumerate().find_map(|(s, section)| {
+                    section
+                        .components
+                        .iter()
+                        .position(|c| c.name == "VTODO" && c.uid().as_deref() == Some(uid.as_str()))
+                        .map(|c| (s, c))
+                })
+            });
+            
+            let Some((s, c)) = existing else {
+                sections[0].components.push(component);
+                result.merged += 1;
+                continue;
+            };
+            let replace = match on_conflict {
+                ConflictPolicy::KeepTarget => false,
+                ConflictPolicy::KeepSource => true,
+                ConflictPolicy::KeepNewest => last_modified_of(&component) > last_modified_of(&sections[s].components[c]),
+            };
+            if replace {
+                sections[s].components[c] = component;
+                result.merged += 1;
+            } else {
+                result.skipped += 1;
+            }
+        }
+        Ok(result)
+    })
+}
+
+// ISO timestamp of a component's LAST-MODIFIED, falling back to DTSTAMP
+fn last_modified_of(component: &CalendarComponent) -> Option<String> {
+    component
+        .property("LAST-MODIFIED")
+        .or_else(|| component.property("DTSTAMP"))
+        .and_then(|value| parse_ical_datetime(&value))
+}
+
+// Differences between two calendars, matched by UID
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CalendarDiff {
+    pub added: Vec<Todo>, // Only in `b`
+    pub removed: Vec<Todo>, // Only in `a`
+    pub changed: Vec<TodoChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TodoChange {
+    pub uid: String,
+    pub title: String, // As in `b`
+    pub fields: Vec<String>, // Serialized names of the fields that differ
+}
+
+// Fields that say where a todo was loaded from, or when it was stamped, rather
+// than what it contains
+const DIFF_IGNORED_FIELDS: [&str; 3] = ["dtstamp", "calendar_name", "calendar_block"];
+
+// Compare two calendars' todos by UID: what `b` adds, what it lacks, and what it changes
+#[tauri::command]
+async fn compare_calendars(a: String, b: String) -> Result<CalendarDiff, String> {
+    let before = parse_todos_from_file(&resolve_calendar_path(&a, false)?)?;
+    let after = parse_todos_from_file(&resolve_calendar_path(&b, false)?)?;
+    
+    let before_by_uid: HashMap<&str, &Todo> = before.iter().map(|todo| (todo.id.as_str(), todo)).collect();
+    let after_uids: std::collections::HashSet<&str> = after.iter().map(|todo| todo.id.as_str()).collect();
+    
+    let mut diff = CalendarDiff::default();
+    for todo in &after {
+        match before_by_uid.get(todo.id.as_str()) {
+            None => diff.added.push(todo.clone()),
+            Some(old) => {
+                let fields = changed_fields(old, todo)?;
+                if !fields.is_empty() {
+                    diff.changed.push(TodoChange { uid: todo.id.clone(), title: todo.title.clone(), fields });
+                }
+            }
+        }
+    }
+    diff.removed = before.iter().filter(|todo| !after_uids.contains(todo.id.as_str())).cloned().collect();
+    Ok(diff)
+}
+
+// Names of the (serialized) fields that differ between two versions of a todo
+fn changed_fields(old: &Todo, new: &Todo) -> Result<Vec<String>, String> {
+    let to_map = |todo: &Todo| match serde_json::to_value(todo) {
+        Ok(serde_json::Value::Object(map)) => Ok(map),
+        Ok(_) => Err("Todo did not serialize to an object".to_string()),
+        Err(e) => Err(format!("Failed to serialize todo: {}", e)),
+    };
+    let (old, new) = (to_map(old)?, to_map(new)?);
+    Ok(new
+        .iter()
+        .filter(|(name, _)| !DIFF_IGNORED_FIELDS.contains(&name.as_str()))
+        .filter(|(name, value)| old.get(name.as_str()) != Some(value))
+        .map(|(name, _)| name.clone())
+        .collect())
+}
+
+// What repair_calendar found and fixed
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RepairReport {
+    pub unterminated_blocks: usize,
+    pub wrapped_orphans: usize,
+    pub duplicate_uids: usize,
+    pub missing_uids: usize,
+    pub stray_lines: usize,
+    pub changed: bool,
+    pub backup_path: Option<String>,
+}
+
+// Repair common structural problems (unterminated blocks, VTODOs outside a
+// VCALENDAR, duplicate or missing UIDs). The original is backed up before rewriting.
+#[tauri::command]
+async fn repair_calendar(path: String, cache: tauri::State<'_, TodoCache>) -> Result<RepairReport, String> {
+    let path = resolve_calendar_path(&path, true)?;
+    let _lock = CalendarLock::acquire(&path)?;
+    let content = read_calendar_file(&path)?;
+    
+    let (mut sections, mut report) = parse_calendar_leniently(&content);
+    
+    // Give every VTODO a unique UID
+    let uid_domain = load_settings().uid_domain;
+    let mut seen = std::collections::HashSet::new();
+    for component in sections.iter_mut().flat_map(|s| s.components.iter_mut()).filter(|c| c.name == "VTODO") {
+        match component.uid() {
+            Some(uid) if seen.insert(uid.clone()) => continue,
+            Some(_) => report.duplicate_uids += 1,
+            None => report.missing_uids += 1,
+        }
+        let uid = new_uid(&uid_domain);
+        component.set_property("UID", format!("UID:{}", uid));
+        seen.insert(uid);
+    }
+    
+    report.changed = report.unterminated_blocks + report.wrapped_orphans + report.duplicate_uids
+        + report.missing_uids + report.stray_lines > 0;
+    if !report.changed {
+        return Ok(report);
+    }
+    
+    let backup = backup_calendar_file(&path)?;
+    report.backup_path = Some(backup.to_string_lossy().to_string());
+    
+    cache.invalidate(&path);
+    write_calendar_file(&path, &render_calendar(&sections))?;
+    log::info!("Repaired calendar {:?}: {:?}", path, report);
+    Ok(report)
+}
+
+// Health of one calendar file, as reported by check_all_calendars
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CalendarHealth {
+    pub path: String,
+    pub name: String,
+    pub loaded: bool,          // The file could be read and decoded
+    pub error: Option<String>, // Why it couldn't, if not
+    pub vtodos: usize,         // VTODO blocks found
+    pub parsed: usize,         // ... of which parsed into todos
+    pub failed: usize,         // ... of which failed to parse
+    pub invalid: usize,        // Parsed todos that fail validation (e.g. DUE before DTSTART)
+    pub issues: Vec<String>,   // Structural problems; repair_calendar fixes most of them
+}
+
+// Check every calendar in the directory without changing anything, so broken
+// files show up before a sync or save trips over them
+#[tauri::command]
+async fn check_all_calendars() -> Result<Vec<CalendarHealth>, String> {
+    Ok(calendar_files(&get_calendars_dir()?)?
+        .iter()
+        .map(|path| calendar_health(path))
+        .collect())
+}
+
+fn calendar_health(path: &Path) -> CalendarHealth {
+    let mut health = CalendarHealth {
+        path: path.to_string_lossy().to_string(),
+        name: calendar_stem(path),
+        loaded: false,
+        error: None,
+        vtodos: 0,
+        parsed: 0,
+        failed: 0,
+        invalid: 0,
+        issues: Vec::new(),
+    };
+    let content = match read_calendar_file(path) {
+        Ok(content) => content,
+        Err(e) => {
+            health.error = Some(e);
+            return health;
+        }
+    };
+    health.loaded = true;
+    
+    let (_, report) = parse_calendar_leniently(&content);
+    if report.unterminated_blocks > 0 {
+        health.issues.push(format!("{} unterminated block(s)", report.unterminated_blocks));
+    }
+    if report.wrapped_orphans > 0 {
+        health.issues.push(format!("{} component(s) outside a VCALENDAR", report.wrapped_orphans));
+    }
+    if report.stray_lines > 0 {
+        health.issues.push(format!("{} stray line(s)", report.stray_lines));
+    }
+    health.issues.extend(calendar_warnings(&content));
+    
+    let mut uids = std::collections::HashSet::new();
+    let (mut missing_uids, mut duplicate_uids) = (0, 0);
+    for (_, lines) in vtodo_blocks(&content) {
+        health.vtodos += 1;
+        match vtodo_block_uid(&lines) {
+            Some(uid) if !uids.insert(uid) => duplicate_uids += 1,
+            Some(_) => {}
+            None => missing_uids += 1,
+        }
+        match parse_vtodo_from_lines(&lines, &health.name) {
+            Ok(todo) => {
+                health.parsed += 1;
+                if todo.validate().is_err() {
+                    health.invalid += 1;
+                }
+            }
+            Err(_) => health.failed += 1,
+        }
+    }
+    if missing_uids > 0 {
+        health.issues.push(format!("{} VTODO(s) without a UID", missing_uids));
+    }
+    if duplicate_uids > 0 {
+        health.issues.push(format!("{} duplicate UID(s)", duplicate_uids));
+    }
+    if content.trim().is_empty() {
+        health.issues.push("File is empty".to_string());
+    }
+    health
+}
+
+// Copy a calendar into `.backups/` next to it, returning the backup path
+fn backup_calendar_file(path: &Path) -> Result<PathBuf, String> {
+    let parent = path.parent().ok_or("Failed to get parent directory")?;
+    let backups_dir = parent.join(".backups");
+    fs::create_dir_all(&backups_dir)
+        .map_err(|e| format!("Failed to create backups directory: {}", e))?;
+    
+    let file_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("calendar.ics");
+    let backup = backups_dir.join(format!("{}-{}", Utc::now().format("%Y%m%dT%H%M%S"), file_name));
+    fs::copy(path, &backup)
+        .map_err(|e| format!("Failed to back up calendar file: {}", e))?;
+    Ok(backup)
+}
+
+// Canonical property order for the VCALENDAR envelope and for components (also
+// used for every VTODO we save); properties not listed, such as X- extensions,
+// keep their relative order after the listed ones
+const ENVELOPE_PROPERTY_ORDER: [&str; 4] = ["VERSION", "PRODID", "CALSCALE", "METHOD"];
+const COMPONENT_PROPERTY_ORDER: [&str; 28] = [
+    "UID", "SUMMARY", "DESCRIPTION", "STATUS", "PRIORITY", "CATEGORIES", "DTSTART", "DUE", "CREATED",
+    "LAST-MODIFIED", "DTSTAMP", "COMPLETED", "PERCENT-COMPLETE", "CLASS", "LOCATION", "GEO", "RESOURCES",
+    "URL", "DURATION", "RRULE", "RDATE", "EXDATE", "RELATED-TO", "ORGANIZER", "ATTENDEE", "CONTACT",
+    "COMMENT", "ATTACH",
+];
+const ALARM_PROPERTY_ORDER: [&str; 5] = ["ACTION", "TRIGGER", "DURATION", "REPEAT", "DESCRIPTION"];
+
+// Longest content line, in octets, before folding
+const MAX_LINE_OCTETS: usize = 75;
+
+// Rewrite a calendar in canonical form: stable property order, uppercase
+// property names, CRLF line endings and 75-octet folding. Values and unknown
+// properties are kept as they are, so running it twice changes nothing.
+#[tauri::command]
+async fn canonicalize_calendar(path: String, cache: tauri::State<'_, TodoCache>) -> Result<(), String> {
+    let path = resolve_calendar_path(&path, true)?;
+    if !path.exists() {
+        return Err(format!("Calendar file not found: {:?}", path));
+    }
+    
+    modify_calendar_file(&path, &cache, |sections| {
+        for section in sections.iter_mut() {
+            let refs: Vec<&str> = section.properties.iter().map(String::as_str).collect();
+            let mut properties: Vec<String> = envelope_properties(&unfold_lines(&refs))
+                .iter()
+                .map(|line| uppercase_property_name(line))
+                .collect();
+            properties.sort_by_key(|line| property_rank(line, &ENVELOPE_PROPERTY_ORDER));
+            section.properties = properties.iter().flat_map(|line| fold_line(line)).collect();
+            
+            for component in &mut section.components {
+                let refs: Vec<&str> = component.lines.iter().map(String::as_str).collect();
+                let mut lines = Vec::new();
+                canonicalize_component(&unfold_lines(&refs), &mut lines);
+                component.lines = lines.iter().flat_map(|line| fold_line(line)).collect();
+            }
+        }
+        Ok(())
+    })
+}
+
+// Canonicalize one unfolded component (BEGIN through END), nested components last
+fn canonicalize_component(lines: &[String], out: &mut Vec<String>) {
+    let name = lines
+        .first()
+        .and_then(|line| component_marker(line, "BEGIN"))
+        .unwrap_or("")
+        .to_ascii_uppercase();
+    let terminated = lines.len() > 1 && lines.last().is_some_and(|line| component_marker(line, "END").is_some());
+    let end = if terminated { lines.len() - 1 } else { lines.len() };
+    let inner = lines.get(1..end).unwrap_or(&[]);
+    
+    let mut properties = Vec::new();
+    let mut children = Vec::new();
+    let mut i = 0;
+    while i < inner.len() {
+        if component_marker(&inner[i], "BEGIN").is_some() {
+            let start = i;
+            let mut depth = 0;
+            while i < inner.len() {
+                if component_marker(&inner[i], "BEGIN").is_some() {
+                    depth += 1;
+                } else if component_marker(&inner[i], "END").is_some() {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                i += 1;
+            }
+            children.push(&inner[start..(i + 1).min(inner.len())]);
+        } else if !inner[i].trim().is_empty() {
+            properties.push(uppercase_property_name(&inner[i]));
+        }
+        i += 1;
+    }
+    
+    let order: &[&str] = if name == "VALARM" { &ALARM_PROPERTY_ORDER } else { &COMPONENT_PROPERTY_ORDER };
+    properties.sort_by_key(|line| property_rank(line, order));
+    
+    out.push(format!("BEGIN:{}", name));
+    out.extend(properties);
+    for child in children {
+        canonicalize_component(child, out);
+    }
+    out.push(format!("END:{}", name));
+}
+
+// Position of a property in a canonical order (unlisted properties sort last)
+fn property_rank(line: &str, order: &[&str]) -> usize {
+    property_base_name(line)
+        .and_then(|name| order.iter().position(|o| *o == name))
+        .unwrap_or(order.len())
+}
+
+// Uppercase the property name of a content line, leaving parameters and value alone
+fn uppercase_property_name(line: &str) -> String {
+    let end = line.find([';', ':']).unwrap_or(line.len());
+    format!("{}{}", line[..end].to_ascii_uppercase(), &line[end..])
+}
+
+// Fold a content line into segments of at most 75 octets (continuations start
+// with a space), never splitting a UTF-8 character
+fn fold_line(line: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    for ch in line.chars() {
+        if current.len() + ch.len_utf8() > MAX_LINE_OCTETS {
+            segments.push(std::mem::take(&mut current));
+            current.push(' ');
+        }
+        current.push(ch);
+    }
+    segments.push(current);
+    segments
+}
+
+// Directory names skipped when zipping the calendars directory
+const BACKUP_EXCLUDED_DIRS: [&str; 2] = [".backups", ".tmp"];
+
+// Zip the whole calendars directory into `.backups/full-<timestamp>.zip`
+#[tauri::command]
+async fn backup_all() -> Result<String, String> {
+    let calendars_dir = get_calendars_dir()?;
+    let backups_dir = calendars_dir.join(".backups");
+    fs::create_dir_all(&backups_dir)
+        .map_err(|e| format!("Failed to create backups directory: {}", e))?;
+    
+    let zip_path = backups_dir.join(format!("full-{}.zip", Utc::now().format("%Y%m%dT%H%M%S")));
+    let file = fs::File::create(&zip_path)
+        .map_err(|e| format!("Failed to create backup archive: {}", e))?;
+    
+    let mut zip = zip::ZipWriter::new(file);
+    add_dir_to_zip(&mut zip, &calendars_dir, &calendars_dir)?;
+    zip.finish()
+        .map_err(|e| format!("Failed to finish backup archive: {}", e))?;
+    
+    log::info!("Backed up calendars to {:?}", zip_path);
+    Ok(zip_path.to_string_lossy().to_string())
+}
+
+// Recursively add a directory's files to a zip, streaming each file
+fn add_dir_to_zip(zip: &mut zip::ZipWriter<fs::File>, root: &Path, dir: &Path) -> Result<(), String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read directory {:?}: {}", dir, e))?;
+    
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        
+        // Entry names inside the archive always use forward slashes
+        let relative = path
+            .strip_prefix(root)
+            .map_err(|e| format!("Failed to compute archive path: {}", e))?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+        
+        if path.is_dir() {
+            if BACKUP_EXCLUDED_DIRS.contains(&name.as_str()) {
+                continue;
+            }
+            add_dir_to_zip(zip, root, &path)?;
+        } else if !name.ends_with(".lock") {
+            zip.start_file(relative, zip::write::SimpleFileOptions::default())
+                .map_err(|e| format!("Failed to add {:?} to backup: {}", path, e))?;
+            let mut source = fs::File::open(&path)
+                .map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+            std::io::copy(&mut source, zip)
+                .map_err(|e| format!("Failed to write {:?} to backup: {}", path, e))?;
+        }
+    }
+    
+    Ok(())
+}
+
+// Split calendar content into sections, recovering from structural damage:
+// unterminated components and VCALENDARs are closed, components found outside a
+// VCALENDAR are wrapped in one, and stray END lines are dropped
+fn parse_calendar_leniently(content: &str) -> (Vec<CalendarSection>, RepairReport) {
+    let mut report = RepairReport::default();
+    let mut sections = Vec::new();
+    let mut section: Option<CalendarSection> = None;
+    let mut component: Option<CalendarComponent> = None;
+    let mut open: Vec<String> = Vec::new(); // Names of the open (nested) components
+    let mut orphans: Vec<CalendarComponent> = Vec::new(); // Components found outside any VCALENDAR
+    
+    // Close the open component, adding END lines for anything left unterminated
+    fn close_component(
+        component: &mut Option<CalendarComponent>,
+        open: &mut Vec<String>,
+        section: &mut Option<CalendarSection>,
+        orphans: &mut Vec<CalendarComponent>,
+        report: &mut RepairReport,
+    ) {
+        if let Some(mut done) = component.take() {
+            report.unterminated_blocks += open.len();
+            while let Some(name) = open.pop() {
+                done.lines.push(format!("END:{}", name));
+            }
+            match section.as_mut() {
+                Some(current) => current.components.push(done),
+                None => orphans.push(done),
+            }
+        }
+    }
+    
+    for line in split_lines(content) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        
+        if let Some(name) = component_marker(line, "BEGIN").map(|n| n.to_ascii_uppercase()) {
+            if name == "VCALENDAR" {
+                close_component(&mut component, &mut open, &mut section, &mut orphans, &mut report);
+                if let Some(done) = section.take() {
+                    report.unterminated_blocks += 1;
+                    sections.push(done);
+                }
+                section = Some(CalendarSection::default());
+            } else if let Some(current) = component.as_mut() {
+                if open.first() == Some(&name) {
+                    // A new top-level component started before the previous one ended
+                    close_component(&mut component, &mut open, &mut section, &mut orphans, &mut report);
+                    component = Some(CalendarComponent { name: name.clone(), lines: vec![line.to_string()] });
+                } else {
+                    current.lines.push(line.to_string());
+                }
+                open.push(name);
+            } else {
+                if section.is_none() {
+                    report.wrapped_orphans += 1;
+                }
+                component = Some(CalendarComponent { name: name.clone(), lines: vec![line.to_string()] });
+                open.push(name);
+            }
+            continue;
+        }
+        
+        if let Some(name) = component_marker(line, "END").map(|n| n.to_ascii_uppercase()) {
+            if name == "VCALENDAR" {
+                close_component(&mut component, &mut open, &mut section, &mut orphans, &mut report);
+                match section.take() {
+                    Some(done) => sections.push(done),
+                    None => report.stray_lines += 1,
+                }
+            } else if let Some(position) = open.iter().rposition(|n| *n == name) {
+                let current = component.as_mut().expect("open components imply a component");
+                while open.len() > position + 1 {
+                    let inner = open.pop().unwrap_or_default();
+                    report.unterminated_blocks += 1;
+                    current.lines.push(format!("END:{}", inner));
+                }
+                open.pop();
+                current.lines.push(line.to_string());
+                if open.is_empty() {
+                    close_component(&mut component, &mut open, &mut section, &mut orphans, &mut report);
+                }
+            } else {
+                report.stray_lines += 1;
+            }
+            continue;
+        }
+        
+        if let Some(current) = component.as_mut() {
+            current.lines.push(line.to_string());
+        } else if let Some(current) = section.as_mut() {
+            current.properties.push(line.to_string());
+        } else {
+            report.stray_lines += 1;
+        }
+    }
+    
+    close_component(&mut component, &mut open, &mut section, &mut orphans, &mut report);
+    if let Some(done) = section.take() {
+        report.unterminated_blocks += 1;
+        sections.push(done);
+    }
+    if !orphans.is_empty() || sections.is_empty() {
+        // Wrap orphans in their own VCALENDAR (the default envelope is added on render)
+        sections.push(CalendarSection { properties: Vec::new(), components: orphans });
+    }
+    
+    (sections, report)
+}
+
+// Whether a raw VTODO has STATUS:COMPLETED
+fn is_component_completed(component: &CalendarComponent) -> bool {
+    component
+        .property("STATUS")
+        .map(|status| status.trim().eq_ignore_ascii_case("COMPLETED"))
+        .unwrap_or(false)
+}
+
+// Remove and return the VTODO components matching a predicate
+fn take_vtodos(sections: &mut [CalendarSection], matches: impl Fn(&CalendarComponent) -> bool) -> Vec<CalendarComponent> {
+    let mut taken = Vec::new();
+    for section in sections.iter_mut() {
+        let (matched, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut section.components)
+            .into_iter()
+            .partition(|component| component.name == "VTODO" && matches(component));
+        section.components = kept;
+        taken.extend(matched);
+    }
+    taken
+}
+
+// Path of a calendar's companion file in a subdirectory next to it, e.g.
+// `work.ics` -> `.archive/work.ics`. Keeping companions out of the calendars
+// directory itself keeps them out of listings, scans and the watcher.
+fn companion_calendar_path(path: &Path, dir: &str) -> PathBuf {
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    parent.join(dir).join(format!("{}.ics", calendar_stem(path)))
+}
+
+// Create the subdirectory a companion calendar lives in
+fn ensure_companion_dir(companion: &Path) -> Result<(), String> {
+    let dir = companion.parent().ok_or("Failed to get parent directory")?;
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))
+}
+
+// Number of distinct UIDs in `uids` that match no VTODO
+fn count_missing_uids(sections: &[CalendarSection], uids: &[String]) -> usize {
+    let present: std::collections::HashSet<String> = sections
+        .iter()
+        .flat_map(|section| &section.components)
+        .filter(|component| component.name == "VTODO")
+        .filter_map(|component| component.uid())
+        .collect();
+    let requested: std::collections::HashSet<&String> = uids.iter().collect();
+    requested.into_iter().filter(|uid| !present.contains(*uid)).count()
+}
+
+// Apply an edit to every VTODO whose UID is in `uids`, returning how many matched
+fn for_each_vtodo(sections: &mut [CalendarSection], uids: &[String], mut edit: impl FnMut(&mut CalendarComponent)) -> usize {
+    let mut changed = 0;
+    for section in sections.iter_mut() {
+        for component in section.components.iter_mut().filter(|c| c.name == "VTODO") {
+            if component.uid().map(|uid| uids.contains(&uid)).unwrap_or(false) {
+                edit(component);
+                changed += 1;
+            }
+        }
+    }
+    changed
+}
+
+// Supported RRULE frequencies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+// The subset of an RRULE used for expansion (FREQ, INTERVAL, COUNT, UNTIL)
+#[derive(Debug, Clone)]
+struct RecurrenceRule {
+    freq: Frequency,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<NaiveDate>,
+}
+
+impl RecurrenceRule {
+    // Parse an RRULE value such as `FREQ=WEEKLY;INTERVAL=2;COUNT=5`.
+    // Other parts (BYDAY, BYMONTHDAY, ...) are ignored.
+    fn parse(value: &str) -> Result<Self, String> {
+        let mut freq = None;
+        let mut interval = 1;
+        let mut count = None;
+        let mut until = None;
+
+        for part in value.split(';') {
+            let Some((key, val)) = part.split_once('=') else {
+                continue;
+            };
+            match key.trim().to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    freq = Some(match val.trim().to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        other => return Err(format!("Unsupported RRULE frequency: {}", other)),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = val.trim().parse::<u32>()
+                        .ok()
+                        .filter(|i| *i > 0)
+                        .ok_or_else(|| format!("Invalid RRULE interval: {}", val))?
+                }
+                "COUNT" => {
+                    count = Some(val.trim().parse::<u32>()
+                        .map_err(|_| format!("Invalid RRULE count: {}", val))?)
+                }
+                "UNTIL" => {
+                    let date_part = val.trim().get(0..8).unwrap_or("");
+                    until = Some(NaiveDate::parse_from_str(date_part, "%Y%m%d")
+                        .map_err(|_| format!("Invalid RRULE until: {}", val))?)
+                }
+                _ => {}
+            }
+        }
+
+        Ok(RecurrenceRule {
+            freq: freq.ok_or("RRULE is missing FREQ")?,
+            interval,
+            count,
+            until,
+        })
+    }
+
+    // Date of the n-th occurrence (0 = the anchor itself), ignoring COUNT/UNTIL
+    fn nth_occurrence(&self, anchor: NaiveDate, n: u32) -> Option<NaiveDate> {
+        let steps = n.checked_mul(self.interval)?;
+        match self.freq {
+            Frequency::Daily => anchor.checked_add_days(chrono::Days::new(steps as u64)),
+            Frequency::Weekly => anchor.checked_add_days(chrono::Days::new(steps as u64 * 7)),
+            Frequency::Monthly => anchor.checked_add_months(chrono::Months::new(steps)),
+            Frequency::Yearly => anchor.checked_add_months(chrono::Months::new(steps.checked_mul(12)?)),
+        }
+    }
+
+    // Whether the n-th occurrence on `date` is still within COUNT/UNTIL
+    fn allows(&self, n: u32, date: NaiveDate) -> bool {
+        self.count.map(|count| n < count).unwrap_or(true)
+            && self.until.map(|until| date <= until).unwrap_or(true)
+    }
+}
+
+// Safety cap on the number of occurrences generated per rule
+const MAX_OCCURRENCES: u32 = 10_000;
+
+// Expand recurring todos into dated instances within [start, end] (YYYY-MM-DD).
+// Each instance keeps the master's UID and carries its occurrence date in
+// `recurrence_id`; non-recurring todos are returned unchanged.
+#[tauri::command]
+async fn expand_recurrences(calendar_path: String, start: String, end: String) -> Result<Vec<Todo>, String> {
+    let path = resolve_calendar_path(&calendar_path, false)?;
+    let start = NaiveDate::parse_from_str(&start, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid start date: {}", e))?;
+    let end = NaiveDate::parse_from_str(&end, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid end date: {}", e))?;
+    let calendar_name = calendar_stem(&path);
+    
+    let mut todos = Vec::new();
+    for (block, section) in parse_calendar_sections(&read_calendar_file(&path)?).iter().enumerate() {
+        for component in section.components.iter().filter(|c| c.name == "VTODO") {
+            let mut todo = component.to_todo(&calendar_name)?;
+            todo.calendar_block = block;
+            
+            let anchor = todo.due_date.as_deref()
+                .and_then(|due| NaiveDate::parse_from_str(due, "%Y-%m-%d").ok());
+            let rule = todo.rrule.as_deref().map(RecurrenceRule::parse);
+            
+            match (rule, anchor) {
+                (Some(Ok(rule)), Some(anchor)) => {
+                    todos.extend(expand_rule(&todo, &rule, anchor, start, end));
+                }
+                (Some(Err(e)), _) => {
+                    log::warn!("Skipping expansion of todo {}: {}", todo.id, e);
+                    todos.push(todo);
+                }
+                _ => todos.push(todo),
+            }
+        }
+    }
+    
+    Ok(todos)
+}
+
+// Generate the instances of a recurring todo that fall within [start, end]
+fn expand_rule(todo: &Todo, rule: &RecurrenceRule, anchor: NaiveDate, start: NaiveDate, end: NaiveDate) -> Vec<Todo> {
+    let mut instances = Vec::new();
+    for n in 0..MAX_OCCURRENCES {
+        let Some(date) = rule.nth_occurrence(anchor, n) else {
+            break;
+        };
+        if !rule.allows(n, date) || date > end {
+            break;
+        }
+        if date >= start {
+            let occurrence = date.format("%Y-%m-%d").to_string();
+            instances.push(Todo {
+                due_date: Some(occurrence.clone()),
+                recurrence_id: Some(occurrence),
+                ..todo.clone()
+            });
+        }
+    }
+    instances
+}
+
+// Parse a VTODO from raw iCalendar lines
+fn parse_vtodo_from_lines(lines: &[&str], calendar_name: &str) -> Result<Todo, String> {
+    let mut id = String::new();
+    let mut title = String::new();
+    let mut description = String::new();
+    let mut completed = false;
+    let mut priority = "medium".to_string();
+    let mut categories = Vec::new();
+    let mut due_date = None;
+    let mut created_at = None;
+    let mut location = None;
+    let mut url = None;
+    let mut completed_at = None;
+    let mut rrule = None;
+    let mut trashed_at = None;
+    let mut snooze_until = None;
+    let mut due_style = None;
+    let mut duration = None;
+    let mut contact = None;
+    let mut contact_params = Vec::new();
+    let mut parent_uid = None;
+    let mut start_style = None;
+    let mut alarms = Vec::new();
+    let mut due_all_day = None;
+    let mut start_all_day = None;
+    let mut due_time = None;
+    let mut attachments = Vec::new();
+    let mut comments = Vec::new();
+    let mut class = None;
+    let mut geo = None;
+    let mut start_date = None;
+    let mut organizer = None;
+    let mut dtstamp = None;
+    let mut attendees = Vec::new();
+    let mut resources = Vec::new();
+    let mut summary_params = Vec::new();
+    let mut description_params = Vec::new();
+    let mut has_description = false;
+    let mut has_created = false;
+    let mut nested_lines: Vec<String> = Vec::new();
+    let mut nested_depth = 0;
+    
+    let unfolded = unfold_lines(lines);
+    let unfolded: Vec<&str> = unfolded.iter().map(String::as_str).collect();
+    
+    for line in join_quoted_printable_lines(&unfolded) {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        
+        // Nested components (VALARM) have their own properties; collect them separately
+        if component_marker(line, "BEGIN").is_some() {
+            nested_depth += 1;
+        }
+        if nested_depth > 0 {
+            nested_lines.push(line.to_string());
+            if component_marker(line, "END").is_some() {
+                nested_depth -= 1;
+                if nested_depth == 0 {
+                    if let Some(alarm) = parse_valarm(&nested_lines) {
+                        alarms.push(alarm);
+                    }
+                    nested_lines.clear();
+                }
+            }
+            continue;
+        }
+        
+        if let Some((property_name, raw_value)) = split_property_line(line) {
+            
+            // Handle properties with parameters (e.g., DUE;VALUE=DATE)
+            let base_property = if let Some(semicolon_pos) = property_name.find(';') {
+                &property_name[..semicolon_pos]
+            } else {
+                property_name
+            };
+            
+            // Decode legacy ENCODING=QUOTED-PRINTABLE values
+            let params = parse_property_params(property_name);
+            let decoded_value;
+            let property_value = if is_quoted_printable(&params) {
+                decoded_value = decode_property_bytes(&decode_quoted_printable(raw_value), &params);
+                decoded_value.as_str()
+            } else {
+                raw_value
+            };
+            
+            match base_property {
+                "UID" => id = property_value.to_string(),
+                "SUMMARY" => {
+                    title = unescape_ical_text(property_value);
+                    summary_params = text_params(&params);
+                },
+                "DESCRIPTION" => {
+                    // DESCRIPTION may appear only once in a VTODO; keep the first
+                    if has_description {
+                        log::warn!("Ignoring extra DESCRIPTION in VTODO");
+                    } else {
+                        description = unescape_ical_text(property_value);
+                        description_params = text_params(&params);
+                        has_description = true;
+                    }
+                },
+                "COMMENT" => comments.push(unescape_ical_text(property_value)),
+                "ORGANIZER" => organizer = Some(Attendee::from_property(property_value, params.clone())),
+                "ATTENDEE" => attendees.push(Attendee::from_property(property_value, params.clone())),
+                "GEO" => match parse_geo(property_value) {
+                    Ok(position) => geo = Some(position),
+                    Err(e) => log::warn!("Ignoring GEO: {}", e),
+                },
+                "CLASS" => {
+                    let value = property_value.trim().to_ascii_uppercase();
+                    if ACCESS_CLASSES.contains(&value.as_str()) {
+                        class = Some(value);
+                    } else {
+                        log::warn!("Unknown CLASS '{}', treating as PUBLIC", property_value);
+                        class = Some("PUBLIC".to_string());
+                    }
+                },
+                "STATUS" => {
+                    completed = property_value == "COMPLETED";
+                },
+                "PRIORITY" => {
+                    priority = match property_value {
+                        "1" | "2" | "3" => "high",
+                        "4" | "5" | "6" => "medium",
+                        "7" | "8" | "9" => "low",
+                        _ => "medium",
+                    }.to_string();
+                },
+                "CATEGORIES" => categories.extend(split_ical_list(property_value)),
+                "LOCATION" => {
+                    location = Some(unescape_ical_text(property_value));
+                },
+                "RESOURCES" => resources.extend(split_ical_list(property_value)),
+                "RELATED-TO" => {
+                    // RELTYPE defaults to PARENT
+                    let reltype = param_value(&params, "RELTYPE").unwrap_or("PARENT");
+                    if reltype.eq_ignore_ascii_case("PARENT") {
+                        parent_uid = Some(property_value.trim().to_string());
+                    }
+                },
+                "CONTACT" => {
+                    contact = Some(unescape_ical_text(property_value));
+                    contact_params = text_params(&params);
+                },
+                "URL" => {
+                    // URI value type, not TEXT - keep it verbatim
+                    url = Some(property_value.to_string());
+                },
+                "COMPLETED" => {
+                    completed_at = parse_ical_datetime(property_value);
+                },
+                "RRULE" => {
+                    rrule = Some(property_value.to_string());
+                },
+                "ATTACH" => {
+                    let mime = param_value(&params, "FMTTYPE").map(str::to_string);
+                    let is_binary = param_value(&params, "ENCODING").is_some_and(|v| v.eq_ignore_ascii_case("BASE64"))
+                        || param_value(&params, "VALUE").is_some_and(|v| v.eq_ignore_ascii_case("BINARY"));
+                    if is_binary {
+                        use base64::Engine;
+                        match base64::engine::general_purpose::STANDARD.decode(property_value.trim()) {
+                            Ok(data) => attachments.push(Attachment { uri: None, data: Some(data), mime }),
+                            Err(e) => log::warn!("Skipping ATTACH with invalid base64: {}", e),
+                        }
+                    } else {
+                        attachments.push(Attachment { uri: Some(property_value.to_string()), data: None, mime });
+                    }
+                },
+                SNOOZE_PROPERTY => snooze_until = parse_ical_datetime(property_value),
+                "DURATION" => {
+                    if is_ical_duration(property_value) {
+                        duration = Some(property_value.trim().to_string());
+                    } else {
+                        log::warn!("Ignoring invalid DURATION '{}'", property_value);
+                    }
+                },
+                "X-TRASHED-AT" => {
+                    trashed_at = parse_ical_datetime(property_value);
+                },
+                "DUE" => {
+                    // Parse iCalendar date format (YYYYMMDD or YYYYMMDDTHHMMSSZ)
+                    if property_value.len() >= 8 {
+                        let date_part = &property_value[0..8];
+                        if let Ok(year) = date_part[0..4].parse::<i32>() {
+                            if let Ok(month) = date_part[4..6].parse::<u32>() {
+                                if let Ok(day) = date_part[6..8].parse::<u32>() {
+                                    if let Some(date) = NaiveDate::from_ymd_opt(year, month, day) {
+                                        due_date = Some(date.format("%Y-%m-%d").to_string());
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    due_all_day = Some(is_date_value(property_value, &params));
+                    due_style = Some(DateStyle::detect(property_value, &params));
+                    if due_all_day == Some(false) {
+                        if let Some(due) = parse_ical_instant(property_value, &params) {
+                            due_date = Some(due.format("%Y-%m-%d").to_string());
+                            due_time = Some(due.format("%H:%M:%S").to_string());
+                        }
+                    }
+                },
+                "DTSTART" => {
+                    start_all_day = Some(is_date_value(property_value, &params));
+                    start_style = Some(DateStyle::detect(property_value, &params));
+                    start_date = if start_all_day == Some(true) {
+                        NaiveDate::parse_from_str(property_value.get(0..8).unwrap_or(""), "%Y%m%d")
+                            .ok()
+                            .map(|d| d.format("%Y-%m-%d").to_string())
+                    } else {
+                        parse_ical_instant(property_value, &params).map(|dt| dt.format("%Y-%m-%dT%H:%M:%S").to_string())
+                    };
+                },
+                "CREATED" | "DTSTAMP" => {
+                    // DTSTAMP stands in for CREATED only when the todo has no CREATED,
+                    // so a later DTSTAMP can't overwrite the creation time
+                    if base_property == "DTSTAMP" {
+                        dtstamp = parse_ical_datetime(property_value);
+                        if has_created {
+                            continue;
+                        }
+                    } else {
+                        has_created = true;
+                    }
+                    log::trace!("Parsing {} field: '{}' (len: {})", base_property, property_value, property_value.len());
+                    // Parse iCalendar datetime format (YYYYMMDDTHHMMSSZ)
+                    if property_value.len() >= 15 && property_value.contains('T') {
+                        let date_part = &property_value[0..8];
+                        let time_part = &property_value[9..15];
+                        log::trace!("  Date part: '{}', Time part: '{}'", date_part, time_part);
+                        
+                        if let Ok(year) = date_part[0..4].parse::<i32>() {
+                            if let Ok(month) = date_part[4..6].parse::<u32>() {
+                                if let Ok(day) = date_part[6..8].parse::<u32>() {
+                                    if let Ok(hour) = time_part[0..2].parse::<u32>() {
+                                        if let Ok(minute) = time_part[2..4].parse::<u32>() {
+                                            if let Ok(second) = time_part[4..6].parse::<u32>() {
+                                                if let Some(dt) = NaiveDate::from_ymd_opt(year, month, day)
+                                                    .and_then(|d| d.and_hms_opt(hour, minute, second)) {
+                                                    let formatted = dt.format("%Y-%m-%dT%H:%M:%S").to_string();
+                                                    log::trace!("  Successfully parsed {} to: '{}'", base_property, formatted);
+                                                    created_at = Some(formatted);
+                                                } else {
+                                                    log::trace!("  Failed to create datetime from {}-{}-{} {}:{}:{}", year, month, day, hour, minute, second);
+                                                }
+                                            } else {
+                                                log::trace!("  Failed to parse second: '{}'", &time_part[4..6]);
+                                            }
+                                        } else {
+                                            log::trace!("  Failed to parse minute: '{}'", &time_part[2..4]);
+                                        }
+                                    } else {
+                                        log::trace!("  Failed to parse hour: '{}'", &time_part[0..2]);
+                                    }
+                                } else {
+                                    log::trace!("  Failed to parse day: '{}'", &date_part[6..8]);
+                                }
+                            } else {
+                                log::trace!("  Failed to parse month: '{}'", &date_part[4..6]);
+                            }
+                        } else {
+                            log::trace!("  Failed to parse year: '{}'", &date_part[0..4]);
+                        }
+                    } else if property_value.len() == 8 {
+                        log::trace!("  Parsing as date-only format");
+                        // Handle date-only format (YYYYMMDD)
+                        if let Ok(year) = property_value[0..4].parse::<i32>() {
+                            if let Ok(month) = property_value[4..6].parse::<u32>() {
+                                if let Ok(day) = property_value[6..8].parse::<u32>() {
+                                    if let Some(date) = NaiveDate::from_ymd_opt(year, month, day) {
+                                        let formatted = date.format("%Y-%m-%d").to_string();
+                                        log::trace!("  Successfully parsed {} to: '{}'", base_property, formatted);
+                                        created_at = Some(formatted);
+                                    } else {
+                                        log::trace!("  Failed to create date from {}-{}-{}", year, month, day);
+                                    }
+                                } else {
+                                    log::trace!("  Failed to parse day: '{}'", &property_value[6..8]);
+                                }
+                            } else {
+                                log::trace!("  Failed to parse month: '{}'", &property_value[4..6]);
+                            }
+                        } else {
+                            log::trace!("  Failed to parse year: '{}'", &property_value[0..4]);
+                        }
+                    } else {
+                        log::trace!("  Field length {} is not 8 or >=15, skipping", property_value.len());
+                    }
+                },
+                _ => {} // Ignore other properties
+            }
+        }
+    }
+    
+    // Generate ID if not present. Parsing doesn't read the settings, so this
+    // stand-in UID uses the default domain.
+    if id.is_empty() {
+        id = new_uid(DEFAULT_UID_DOMAIN);
+    }
+    
+    // Set default title if empty
+    if title.is_empty() {
+        title = "Untitled Task".to_string();
+    }
+    
+    Ok(Todo {
+        id,
+        title,
+        description,
+        completed,
+        priority,
+        categories,
+        due_date,
+        created_at,
+        calendar_name: calendar_name.to_string(),
+        location,
+        url,
+        completed_at,
+        calendar_block: 0,
+        recurrence_id: None,
+        rrule,
+        trashed_at,
+        alarms,
+        all_day: due_all_day.or(start_all_day).unwrap_or(true),
+        due_time,
+        attachments,
+        comments,
+        class,
+        geo,
+        start_date,
+        organizer,
+        attendees,
+        dtstamp,
+        resources,
+        summary_params,
+        description_params,
+        snooze_until,
+        due_style,
+        start_style,
+        duration,
+        contact,
+        contact_params,
+        parent_uid,
+    })
+}
+
+// A DUE/DTSTART value is date-only if it says VALUE=DATE or is a bare YYYYMMDD
+fn is_date_value(value: &str, params: &[(String, String)]) -> bool {
+    param_value(params, "VALUE").is_some_and(|v| v.eq_ignore_ascii_case("DATE")) || value.trim().len() == 8
+}
+
+// Parse a GEO value ("<lat>;<lon>"), checking both are in range
+fn parse_geo(value: &str) -> Result<(f64, f64), String> {
+    let (lat, lon) = value
+        .split_once(';')
+        .ok_or_else(|| format!("Expected '<latitude>;<longitude>', got '{}'", value))?;
+    let lat: f64 = lat.trim().parse().map_err(|_| format!("Invalid latitude '{}'", lat))?;
+    let lon: f64 = lon.trim().parse().map_err(|_| format!("Invalid longitude '{}'", lon))?;
+    validate_geo(lat, lon)?;
+    Ok((lat, lon))
+}
+
+fn validate_geo(lat: f64, lon: f64) -> Result<(), String> {
+    if !(-90.0..=90.0).contains(&lat) {
+        return Err(format!("Latitude out of range: {}", lat));
+    }
+    if !(-180.0..=180.0).contains(&lon) {
+        return Err(format!("Longitude out of range: {}", lon));
+    }
+    Ok(())
+}
+
+// Allowed CLASS values
+const ACCESS_CLASSES: [&str; 3] = ["PUBLIC", "PRIVATE", "CONFIDENTIAL"];
+
+// Parse a VALARM block (BEGIN through END lines, already unfolded). Other nested
+// components and alarms with an invalid trigger are skipped.
+fn parse_valarm(lines: &[String]) -> Option<Alarm> {
+    if !lines.first().and_then(|line| component_marker(line, "BEGIN")).is_some_and(|name| name.eq_ignore_ascii_case("VALARM")) {
+        return None;
+    }
+    
+    let mut action = None;
+    let mut trigger = None;
+    let mut description = None;
+    for line in &lines[1..lines.len() - 1] {
+        let Some((property_name, value)) = split_property_line(line) else {
+            continue;
+        };
+        match property_name.split(';').next().unwrap_or("").to_ascii_uppercase().as_str() {
+            "ACTION" => action = Some(value.to_string()),
+            "TRIGGER" => trigger = Some(value.to_string()),
+            "DESCRIPTION" => description = Some(unescape_ical_text(value)),
+            _ => {}
+        }
+    }
+    
+    let alarm = Alarm {
+        action: action.unwrap_or_else(|| "DISPLAY".to_string()),
+        trigger: trigger?,
+        description,
+    };
+    match alarm.validate() {
+        Ok(()) => Some(alarm),
+        Err(e) => {
+            log::warn!("Skipping VALARM: {}", e);
+            None
+        }
+    }
+}
+
+// Length of an iCalendar DURATION value, or None if it is malformed
+fn parse_ical_duration(value: &str) -> Option<chrono::Duration> {
+    if !is_ical_duration(value) {
+        return None;
+    }
+    let value = value.trim();
+    let negative = value.starts_with('-');
+    
+    let mut seconds: i64 = 0;
+    let mut number: i64 = 0;
+    for ch in value.chars() {
+        match ch {
+            '0'..='9' => number = number.checked_mul(10)?.checked_add(i64::from(ch as u8 - b'0'))?,
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                let unit = match ch {
+                    'W' => 7 * 86_400,
+                    'D' => 86_400,
+                    'H' => 3_600,
+                    'M' => 60, // Only valid after T, so never months
+                    _ => 1,
+                };
+                seconds = seconds.checked_add(number.checked_mul(unit)?)?;
+                number = 0;
+            }
+            _ => {}
+        }
+    }
+    chrono::Duration::try_seconds(if negative { -seconds } else { seconds })
+}
+
+// Check an iCalendar DURATION value such as -PT1H, P1D, or +P1DT12H30M
+fn is_ical_duration(value: &str) -> bool {
+    let value = value.trim();
+    let value = value.strip_prefix(['+', '-']).unwrap_or(value);
+    let Some(rest) = value.strip_prefix('P') else {
+        return false;
+    };
+    
+    // Each part is a sequence of <digits><unit>, with units in the given order
+    fn units_valid(part: &str, units: &str) -> bool {
+        let mut remaining = units;
+        let mut digits = 0;
+        for ch in part.chars() {
+            if ch.is_ascii_digit() {
+                digits += 1;
+                continue;
+            }
+            match remaining.find(ch) {
+                Some(pos) if digits > 0 => {
+                    remaining = &remaining[pos + 1..];
+                    digits = 0;
+                }
+                _ => return false,
+            }
+        }
+        digits == 0
+    }
+    
+    match rest.split_once('T') {
+        Some((date, time)) => !time.is_empty() && units_valid(date, "WD") && units_valid(time, "HMS"),
+        None => !rest.is_empty() && units_valid(rest, "WD"),
+    }
+}
+
+// Split content into lines on CRLF, LF, or a lone CR (classic Mac exports)
+fn split_lines(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .split('\n')
+        .flat_map(|line| line.strip_suffix('\r').unwrap_or(line).split('\r'))
+}
+
+// Unfold RFC 5545 folded lines: a line starting with a space or tab continues the previous one
+fn unfold_lines(lines: &[&str]) -> Vec<String> {
+    let mut unfolded: Vec<String> = Vec::with_capacity(lines.len());
+    for line in lines {
+        match (line.strip_prefix([' ', '\t']), unfolded.last_mut()) {
+            (Some(continuation), Some(previous)) => previous.push_str(continuation),
+            _ => unfolded.push(line.to_string()),
+        }
+    }
+    unfolded
+}
+
+// Split a content line into its name (with parameters) and value at the first colon
+// outside double quotes, so values like `https://...` and quoted parameters stay intact
+fn split_property_line(line: &str) -> Option<(&str, &str)> {
+    let mut in_quotes = false;
+    for (pos, ch) in line.char_indices() {
+        match ch {
+            '"' => in_quotes = !in_quotes,
+            ':' if !in_quotes => return Some((&line[..pos], &line[pos + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Parse the parameters of a property name (e.g. DESCRIPTION;ENCODING=QUOTED-PRINTABLE)
+// into uppercase keys and values. A single value is unquoted; a multi-valued
+// parameter (MEMBER="mailto:a","mailto:b") keeps every item quoted, as written back.
+fn parse_property_params(property_name: &str) -> Vec<(String, String)> {
+    split_unquoted(property_name, ';')
+        .into_iter()
+        .skip(1)
+        .filter_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            let items: Vec<&str> = split_unquoted(value, ',').into_iter().map(|item| item.trim().trim_matches('"')).collect();
+            let value = match items.as_slice() {
+                [single] => single.to_string(),
+                _ => items.iter().map(|item| format!("\"{}\"", item)).collect::<Vec<_>>().join(","),
+            };
+            Some((key.trim().to_ascii_uppercase(), value))
+        })
+        .collect()
+}
+
+// Split on a separator outside double quotes
+fn split_unquoted(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (pos, ch) in text.char_indices() {
+        if ch == '"' {
+            in_quotes = !in_quotes;
+        } else if ch == separator && !in_quotes {
+            parts.push(&text[start..pos]);
+            start = pos + ch.len_utf8();
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+// Look up a parameter value by (uppercase) name
+fn param_value<'a>(params: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    params.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+// Check whether a property declares ENCODING=QUOTED-PRINTABLE
+fn is_quoted_printable(params: &[(String, String)]) -> bool {
+    param_value(params, "ENCODING")
+        .map(|v| v.eq_ignore_ascii_case("QUOTED-PRINTABLE"))
+        .unwrap_or(false)
+}
+
+// Join quoted-printable soft line breaks (a trailing `=` continues onto the next line)
+fn join_quoted_printable_lines(lines: &[&str]) -> Vec<String> {
+    let mut joined: Vec<String> = Vec::with_capacity(lines.len());
+    let mut continuing = false;
+
+    for line in lines {
+        if continuing {
+            if let Some(last) = joined.last_mut() {
+                last.pop(); // Drop the soft break `=`
+                last.push_str(line.trim());
+                continuing = last.ends_with('=');
+                continue;
+            }
+        }
+
+        let line = line.trim();
+        continuing = line.ends_with('=')
+            && split_property_line(line)
+                .map(|(name, _)| is_quoted_printable(&parse_property_params(name)))
+                .unwrap_or(false);
+        joined.push(line.to_string());
+    }
+
+    joined
+}
+
+// Decode a quoted-printable value into raw bytes (`=XX` escapes, `=` soft breaks)
+fn decode_quoted_printable(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'=' {
+            let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            if i + 1 == bytes.len() {
+                // Trailing soft line break
+                break;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    decoded
+}
+
+// Convert decoded property bytes to a String, honoring the CHARSET parameter (default UTF-8)
+fn decode_property_bytes(bytes: &[u8], params: &[(String, String)]) -> String {
+    let encoding = param_value(params, "CHARSET")
+        .and_then(|charset| encoding_rs::Encoding::for_label(charset.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+// A top-level component (VTODO, VEVENT, VTIMEZONE, ...) kept as raw lines,
+// including its BEGIN/END lines and any nested components
+#[derive(Debug, Clone)]
+struct CalendarComponent {
+    name: String,
+    lines: Vec<String>,
+}
+
+impl CalendarComponent {
+    fn render_into(&self, out: &mut String) {
+        for line in &self.lines {
+            out.push_str(line);
+            out.push_str("\r\n");
+        }
+    }
+
+    // Line ranges [start, end) of this component's own properties, including folded
+    // continuation lines and excluding BEGIN/END and nested components (e.g. VALARM)
+    fn property_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        let mut depth = 0;
+        for (i, line) in self.lines.iter().enumerate() {
+            if line.starts_with([' ', '\t']) {
+                if let Some(last) = ranges.last_mut() {
+                    if last.1 == i {
+                        last.1 = i + 1;
+                    }
+                }
+                continue;
+            }
+            if component_marker(line, "BEGIN").is_some() {
+                depth += 1;
+            } else if component_marker(line, "END").is_some() {
+                depth -= 1;
+            } else if depth == 1 {
+                ranges.push((i, i + 1));
+            }
+        }
+        ranges
+    }
+
+    // Unfolded raw value of the first occurrence of a property
+    fn property(&self, name: &str) -> Option<String> {
+        self.property_ranges().into_iter().find_map(|(start, end)| {
+            let refs: Vec<&str> = self.lines[start..end].iter().map(String::as_str).collect();
+            let line = unfold_lines(&refs).concat();
+            if property_base_name(&line).as_deref() != Some(name) {
+                return None;
+            }
+            split_property_line(&line).map(|(_, value)| value.to_string())
+        })
+    }
+
+    fn uid(&self) -> Option<String> {
+        self.property("UID")
+    }
+
+    // Parse this VTODO component into a Todo
+    fn to_todo(&self, calendar_name: &str) -> Result<Todo, String> {
+        let terminated = self.lines.last().map(|line| component_marker(line, "END").is_some()).unwrap_or(false);
+        let end = if terminated { self.lines.len() - 1 } else { self.lines.len() };
+        let inner = self.lines.get(1..end).unwrap_or(&[]);
+        let refs: Vec<&str> = inner.iter().map(String::as_str).collect();
+        parse_vtodo_from_lines(&refs, calendar_name)
+    }
+
+    // Remove every occurrence of a property
+    fn remove_property(&mut self, name: &str) {
+        for (start, end) in self.property_ranges().into_iter().rev() {
+            if property_base_name(&self.lines[start]).as_deref() == Some(name) {
+                self.lines.drain(start..end);
+            }
+        }
+    }
+
+    // Replace all occurrences of a property with a single content line
+    fn set_property(&mut self, name: &str, line: String) {
+        self.remove_property(name);
+        let end = self.lines.len().saturating_sub(1);
+        self.lines.insert(end, line);
+    }
+}
+
+// A VCALENDAR block: its envelope property lines and its components
+#[derive(Debug, Clone, Default)]
+struct CalendarSection {
+    properties: Vec<String>,
+    components: Vec<CalendarComponent>,
+}
+
+// Name of the component opened or closed by a BEGIN:/END: line
+fn component_marker<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    let (name, value) = split_property_line(line.trim())?;
+    name.eq_ignore_ascii_case(marker).then_some(value.trim())
+}
+
+// Our PRODID, written when a calendar doesn't declare one
+const DEFAULT_PRODID: &str = "PRODID:-//Todo Calendar//Todo Calendar//EN";
+
+// Base (uppercase) property name of a content line, without parameters
+fn property_base_name(line: &str) -> Option<String> {
+    let (name, _) = split_property_line(line.trim())?;
+    Some(name.split(';').next().unwrap_or(name).to_ascii_uppercase())
+}
+
+// VCALENDAR envelope to write: the existing properties, with VERSION and PRODID
+// added if missing (or a default envelope for new files)
+fn envelope_properties(existing: &[String]) -> Vec<String> {
+    if existing.is_empty() {
+        return vec![
+            "VERSION:2.0".to_string(),
+            DEFAULT_PRODID.to_string(),
+            "CALSCALE:GREGORIAN".to_string(),
+        ];
+    }
+
+    let has = |name: &str| existing.iter().any(|line| property_base_name(line).as_deref() == Some(name));
+    let mut properties = Vec::with_capacity(existing.len() + 2);
+    if !has("VERSION") {
+        properties.push("VERSION:2.0".to_string());
+    }
+    if !has("PRODID") {
+        properties.push(DEFAULT_PRODID.to_string());
+    }
+    properties.extend(existing.iter().cloned());
+    properties
+}
+
+// Split calendar content into VCALENDAR sections with raw components
+fn parse_calendar_sections(content: &str) -> Vec<CalendarSection> {
+    let mut sections = Vec::new();
+    let mut section: Option<CalendarSection> = None;
+    let mut component: Option<CalendarComponent> = None;
+    let mut depth = 0;
+
+    for line in split_lines(content) {
+        if let Some(open) = component.as_mut() {
+            open.lines.push(line.to_string());
+            if component_marker(line, "BEGIN").is_some() {
+                depth += 1;
+            } else if component_marker(line, "END").is_some() {
+                depth -= 1;
+                if depth == 0 {
+                    if let (Some(done), Some(current)) = (component.take(), section.as_mut()) {
+                        current.components.push(done);
+                    }
+                }
+            }
+            continue;
+        }
+
+        if let Some(name) = component_marker(line, "BEGIN") {
+            if name.eq_ignore_ascii_case("VCALENDAR") {
+                section = Some(CalendarSection::default());
+            } else if section.is_some() {
+                component = Some(CalendarComponent {
+                    name: name.to_ascii_uppercase(),
+                    lines: vec![line.to_string()],
+                });
+                depth = 1;
+            }
+        } else if component_marker(line, "END")
+            .map(|name| name.eq_ignore_ascii_case("VCALENDAR"))
+            .unwrap_or(false)
+        {
+            if let Some(done) = section.take() {
+                sections.push(done);
+            }
+        } else if let Some(current) = section.as_mut() {
+            if !line.trim().is_empty() {
+                current.properties.push(line.to_string());
+            }
+        }
+    }
+
+    // Tolerate a missing END:VCALENDAR
+    if let Some(mut current) = section.take() {
+        if let Some(open) = component.take() {
+            current.components.push(open);
+        }
+        sections.push(current);
+    }
+
+    sections
+}
+
+// Parse an iCalendar DATE-TIME (YYYYMMDDTHHMMSS[Z]) into an ISO datetime string
+fn parse_ical_datetime(value: &str) -> Option<String> {
+    let value = value.trim().trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|dt| dt.format("%Y-%m-%dT%H:%M:%S").to_string())
+}
+
+// Parse a timed DUE/DTSTART value. A local time in a known TZID is converted to
+// UTC, as is already the case for a trailing Z; floating times stay as written.
+fn parse_ical_instant(value: &str, params: &[(String, String)]) -> Option<NaiveDateTime> {
+    use chrono::TimeZone;
+    let local = NaiveDateTime::parse_from_str(value.trim().trim_end_matches('Z'), "%Y%m%dT%H%M%S").ok()?;
+    if value.trim().ends_with('Z') {
+        return Some(local);
+    }
+    match param_value(params, "TZID").and_then(|name| name.parse::<chrono_tz::Tz>().ok()) {
+        Some(tz) => tz.from_local_datetime(&local).earliest().map(|dt| dt.naive_utc()),
+        None => Some(local),
+    }
+}
+
+// Format a UTC datetime as an iCalendar DATE-TIME (YYYYMMDDTHHMMSSZ)
+fn format_ical_utc(dt: &NaiveDateTime) -> String {
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        dt.year(), dt.month(), dt.day(), dt.hour(), dt.minute(), dt.second()
+    )
+}
+
+// Turn a phrase like "tomorrow", "in 3 days" or "next monday" into an ISO date
+// (YYYY-MM-DD), relative to `reference` (YYYY-MM-DD, defaults to today's local date)
+#[tauri::command]
+fn parse_due(input: String, reference: Option<String>) -> Result<String, String> {
+    let reference = reference_date(reference.as_deref())?;
+    
+    let phrase = input.trim().to_lowercase();
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+    
+    let date = match words.as_slice() {
+        ["today"] => Some(reference),
+        ["tomorrow"] => reference.succ_opt(),
+        ["yesterday"] => reference.pred_opt(),
+        ["in", amount, unit] => {
+            let amount: u32 = amount
+                .parse()
+                .map_err(|_| format!("Unrecognized due date: {}", input.trim()))?;
+            match unit.trim_end_matches('s') {
+                "day" => reference.checked_add_days(chrono::Days::new(amount.into())),
+                "week" => reference.checked_add_days(chrono::Days::new(u64::from(amount) * 7)),
+                "month" => reference.checked_add_months(chrono::Months::new(amount)),
+                "year" => reference.checked_add_months(chrono::Months::new(amount.saturating_mul(12))),
+                _ => None,
+            }
+        }
+        ["next", "week"] => reference.checked_add_days(chrono::Days::new(7)),
+        ["next", "month"] => reference.checked_add_months(chrono::Months::new(1)),
+        ["next", day] | [day] => parse_weekday(day).and_then(|weekday| {
+            // Always strictly after the reference date, so "monday" on a Monday is a week out
+            let ahead = (weekday.num_days_from_monday() + 7 - reference.weekday().num_days_from_monday()) % 7;
+            let ahead = if ahead == 0 { 7 } else { ahead };
+            reference.checked_add_days(chrono::Days::new(ahead.into()))
+        }).or_else(|| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()),
+        _ => None,
+    };
+    
+    date.map(|d| d.format("%Y-%m-%d").to_string())
+        .ok_or_else(|| format!("Unrecognized due date: {}", input.trim()))
+}
+
+// Describe a due date (ISO date or datetime) relative to `reference`
+// (YYYY-MM-DD, defaults to today's local date): "today", "tomorrow",
+// "yesterday", "in 5 days" or "3 days ago"
+#[tauri::command]
+fn format_due_relative(due: String, reference: Option<String>) -> Result<String, String> {
+    let reference = reference_date(reference.as_deref())?;
+    let due_day = parse_iso_datetime(due.trim())
+        .ok_or_else(|| format!("Invalid due date '{}'", due))?
+        .date();
+    
+    let days = (due_day - reference).num_days();
+    Ok(match days {
+        0 => "today".to_string(),
+        1 => "tomorrow".to_string(),
+        -1 => "yesterday".to_string(),
+        d if d > 1 => format!("in {} days", d),
+        d => format!("{} days ago", -d),
+    })
+}
+
+// A YYYY-MM-DD reference date, or today's local date if none is given
+fn reference_date(reference: Option<&str>) -> Result<NaiveDate, String> {
+    match reference.map(str::trim).filter(|r| !r.is_empty()) {
+        Some(r) => NaiveDate::parse_from_str(r, "%Y-%m-%d")
+            .map_err(|e| format!("Invalid reference date '{}': {}", r, e)),
+        None => Ok(chrono::Local::now().date_naive()),
+    }
+}
+
+// Full or three-letter English weekday name
+fn parse_weekday(word: &str) -> Option<chrono::Weekday> {
+    use chrono::Weekday;
+    const WEEKDAYS: [(&str, Weekday); 7] = [
+        ("monday", Weekday::Mon),
+        ("tuesday", Weekday::Tue),
+        ("wednesday", Weekday::Wed),
+        ("thursday", Weekday::Thu),
+        ("friday", Weekday::Fri),
+        ("saturday", Weekday::Sat),
+        ("sunday", Weekday::Sun),
+    ];
+    WEEKDAYS
+        .iter()
+        .find(|(name, _)| word == *name || word == &name[..3])
+        .map(|(_, weekday)| *weekday)
+}
+
+// Save todos back to a calendar file, returning the file's new modification token
+#[tauri::command]
+async fn save_todos_to_calendar(
+    calendar_path: String,
+    todos: Vec<Todo>,
+    expected_modified: Option<String>,
+    force: Option<bool>,
+    cache: tauri::State<'_, TodoCache>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    for todo in &todos {
+        todo.validate()?;
+    }
+    let _lock = CalendarLock::acquire(&path)?;
+    
+    // Refuse to overwrite external edits unless forced
+    if !force.unwrap_or(false) {
+        check_not_modified(&path, expected_modified.as_deref())?;
+    }
+    
+    log::info!("Saving {} todos to calendar file: {}", todos.len(), calendar_path);
+    write_todos(&path, &todos, &cache, &app)
+}
+
+// Replace a calendar's VTODOs on disk (the caller holds the calendar lock), then
+// commit and refresh the tray. Returns the file's new modification token.
+fn write_todos(path: &Path, todos: &[Todo], cache: &TodoCache, app: &tauri::AppHandle) -> Result<String, String> {
+    let calendar_content = render_todos_into(path, todos)?;
+    
+    // Write to file
+    log::debug!("Writing calendar content ({} bytes) to file", calendar_content.len());
+    cache.invalidate(path);
+    write_calendar_file(path, &calendar_content)?;
+    
+    log::info!("Successfully saved calendar file");
+    
+    // Versioning is best-effort; a failed commit never fails the save
+    if load_settings().git_commit_on_save {
+        if let Err(e) = commit_calendar_change(path) {
+            log::warn!("Failed to commit {:?} to git: {}", path, e);
+        }
+    }
+    refresh_tray(app);
+    
+    // New modification token, for the caller's next save
+    Ok(modified_token(file_modified_time(path)?))
+}
+
+// How long queue_save waits for further edits before writing
+const SAVE_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(800);
+
+// Queued todos per calendar, with the generation of the save that queued them
+type PendingSaves = HashMap<PathBuf, (u64, Vec<Todo>)>;
+
+// Pending debounced saves; the generation tells a timer whether it is still
+// the latest queued save for its path
+#[derive(Default)]
+pub struct SaveQueue(std::sync::Arc<Mutex<PendingSaves>>);
+
+// Source of save generations. Never reused, so a timer left over from a flushed
+// burst can't mistake a newer save for its own.
+static SAVE_GENERATION: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+impl SaveQueue {
+    // Hold `todos` for `path` and hand them to `flush` after `delay`, unless a
+    // later push for the same path (or take_all) gets there first
+    fn push(
+        &self,
+        path: PathBuf,
+        todos: Vec<Todo>,
+        delay: std::time::Duration,
+        flush: impl FnOnce(&Path, Vec<Todo>) + Send + 'static,
+    ) -> Result<(), String> {
+        let generation = SAVE_GENERATION.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        self.0
+            .lock()
+            .map_err(|_| "Save queue is poisoned".to_string())?
+            .insert(path.clone(), (generation, todos));
+        
+        let queue = std::sync::Arc::clone(&self.0);
+        std::thread::spawn(move || {
+            std::thread::sleep(delay);
+            let todos = {
+                let Ok(mut pending) = queue.lock() else { return };
+                match pending.get(&path) {
+                    Some((latest, _)) if *latest == generation => pending.remove(&path).map(|(_, todos)| todos),
+                    _ => None, // Superseded by a later push, or already flushed
+                }
+            };
+            if let Some(todos) = todos {
+                flush(&path, todos);
+            }
+        });
+        Ok(())
+    }
+    
+    // Remove and return every queued save
+    fn take_all(&self) -> HashMap<PathBuf, Vec<Todo>> {
+        match self.0.lock() {
+            Ok(mut pending) => std::mem::take(&mut *pending).into_iter().map(|(path, (_, todos))| (path, todos)).collect(),
+            Err(_) => HashMap::new(),
+        }
+    }
+}
+
+// Save todos after SAVE_DEBOUNCE, unless another queue_save for the same
+// calendar arrives first; rapid edits then result in a single write
+#[tauri::command]
+async fn queue_save(calendar_path: String, todos: Vec<Todo>, queue: tauri::State<'_, SaveQueue>, app: tauri::AppHandle) -> Result<(), String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    for todo in &todos {
+        todo.validate()?;
+    }
+    
+    queue.push(path, todos, SAVE_DEBOUNCE, move |path, todos| {
+        if let Err(e) = flush_save(&app, path, &todos) {
+            log::error!("Queued save of {:?} failed: {}", path, e);
+        }
+    })
+}
+
+// Write every queued save now (on exit)
+fn flush_queued_saves(app: &tauri::AppHandle) {
+    for (path, todos) in app.state::<SaveQueue>().take_all() {
+        if let Err(e) = flush_save(app, &path, &todos) {
+            log::error!("Queued save of {:?} failed: {}", path, e);
+        }
+    }
+}
+
+fn flush_save(app: &tauri::AppHandle, path: &Path, todos: &[Todo]) -> Result<String, String> {
+    let _lock = CalendarLock::acquire(path)?;
+    log::info!("Flushing queued save of {} todos to {:?}", todos.len(), path);
+    write_todos(path, todos, &app.state::<TodoCache>(), app)
+}
+
+// Dry run of save_todos_to_calendar: the iCalendar text it would write, without
+// touching disk
+#[tauri::command]
+async fn preview_save(calendar_path: String, todos: Vec<Todo>) -> Result<String, String> {
+    let path = resolve_calendar_path(&calendar_path, true)?;
+    for todo in &todos {
+        todo.validate()?;
+    }
+    render_todos_into(&path, &todos)
+}
+
+// Render a calendar file with its VTODOs replaced by `todos`
+fn render_todos_into(path: &Path, todos: &[Todo]) -> Result<String, String> {
+    // Start from the existing file so the envelope (X-WR-CALNAME, colors, ...) and
+    // events, journals, timezones etc. are kept; only the VTODOs are replaced
+    let mut sections = read_calendar_sections(path)?;
+    
+    // Each todo as it is on disk, so only todos whose fields changed get a new
+    // DTSTAMP (property order, folding and unmodeled properties don't count)
+    let calendar_name = calendar_stem(path);
+    let previous: HashMap<String, Todo> = sections
+        .iter()
+        .flat_map(|section| &section.components)
+        .filter(|component| component.name == "VTODO" && component.uid().is_some())
+        .filter_map(|component| component.to_todo(&calendar_name).ok())
+        .map(|todo| (todo.id.clone(), todo))
+        .collect();
+    let now = Utc::now().naive_utc().format("%Y-%m-%dT%H:%M:%S").to_string();
+    
+    for section in &mut sections {
+        section.components.retain(|component| component.name != "VTODO");
+    }
+    // Each todo goes back into the VCALENDAR block it was loaded from
+    let last_section = sections.len() - 1;
+    let timezone = output_timezone();
+    for todo in todos {
+        let unchanged = previous
+            .get(&todo.id)
+            .is_some_and(|old| changed_fields(old, todo).is_ok_and(|fields| fields.is_empty()));
+        // Rebuild rather than patch so DTSTAMP keeps its place in the order
+        let component = if unchanged {
+            vtodo_component(todo, timezone)
+        } else {
+            vtodo_component(&Todo { dtstamp: Some(now.clone()), ..todo.clone() }, timezone)
+        };
+        sections[todo.calendar_block.min(last_section)].components.push(component);
+    }
+    add_missing_vtimezones(&mut sections, timezone);
+    
+    Ok(render_calendar(&sections))
+}
+
+// Enable or disable committing saved calendars to git
+#[tauri::command]
+fn set_git_commit_on_save(enabled: bool) -> Result<(), String> {
+    let mut settings = load_settings();
+    settings.git_commit_on_save = enabled;
+    save_settings(settings)
+}
+
+// Stage and commit a calendar file if it lives in a git work tree
+fn commit_calendar_change(path: &Path) -> Result<(), String> {
+    let dir = path.parent().ok_or("Calendar file has no parent directory")?;
+    let repo = match git2::Repository::discover(dir) {
+        Ok(repo) => repo,
+        Err(_) => {
+            log::debug!("{:?} is not in a git repository, skipping commit", dir);
+            return Ok(());
+        }
+    };
+    let workdir = repo
+        .workdir()
+        .ok_or("Repository has no work tree")?
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve repository work tree: {}", e))?;
+    let relative = path
+        .strip_prefix(&workdir)
+        .map_err(|_| format!("{:?} is outside the repository work tree", path))?;
+    
+    let mut index = repo.index().map_err(|e| format!("Failed to open git index: {}", e))?;
+    index.add_path(relative).map_err(|e| format!("Failed to stage calendar: {}", e))?;
+    index.write().map_err(|e| format!("Failed to write git index: {}", e))?;
+    let tree_id = index.write_tree().map_err(|e| format!("Failed to write git tree: {}", e))?;
+    
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    if parent.as_ref().is_some_and(|commit| commit.tree_id() == tree_id) {
+        return Ok(());
+    }
+    
+    let tree = repo.find_tree(tree_id).map_err(|e| format!("Failed to find git tree: {}", e))?;
+    let signature = repo
+        .signature()
+        .or_else(|_| git2::Signature::now("2DO", "2do@localhost"))
+        .map_err(|e| format!("Failed to create git signature: {}", e))?;
+    let message = format!("Update {} via 2DO", calendar_stem(path));
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    repo.commit(Some("HEAD"), &signature, &signature, &message, &tree, &parents)
+        .map_err(|e| format!("Failed to create git commit: {}", e))?;
+    
+    log::info!("Committed {:?} to git", relative);
+    Ok(())
+}
+
+// Timezone for writing timed due dates, from the settings (None means UTC)
+fn output_timezone() -> Option<chrono_tz::Tz> {
+    let name = load_settings().output_timezone?;
+    match name.parse::<chrono_tz::Tz>() {
+        Ok(tz) => Some(tz),
+        Err(e) => {
+            log::warn!("Ignoring unknown output timezone '{}': {}", name, e);
+            None
+        }
+    }
+}
+
+// Give every section that references `timezone` a VTIMEZONE definition for it
+fn add_missing_vtimezones(sections: &mut [CalendarSection], timezone: Option<chrono_tz::Tz>) {
+    let Some(tz) = timezone else {
+        return;
+    };
+    let reference = format!(";TZID={}:", tz.name());
+    for section in sections.iter_mut() {
+        let defined = section
+            .components
+            .iter()
+            .any(|c| c.name == "VTIMEZONE" && c.property("TZID").as_deref() == Some(tz.name()));
+        let referenced = section
+            .components
+            .iter()
+            .any(|c| c.lines.iter().any(|line| line.contains(&reference)));
+        if referenced && !defined {
+            section.components.insert(0, vtimezone_component(tz, Utc::now().year()));
+        }
+    }
+}
+
+// Build a VTIMEZONE for a zone from its offset transitions in `year`, with
+// yearly rules (e.g. second Sunday of March) so it applies to other years too
+fn vtimezone_component(tz: chrono_tz::Tz, year: i32) -> CalendarComponent {
+    use chrono::{Offset, TimeZone};
+    let offset_at = |utc: &NaiveDateTime| tz.offset_from_utc_datetime(utc).fix().local_minus_utc();
+    let year_start = NaiveDate::from_ymd_opt(year, 1, 1).unwrap_or_default().and_time(chrono::NaiveTime::MIN);
+    
+    // Scan the year day by day, then bisect a day that changed down to the second
+    let mut transitions = Vec::new();
+    let mut previous = offset_at(&year_start);
+    let mut day = year_start;
+    while day.year() == year {
+        let next = day + chrono::Duration::days(1);
+        if offset_at(&next) != previous {
+            // Invariant: the offset at `before` is unchanged, at `after` it has changed
+            let (mut before, mut after) = (day, next);
+            while after - before > chrono::Duration::seconds(1) {
+                let middle = before + (after - before) / 2;
+                if offset_at(&middle) == previous {
+                    before = middle;
+                } else {
+                    after = middle;
+                }
+            }
+            let offset = offset_at(&after);
+            transitions.push((after, previous, offset));
+            previous = offset;
+        }
+        day = next;
+    }
+    
+    let mut lines = vec!["BEGIN:VTIMEZONE".to_string(), format!("TZID:{}", tz.name())];
+    if transitions.is_empty() {
+        lines.extend([
+            "BEGIN:STANDARD".to_string(),
+            "DTSTART:19700101T000000".to_string(),
+            format!("TZOFFSETFROM:{}", format_utc_offset(previous)),
+            format!("TZOFFSETTO:{}", format_utc_offset(previous)),
+            "END:STANDARD".to_string(),
+        ]);
+    }
+    for (instant, from, to) in transitions {
+        // Observances start at the wall-clock time before the change
+        let local = instant + chrono::Duration::seconds(from.into());
+        let kind = if to > from { "DAYLIGHT" } else { "STANDARD" };
+        // The last such weekday of its month becomes -1 (e.g. -1SU), otherwise 1..4
+        let week = if (local + chrono::Duration::days(7)).month() != local.month() {
+            -1
+        } else {
+            (local.day() as i32 - 1) / 7 + 1
+        };
+        let weekday = ["MO", "TU", "WE", "TH", "FR", "SA", "SU"][local.weekday().num_days_from_monday() as usize];
+        lines.extend([
+            format!("BEGIN:{}", kind),
+            format!("DTSTART:{}", local.format("%Y%m%dT%H%M%S")),
+            format!("TZOFFSETFROM:{}", format_utc_offset(from)),
+            format!("TZOFFSETTO:{}", format_utc_offset(to)),
+            format!("RRULE:FREQ=YEARLY;BYMONTH={};BYDAY={}{}", local.month(), week, weekday),
+            format!("END:{}", kind),
+        ]);
+    }
+    lines.push("END:VTIMEZONE".to_string());
+    
+    CalendarComponent {
+        name: "VTIMEZONE".to_string(),
+        lines,
+    }
+}
+
+// Format a UTC offset in seconds as iCalendar's +HHMM / -HHMM
+fn format_utc_offset(seconds: i32) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+}
+
+// Read a calendar's sections, or a single empty section if the file doesn't exist
+fn read_calendar_sections(path: &Path) -> Result<Vec<CalendarSection>, String> {
+    let mut sections = if path.exists() {
+        parse_calendar_sections(&read_calendar_file(path)?)
+    } else {
+        Vec::new()
+    };
+    if sections.is_empty() {
+        sections.push(CalendarSection::default());
+    }
+    Ok(sections)
+}
+
+// Render calendar sections back to iCalendar text
+fn render_calendar(sections: &[CalendarSection]) -> String {
+    let mut calendar_content = String::new();
+    for section in sections {
+        calendar_content.push_str("BEGIN:VCALENDAR\r\n");
+        for line in envelope_properties(&section.properties) {
+            calendar_content.push_str(&line);
+            calendar_content.push_str("\r\n");
+        }
+        for component in &section.components {
+            component.render_into(&mut calendar_content);
+        }
+        calendar_content.push_str("END:VCALENDAR\r\n");
+    }
+    calendar_content
+}
+
+// Apply an in-place edit to a calendar file's sections in one locked
+// read-modify-write, invalidating any cached parse
+fn modify_calendar_file<T>(
+    path: &Path,
+    cache: &TodoCache,
+    edit: impl FnOnce(&mut Vec<CalendarSection>) -> Result<T, String>,
+) -> Result<T, String> {
+    let _lock = CalendarLock::acquire(path)?;
+    let mut sections = read_calendar_sections(path)?;
+    let result = edit(&mut sections)?;
+    
+    cache.invalidate(path);
+    write_calendar_file(path, &render_calendar(&sections))?;
+    Ok(result)
+}
+
+// Build the VTODO component for a todo. Timed due dates are written in
+// `timezone` (with a TZID) when given, otherwise in UTC.
+fn vtodo_component(todo: &Todo, timezone: Option<chrono_tz::Tz>) -> CalendarComponent {
+    let mut lines = vec![
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", todo.id),
+        format!("SUMMARY{}:{}", format_params(&todo.summary_params), escape_ical_text(&todo.title)),
+    ];
+    
+    if !todo.description.is_empty() {
+        lines.push(format!(
+            "DESCRIPTION{}:{}",
+            format_params(&todo.description_params),
+            escape_ical_text(&todo.description)
+        ));
+    }
+    
+    // Status (completed todos also carry a COMPLETED timestamp)
+    if todo.completed {
+        lines.push("STATUS:COMPLETED".to_string());
+        let completed_at = todo
+            .completed_at
+            .as_deref()
+            .and_then(|value| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").ok())
+            .unwrap_or_else(|| Utc::now().naive_utc());
+        lines.push(format!("COMPLETED:{}", format_ical_utc(&completed_at)));
+    } else {
+        lines.push("STATUS:NEEDS-ACTION".to_string());
+    }
+    
+    // Priority (convert back to iCalendar format)
+    lines.push(format!("PRIORITY:{}", ical_priority(&todo.priority).unwrap_or(5)));
+    
+    // Categories, merged into one property
+    let categories = todo_categories(todo);
+    if !categories.is_empty() {
+        lines.push(categories_property(&categories));
+    }
+    
+    // Location
+    if let Some(location) = &todo.location {
+        lines.push(format!("LOCATION:{}", escape_ical_text(location)));
+    }
+    
+    // Resources (one comma-separated line)
+    if !todo.resources.is_empty() {
+        let resources: Vec<String> = todo.resources.iter().map(|r| escape_ical_text(r)).collect();
+        lines.push(format!("RESOURCES:{}", resources.join(",")));
+    }
+    
+    // Contact, with its parameters (e.g. ALTREP)
+    if let Some(contact) = &todo.contact {
+        lines.push(format!("CONTACT{}:{}", format_params(&todo.contact_params), escape_ical_text(contact)));
+    }
+    
+    // Geographic position (six decimals is about 10 cm)
+    if let Some((lat, lon)) = todo.geo {
+        match validate_geo(lat, lon) {
+            Ok(()) => lines.push(format!("GEO:{:.6};{:.6}", lat, lon)),
+            Err(e) => log::warn!("Not writing GEO: {}", e),
+        }
+    }
+    
+    // Access classification
+    if let Some(class) = &todo.class {
+        let class = class.to_ascii_uppercase();
+        if ACCESS_CLASSES.contains(&class.as_str()) {
+            lines.push(format!("CLASS:{}", class));
+        } else {
+            log::warn!("Unknown CLASS '{}', writing PUBLIC", class);
+            lines.push("CLASS:PUBLIC".to_string());
+        }
+    }
+    
+    // URL (URI value, written without text escaping)
+    if let Some(url) = &todo.url {
+        lines.push(format!("URL:{}", url));
+    }
+    
+    // Parent todo (RELTYPE=PARENT is the default)
+    if let Some(parent) = &todo.parent_uid {
+        lines.push(format!("RELATED-TO:{}", parent));
+    }
+    
+    // Recurrence rule (written back verbatim)
+    if let Some(rrule) = &todo.rrule {
+        lines.push(format!("RRULE:{}", rrule));
+    }
+    
+    // Start date (date-only or datetime, in the style it was read in)
+    if let Some(start) = todo.start_date.as_deref() {
+        if let Some(dt) = parse_iso_datetime(start) {
+            lines.push(date_property("DTSTART", dt, start.len() == 10, todo.start_style.as_ref()));
+        }
+    }
+    
+    // Due date
+    if let Some(due_date) = &todo.due_date {
+        if let Ok(date) = NaiveDate::parse_from_str(due_date, "%Y-%m-%d") {
+            if todo.all_day {
+                lines.push(date_property("DUE", date.and_time(chrono::NaiveTime::MIN), true, todo.due_style.as_ref()));
+            } else {
+                let time = todo
+                    .due_time
+                    .as_deref()
+                    .and_then(|time| chrono::NaiveTime::parse_from_str(time, "%H:%M:%S").ok())
+                    .unwrap_or_default();
+                let due = date.and_time(time);
+                // Floating and zoned values keep their form; others go out in the output zone
+                match timezone {
+                    Some(tz) if !matches!(todo.due_style, Some(DateStyle::Floating | DateStyle::Tzid(_))) => {
+                        lines.push(date_property("DUE", due, false, Some(&DateStyle::Tzid(tz.name().to_string()))));
+                    }
+                    _ => lines.push(date_property("DUE", due, false, todo.due_style.as_ref())),
+                }
+            }
+        }
+    }
+    
+    // Duration (validated on save)
+    if let Some(duration) = &todo.duration {
+        lines.push(format!("DURATION:{}", duration.trim()));
+    }
+    
+    // Created date. Only a bare date (as read from a date-only CREATED) is written
+    // date-only; any datetime, including the frontend's toISOString() form, keeps
+    // its time rather than being dropped.
+    if let Some(created_at) = &todo.created_at {
+        if let Ok(date) = NaiveDate::parse_from_str(created_at, "%Y-%m-%d") {
+            lines.push(format!(
+                "CREATED:{:04}{:02}{:02}",
+                date.year(), date.month(), date.day()
+            ));
+        } else if let Some(dt) = parse_iso_datetime(created_at) {
+            lines.push(format!("CREATED:{}", format_ical_utc(&dt)));
+        } else {
+            log::warn!("Not writing unparseable CREATED '{}'", created_at);
+        }
+    }
+    
+    // Timestamp (kept from the file; save_todos_to_calendar bumps it on changes)
+    let dtstamp = todo
+        .dtstamp
+        .as_deref()
+        .and_then(parse_iso_datetime)
+        .unwrap_or_else(|| Utc::now().naive_utc());
+    lines.push(format!("DTSTAMP:{}", format_ical_utc(&dtstamp)));
+    
+    // Snooze (hidden from list_active until then)
+    if let Some(until) = todo.snooze_until.as_deref().and_then(parse_iso_datetime) {
+        lines.push(format!("{}:{}", SNOOZE_PROPERTY, format_ical_utc(&until)));
+    }
+    
+    // Trash stamp, so a trashed todo stays trashed when it is rebuilt
+    if let Some(trashed_at) = todo.trashed_at.as_deref().and_then(parse_iso_datetime) {
+        lines.push(format!("X-TRASHED-AT:{}", format_ical_utc(&trashed_at)));
+    }
+    
+    // Organizer and attendees, with their parameters
+    if let Some(organizer) = &todo.organizer {
+        lines.push(organizer.to_property("ORGANIZER"));
+    }
+    for attendee in &todo.attendees {
+        lines.push(attendee.to_property("ATTENDEE"));
+    }
+    
+    // Comments (may repeat)
+    for comment in &todo.comments {
+        lines.push(format!("COMMENT:{}", escape_ical_text(comment)));
+    }
+    
+    // Attachments (inline binaries are base64-encoded)
+    for attachment in &todo.attachments {
+        let fmttype = attachment
+            .mime
+            .as_deref()
+            .map(|mime| format!(";FMTTYPE={}", mime))
+            .unwrap_or_default();
+        if let Some(data) = &attachment.data {
+            use base64::Engine;
+            lines.push(format!(
+                "ATTACH{};ENCODING=BASE64;VALUE=BINARY:{}",
+                fmttype,
+                base64::engine::general_purpose::STANDARD.encode(data)
+            ));
+        } else if let Some(uri) = &attachment.uri {
+            lines.push(format!("ATTACH{}:{}", fmttype, uri));
+        }
+    }
+    
+    // Alarms, in order
+    for alarm in &todo.alarms {
+        lines.push("BEGIN:VALARM".to_string());
+        lines.push(format!("ACTION:{}", alarm.action));
+        if is_ical_duration(&alarm.trigger) {
+            lines.push(format!("TRIGGER:{}", alarm.trigger.trim()));
+        } else {
+            lines.push(format!("TRIGGER;VALUE=DATE-TIME:{}", alarm.trigger.trim()));
+        }
+        if let Some(description) = &alarm.description {
+            lines.push(format!("DESCRIPTION:{}", escape_ical_text(description)));
+        }
+        lines.push("END:VALARM".to_string());
+    }
+    
+    lines.push("END:VTODO".to_string());
+    
+    // Properties in canonical order (VALARMs last) so saves diff cleanly
+    let mut ordered = Vec::with_capacity(lines.len());
+    canonicalize_component(&lines, &mut ordered);
+    CalendarComponent {
+        name: "VTODO".to_string(),
+        lines: ordered.iter().flat_map(|line| fold_line(line)).collect(),
+    }
+}
+
+// A DUE/DTSTART line in the given style; without one, dates get VALUE=DATE
+// and times are written in UTC
+fn date_property(name: &str, value: NaiveDateTime, all_day: bool, style: Option<&DateStyle>) -> String {
+    match (all_day, style) {
+        (true, Some(DateStyle::BareDate)) => format!("{}:{}", name, value.format("%Y%m%d")),
+        (true, _) => format!("{};VALUE=DATE:{}", name, value.format("%Y%m%d")),
+        (false, Some(DateStyle::Floating)) => format!("{}:{}", name, value.format("%Y%m%dT%H%M%S")),
+        (false, Some(DateStyle::Tzid(tzid))) => {
+            // Timed values in a known zone are held in UTC; unknown zones as written
+            use chrono::TimeZone;
+            let local = match tzid.parse::<chrono_tz::Tz>() {
+                Ok(tz) => tz.from_utc_datetime(&value).naive_local(),
+                Err(_) => value,
+            };
+            format!("{};TZID={}:{}", name, quote_param_value(tzid), local.format("%Y%m%dT%H%M%S"))
+        }
+        (false, _) => format!("{}:{}", name, format_ical_utc(&value)),
+    }
+}
+
+// Helper function to escape text for iCalendar format.
+// Works per character so emoji and combining marks pass through untouched;
+// CR, NUL and other control characters (except tab) are dropped.
+fn escape_ical_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push('\t'),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Split a multi-valued TEXT property on unescaped commas, unescaping each item
+fn split_ical_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, ch) in value.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                items.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&value[start..]);
+    
+    items
+        .into_iter()
+        .map(|item| unescape_ical_text(item.trim()))
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+// Helper to unescape iCalendar text per RFC 5545
+// - \n or \N => newline
+// - \; => ;, \, => ,
+// - \\ => \
+fn unescape_ical_text(text: &str) -> String {
+    // Single left-to-right pass so an escaped backslash is never re-read as
+    // the start of another escape (e.g. `C:\\\\temp` => `C:\\temp`)
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            unescaped.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(';') => unescaped.push(';'),
+            Some(',') => unescaped.push(','),
+            Some('\\') => unescaped.push('\\'),
+            // Unknown escape: keep it as-is
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+// Environment variable controlling log verbosity (error, warn, info, debug, trace, off)
+const LOG_LEVEL_ENV: &str = "TWODO_LOG";
+
+// Log level from the environment, defaulting to info
+fn log_level_from_env() -> log::LevelFilter {
+    std::env::var(LOG_LEVEL_ENV)
+        .ok()
+        .and_then(|level| level.trim().parse().ok())
+        .unwrap_or(log::LevelFilter::Info)
+}
+
+// Logger for headless runs, which have no window and so no log plugin
+struct StderrLogger {
+    level: log::LevelFilter,
+}
+
+impl StderrLogger {
+    // Send log records to stderr; does nothing if a logger is already installed
+    fn install(level: log::LevelFilter) {
+        static LOGGER: OnceLock<StderrLogger> = OnceLock::new();
+        if log::set_logger(LOGGER.get_or_init(|| StderrLogger { level })).is_ok() {
+            log::set_max_level(level);
+        }
+    }
+}
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+    
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+    
+    fn flush(&self) {}
+}
+
+const CLI_USAGE: &str = "Usage: 2do add --calendar <name> --title <text> [--due <date>] [--priority high|medium|low] [--description <text>] [--category <text>]";
+
+// Run a headless CLI command if the arguments name one, returning the exit code.
+// Returns None to start the GUI as usual. Headless runs log to stderr, since the
+// log plugin only starts with the window.
+fn run_cli(args: &[String]) -> Option<i32> {
+    let command = args.first().map(String::as_str);
+    if !matches!(command, Some("add" | "--stdio")) {
+        return None;
+    }
+    
+    StderrLogger::install(log_level_from_env());
+    if let Err(e) = migrate_app_config() {
+        log::warn!("Failed to migrate app config: {}", e);
+    }
+    if command == Some("--stdio") {
+        return Some(run_stdio());
+    }
+    
+    let result = todo_from_add_args(&args[1..]).and_then(|(calendar, todo)| {
+        let path = resolve_calendar_path(&calendar, true)?;
+        append_todo(&path, todo, &TodoCache::default())
+    });
+    match result {
+        Ok(todo) => {
+            println!("{}", todo.id);
+            Some(0)
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("{}", CLI_USAGE);
+            Some(1)
+        }
+    }
+}
+
+// Build the todo for `2do add` from its flags, returning the calendar file name too.
+// The calendar may be given without its .ics extension.
+fn todo_from_add_args(args: &[String]) -> Result<(String, Todo), String> {
+    let mut flags: HashMap<&str, &str> = HashMap::new();
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let name = match flag.as_str() {
+            "--calendar" | "--title" | "--due" | "--priority" | "--description" | "--category" => flag.as_str(),
+            other => return Err(format!("Unknown argument: {}", other)),
+        };
+        let value = iter.next().ok_or_else(|| format!("Missing value for {}", name))?;
+        flags.insert(name, value.as_str());
+    }
+    
+    let calendar = flags.get("--calendar").map(|c| c.trim()).filter(|c| !c.is_empty()).ok_or("Missing --calendar")?;
+    let title = flags.get("--title").map(|t| t.trim()).filter(|t| !t.is_empty()).ok_or("Missing --title")?;
+    let calendar_file = if is_calendar_file(Path::new(calendar)) {
+        calendar.to_string()
+    } else {
+        format!("{}.ics", calendar)
+    };
+    
+    let mut todo = Todo::new(title, &calendar_stem(Path::new(&calendar_file)));
+    if let Some(due) = flags.get("--due") {
+        todo.due_date = Some(parse_due(due.to_string(), None)?);
+    }
+    if let Some(priority) = flags.get("--priority") {
+        let priority = priority.to_ascii_lowercase();
+        if !["high", "medium", "low"].contains(&priority.as_str()) {
+            return Err(format!("Invalid priority: {}", priority));
+        }
+        todo.priority = priority;
+    }
+    if let Some(description) = flags.get("--description") {
+        todo.description = description.to_string();
+    }
+    if let Some(category) = flags.get("--category") {
+        todo.categories = vec![category.to_string()];
+    }
+    
+    Ok((calendar_file, todo))
+}
+
+// One line of `--stdio` input: {"id": ..., "cmd": "...", "args": {...}}
+#[derive(Debug, Deserialize)]
+struct StdioRequest {
+    #[serde(default)]
+    id: serde_json::Value, // Echoed back so callers can match responses
+    cmd: String,
+    #[serde(default)]
+    args: serde_json::Value,
+}
+
+// One line of `--stdio` output: {"id": ..., "ok": true, "result": ...}
+// or {"id": ..., "ok": false, "error": "..."}
+#[derive(Debug, Serialize)]
+struct StdioResponse {
+    id: serde_json::Value,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+// Serve newline-delimited JSON commands from stdin until EOF
+fn run_stdio() -> i32 {
+    let cache = TodoCache::default();
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    
+    for line in std::io::BufRead::lines(stdin.lock()) {
+        let Ok(line) = line else {
+            return 1;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        
+        let response = match serde_json::from_str::<StdioRequest>(&line) {
+            Ok(request) => {
+                let result = dispatch_stdio_command(&request.cmd, &request.args, &cache);
+                StdioResponse {
+                    id: request.id,
+                    ok: result.is_ok(),
+                    error: result.as_ref().err().cloned(),
+                    result: result.ok(),
+                }
+            }
+            Err(e) => StdioResponse {
+                id: serde_json::Value::Null,
+                ok: false,
+                result: None,
+                error: Some(format!("Invalid request: {}", e)),
+            },
+        };
+        
+        let Ok(json) = serde_json::to_string(&response) else {
+            return 1;
+        };
+        if writeln!(stdout, "{}", json).and_then(|_| stdout.flush()).is_err() {
+            return 1;
+        }
+    }
+    0
+}
+
+// Run one stdio command through the same functions the Tauri commands use
+fn dispatch_stdio_command(cmd: &str, args: &serde_json::Value, cache: &TodoCache) -> Result<serde_json::Value, String> {
+    fn arg<T: serde::de::DeserializeOwned>(args: &serde_json::Value, name: &str) -> Result<T, String> {
+        let value = args.get(name).cloned().unwrap_or(serde_json::Value::Null);
+        serde_json::from_value(value).map_err(|e| format!("Invalid argument '{}': {}", name, e))
+    }
+    fn to_json<T: Serialize>(value: T) -> Result<serde_json::Value, String> {
+        serde_json::to_value(value).map_err(|e| format!("Failed to serialize result: {}", e))
+    }
+    
+    match cmd {
+        "get_calendars_path" => to_json(get_calendars_path()?),
+        "list_calendars" => to_json(tauri::async_runtime::block_on(list_calendars())?),
+        "load_todos" => {
+            let path = resolve_calendar_path(&arg::<String>(args, "calendar_path")?, false)?;
+            to_json(parse_todos_from_file(&path)?)
+        }
+        "get_todo" => to_json(tauri::async_runtime::block_on(get_todo_by_id(
+            arg(args, "calendar_path")?,
+            arg(args, "uid")?,
+        ))?),
+        "filter_todos" => {
+            let path = resolve_calendar_path(&arg::<String>(args, "calendar_path")?, false)?;
+            let filter: TodoFilter = arg(args, "filter")?;
+            let mut matching = Vec::new();
+            for todo in parse_todos_from_file(&path)? {
+                if filter.matches(&todo)? {
+                    matching.push(todo);
+                }
+            }
+            to_json(matching)
+        }
+        "add_todo" => {
+            let path = resolve_calendar_path(&arg::<String>(args, "calendar_path")?, true)?;
+            to_json(append_todo(&path, arg(args, "todo")?, cache)?)
+        }
+        "parse_due" => to_json(parse_due(arg(args, "input")?, arg(args, "reference")?)?),
+        _ => Err(format!("Unknown command: {}", cmd)),
+    }
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    // Resolve the app data directory up front so headless commands find the same
+    // calendars and settings as the GUI
+    let context = tauri::generate_context!();
+    if let Some(dir) = app_data_dir(&context.config().identifier) {
+        let _ = APP_DATA_DIR.set(dir);
+    }
+    
+    // Headless CLI commands run and exit before any window is created
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = run_cli(&args) {
+        std::process::exit(code);
+    }
+    
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(
+            tauri_plugin_log::Builder::new()
+                .level(log_level_from_env())
+                .build(),
+        )
+        .manage(TodoCache::default())
+        .manage(SaveQueue::default())
+        .manage(CalendarWatcher::default())
+        .setup(|app| {
+            // Only takes effect if the platform data directory was unknown above
+            if let Ok(dir) = app.path().app_data_dir() {
+                let _ = APP_DATA_DIR.set(dir);
+            }
+            // Runs once the log plugin is up so a failure gets logged
+            if let Err(e) = migrate_app_config() {
+                log::warn!("Failed to migrate app config: {}", e);
+            }
+            setup_tray(app)?;
+            if load_settings().auto_reload {
+                if let Err(e) = start_calendar_watcher(app.handle()) {
+                    log::warn!("Auto-reload is unavailable: {}", e);
+                }
+            }
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![greet, get_calendars_path, list_calendars, load_todos_from_calendar, save_todos_to_calendar, create_calendar, set_calendars_dir, get_todo_by_id, load_todos_with_meta, duplicate_todo, bulk_complete, bulk_delete, parse_ics_string, expand_recurrences, complete_recurring, archive_completed, delete_todo, list_trash, restore_todo, repair_calendar, backup_all, load_todos_page, filter_todos, notify_due_todos, set_due_notifications, parse_due, canonicalize_calendar, set_git_commit_on_save, merge_calendars, sort_todos, add_todo, load_settings, save_settings, import_ics, export_todo, list_due_within, group_by_category, search_todos_fuzzy, get_calendar_color, set_calendar_color, recompute_counts, reschedule_overdue, snooze_todo, list_active, describe_todo, convert_event_to_todo, stream_todos, preview_save, compare_calendars, queue_save, get_calendar_metadata, normalize_categories, format_due_relative, fix_calendar_names, rename_calendar, move_todo, bulk_set_priority, bulk_tag, find_orphans, outline_todos, set_auto_reload, is_auto_reload_enabled, list_categories, list_all_categories, check_all_calendars, load_external_calendar, today_agenda])
+        .build(context)
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                flush_queued_saves(app);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Tests that touch the process environment or a calendars directory run one at a time
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+    
+    // A unique scratch directory, removed when dropped
+    struct TempDir(PathBuf);
+    
+    impl TempDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("2do-test-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(fs::canonicalize(&dir).unwrap())
+        }
+    }
+    
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+    
+    // Run `f` with TWODO_CALENDARS_DIR pointing at a fresh calendars directory.
+    // The settings file lands beside it, inside the same scratch directory.
+    fn with_calendars_dir<T>(f: impl FnOnce(&Path) -> T) -> T {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let root = TempDir::new();
+        let dir = root.0.join("calendars");
+        fs::create_dir_all(&dir).unwrap();
+        std::env::set_var(CALENDARS_DIR_ENV, &dir);
+        forget_known_calendars();
+        f(&dir)
+    }
+    
+    #[test]
+    fn calendars_dir_env_var_takes_precedence() {
+        with_calendars_dir(|dir| {
+            let location = get_calendars_path().unwrap();
+            assert_eq!(location.source, CalendarsSource::Env);
+            assert_eq!(Path::new(&location.path), dir);
+        });
+    }
+    
+    #[test]
+    fn calendars_dir_from_env_var_is_created() {
+        with_calendars_dir(|dir| {
+            let nested = dir.join("nested").join("calendars");
+            std::env::set_var(CALENDARS_DIR_ENV, &nested);
+            assert_eq!(get_calendars_dir().unwrap(), nested);
+            assert!(nested.is_dir());
+        });
+    }
+    
+    #[test]
+    fn calendars_dir_env_var_must_be_a_directory() {
+        with_calendars_dir(|dir| {
+            let file = dir.join("not-a-dir");
+            fs::write(&file, "").unwrap();
+            std::env::set_var(CALENDARS_DIR_ENV, &file);
+            assert!(get_calendars_dir().unwrap_err().contains("not a directory"));
+        });
+    }
+    
+    #[test]
+    fn discovery_skips_debug_and_release_build_dirs() {
+        let scratch = TempDir::new();
+        let calendars = scratch.0.join("project").join("calendars");
+        fs::create_dir_all(&calendars).unwrap();
+        fs::write(calendars.join("work.ics"), "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n").unwrap();
+        
+        for profile in BUILD_PROFILE_DIRS {
+            let exe_dir = scratch.0.join("project").join("src-tauri").join("target").join(profile);
+            // A calendars folder inside the build output is never picked
+            fs::create_dir_all(exe_dir.join("calendars")).unwrap();
+            fs::write(exe_dir.join("calendars").join("stale.ics"), "").unwrap();
+            assert_eq!(find_calendars_dir_from(&exe_dir), Some(calendars.clone()));
+        }
+    }
+    
+    #[test]
+    fn discovery_ignores_calendars_dirs_without_ics_files() {
+        let scratch = TempDir::new();
+        let exe_dir = scratch.0.join("app").join("bin");
+        fs::create_dir_all(exe_dir.join("calendars")).unwrap();
+        let found = find_calendars_dir_from(&exe_dir);
+        assert!(!found.is_some_and(|dir| dir.starts_with(&scratch.0)));
+    }
+    
+    #[test]
+    fn app_data_dir_is_named_after_the_identifier() {
+        if let Some(dir) = app_data_dir("com.example.todo") {
+            assert!(dir.ends_with("com.example.todo"));
+        }
+    }
+    
+    // Parse the inner lines (between BEGIN:VTODO and END:VTODO) of one VTODO
+    fn parse_vtodo(lines: &[&str]) -> Todo {
+        parse_vtodo_from_lines(lines, "test").unwrap()
+    }
+    
+    #[test]
+    fn quoted_printable_accents_are_decoded() {
+        let todo = parse_vtodo(&["UID:qp@test", "SUMMARY;ENCODING=QUOTED-PRINTABLE;CHARSET=UTF-8:Caf=C3=A9 cr=C3=A8me"]);
+        assert_eq!(todo.title, "Café crème");
+    }
+    
+    #[test]
+    fn quoted_printable_soft_line_breaks_are_joined() {
+        let todo = parse_vtodo(&[
+            "UID:qp@test",
+            "SUMMARY:Call",
+            "DESCRIPTION;ENCODING=QUOTED-PRINTABLE:First line, =",
+            "continued=0D=0Anext line",
+        ]);
+        assert_eq!(todo.description, "First line, continued\r\nnext line");
+    }
+    
+    #[test]
+    fn decode_quoted_printable_keeps_invalid_escapes() {
+        assert_eq!(decode_quoted_printable("a=3Db"), b"a=b");
+        assert_eq!(decode_quoted_printable("50=ZZ"), b"50=ZZ");
+        assert_eq!(decode_quoted_printable("end="), b"end");
+    }
+    
+    #[test]
+    fn charset_transcodes_quoted_printable_latin1() {
+        let todo = parse_vtodo(&["UID:cs@test", "SUMMARY;ENCODING=QUOTED-PRINTABLE;CHARSET=ISO-8859-1:Caf=E9"]);
+        assert_eq!(todo.title, "Café");
+    }
+    
+    #[test]
+    fn charset_transcodes_raw_latin1_lines() {
+        let bytes = b"BEGIN:VTODO\r\nUID:cs@test\r\nSUMMARY;CHARSET=ISO-8859-1:Na\xefve caf\xe9\r\nEND:VTODO\r\n";
+        let (content, lossy_lines) = decode_calendar_bytes(bytes);
+        assert!(lossy_lines.is_empty());
+        assert!(content.contains("SUMMARY;CHARSET=ISO-8859-1:Naïve café\r\n"));
+        assert_eq!(parse_todos_from_content(&content, "test")[0].title, "Naïve café");
+    }
+    
+    #[test]
+    fn charset_defaults_to_utf8() {
+        assert_eq!(decode_property_bytes("é".as_bytes(), &[]), "é");
+    }
+    
+    #[test]
+    fn location_round_trips_with_escaped_comma() {
+        let todo = parse_vtodo(&["UID:loc@test", "SUMMARY:Buy screws", "LOCATION:Hardware store\\, Main St"]);
+        assert_eq!(todo.location.as_deref(), Some("Hardware store, Main St"));
+        
+        let component = vtodo_component(&todo, None);
+        assert!(component.lines.iter().any(|line| line == "LOCATION:Hardware store\\, Main St"));
+        assert_eq!(component.to_todo("test").unwrap().location, todo.location);
+    }
+    
+    #[test]
+    fn url_with_query_round_trips_verbatim() {
+        let url = "https://example.com/tickets?id=42&view=full#notes";
+        let todo = parse_vtodo(&["UID:url@test", "SUMMARY:Ticket", &format!("URL:{}", url)]);
+        assert_eq!(todo.url.as_deref(), Some(url));
+        
+        let component = vtodo_component(&todo, None);
+        assert!(component.lines.iter().any(|line| *line == format!("URL:{}", url)));
+        assert_eq!(component.to_todo("test").unwrap().url.as_deref(), Some(url));
+    }
+    
+    #[test]
+    fn property_lines_split_at_the_first_colon() {
+        assert_eq!(split_property_line("URL:https://example.com:8080/a"), Some(("URL", "https://example.com:8080/a")));
+        assert_eq!(
+            split_property_line("ATTENDEE;DELEGATED-FROM=\"mailto:a@b.c\":mailto:d@e.f"),
+            Some(("ATTENDEE;DELEGATED-FROM=\"mailto:a@b.c\"", "mailto:d@e.f"))
+        );
+    }
+    
+    // Inner lines of a VTODO as a CRLF-terminated component
+    fn vtodo(properties: &[&str]) -> String {
+        let mut component = String::from("BEGIN:VTODO\r\n");
+        for property in properties {
+            component.push_str(property);
+            component.push_str("\r\n");
+        }
+        component.push_str("END:VTODO\r\n");
+        component
+    }
+    
+    // Write `components` into `dir/name` inside a minimal VCALENDAR
+    fn write_calendar(dir: &Path, name: &str, components: &str) -> PathBuf {
+        let path = dir.join(name);
+        let content = format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\n{}END:VCALENDAR\r\n", components);
+        fs::write(&path, content).unwrap();
+        path
+    }
+    
+    // Run `f` with the TodoCache state of a mock app, for commands that take State
+    fn with_cache<T>(f: impl FnOnce(tauri::State<'_, TodoCache>) -> T) -> T {
+        let app = tauri::test::mock_app();
+        app.manage(TodoCache::default());
+        f(app.state::<TodoCache>())
+    }
+    
+    fn set_mtime(path: &Path, modified: SystemTime) {
+        fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+    
+    #[test]
+    fn cache_is_reused_while_mtime_is_unchanged() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:Original"]));
+            let modified = file_modified_time(&path).unwrap();
+            let cache = TodoCache::default();
+            assert_eq!(cached_todos("work.ics", &cache, |_, _| {}).unwrap()[0].title, "Original");
+            
+            // Same mtime: the cached parse is returned and the new content never read
+            write_calendar(dir, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:Edited"]));
+            set_mtime(&path, modified);
+            assert_eq!(cached_todos("work.ics", &cache, |_, _| {}).unwrap()[0].title, "Original");
+            
+            // Touching the file invalidates the entry
+            set_mtime(&path, modified + std::time::Duration::from_secs(5));
+            assert_eq!(cached_todos("work.ics", &cache, |_, _| {}).unwrap()[0].title, "Edited");
+        });
+    }
+    
+    #[test]
+    fn cache_invalidate_drops_the_entry() {
+        let cache = TodoCache::default();
+        let path = PathBuf::from("/calendars/work.ics");
+        let modified = SystemTime::UNIX_EPOCH;
+        cache.insert(path.clone(), modified, vec![Todo::new("Task", "work")]);
+        assert_eq!(cache.get(&path, modified).map(|todos| todos.len()), Some(1));
+        assert!(cache.get(&path, modified + std::time::Duration::from_secs(1)).is_none());
+        cache.invalidate(&path);
+        assert!(cache.get(&path, modified).is_none());
+    }
+    
+    #[test]
+    fn get_todo_by_id_finds_one_todo() {
+        with_calendars_dir(|dir| {
+            let todos = vtodo(&["UID:a@test", "SUMMARY:First"]) + &vtodo(&["UID:b@test", "SUMMARY:Second"]);
+            write_calendar(dir, "work.ics", &todos);
+            
+            let found = tauri::async_runtime::block_on(get_todo_by_id("work.ics".into(), "b@test".into())).unwrap();
+            assert_eq!(found.map(|todo| todo.title).as_deref(), Some("Second"));
+        });
+    }
+    
+    #[test]
+    fn get_todo_by_id_returns_none_for_a_missing_uid() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:First"]));
+            let found = tauri::async_runtime::block_on(get_todo_by_id("work.ics".into(), "missing@test".into())).unwrap();
+            assert!(found.is_none());
+        });
+    }
+    
+    #[test]
+    fn changed_mtime_is_a_conflict() {
+        let scratch = TempDir::new();
+        let path = write_calendar(&scratch.0, "work.ics", "");
+        let modified = file_modified_time(&path).unwrap();
+        let token = modified_token(modified);
+        assert!(check_not_modified(&path, Some(&token)).is_ok());
+        
+        set_mtime(&path, modified + std::time::Duration::from_secs(5));
+        let error = check_not_modified(&path, Some(&token)).unwrap_err();
+        assert!(error.starts_with(CONFLICT_ERROR));
+        
+        // A forced save skips the token
+        assert!(check_not_modified(&path, None).is_ok());
+    }
+    
+    #[test]
+    fn missing_file_is_not_a_conflict() {
+        let scratch = TempDir::new();
+        assert!(check_not_modified(&scratch.0.join("new.ics"), Some("123")).is_ok());
+    }
+    
+    #[test]
+    fn cr_only_line_endings_yield_every_todo() {
+        let content = "BEGIN:VCALENDAR\rVERSION:2.0\rBEGIN:VTODO\rUID:a@test\rSUMMARY:Classic\r  Mac\rEND:VTODO\r\
+                       BEGIN:VTODO\rUID:b@test\rSUMMARY:Second\rEND:VTODO\rEND:VCALENDAR\r";
+        let todos = parse_todos_from_content(content, "test");
+        let titles: Vec<&str> = todos.iter().map(|todo| todo.title.as_str()).collect();
+        assert_eq!(titles, ["Classic Mac", "Second"]);
+        assert_eq!(todo_counts(content), (2, 2));
+    }
+    
+    #[test]
+    fn mixed_line_endings_are_split() {
+        let lines: Vec<&str> = split_lines("a\r\nb\nc\rd").collect();
+        assert_eq!(lines, ["a", "b", "c", "d"]);
+    }
+    
+    #[test]
+    fn leading_bom_is_stripped_on_read() {
+        let scratch = TempDir::new();
+        let path = scratch.0.join("bom.ics");
+        let content = format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}END:VCALENDAR\r\n", vtodo(&["UID:a@test", "SUMMARY:Windows"]));
+        fs::write(&path, [UTF8_BOM, content.as_bytes()].concat()).unwrap();
+        
+        let read = read_calendar_file(&path).unwrap();
+        assert!(read.starts_with("BEGIN:VCALENDAR"));
+        assert!(calendar_warnings(&read).is_empty());
+        assert_eq!(parse_todos_from_file(&path).unwrap()[0].title, "Windows");
+    }
+    
+    #[test]
+    fn parse_ics_string_strips_a_bom() {
+        let content = format!("\u{FEFF}BEGIN:VCALENDAR\r\n{}END:VCALENDAR\r\n", vtodo(&["UID:a@test", "SUMMARY:Pasted"]));
+        assert_eq!(parse_ics_string(content, "test".into()).unwrap()[0].title, "Pasted");
+    }
+    
+    #[test]
+    fn invalid_utf8_is_read_lossily_and_reported() {
+        let bytes = b"BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:a@test\r\nSUMMARY:Bad \xff byte\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+        let (content, lossy_lines) = decode_calendar_bytes(bytes);
+        assert_eq!(lossy_lines, [4]);
+        assert_eq!(parse_todos_from_content(&content, "test")[0].title, "Bad \u{FFFD} byte");
+    }
+    
+    #[test]
+    fn invalid_utf8_file_still_loads() {
+        let scratch = TempDir::new();
+        let path = scratch.0.join("broken.ics");
+        fs::write(&path, b"BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:a@test\r\nSUMMARY:\xc3\x28\r\nEND:VTODO\r\nEND:VCALENDAR\r\n").unwrap();
+        assert_eq!(parse_todos_from_file(&path).unwrap().len(), 1);
+    }
+    
+    #[test]
+    fn calendar_paths_may_not_escape_the_calendars_dir() {
+        with_calendars_dir(|dir| {
+            let outside = dir.parent().unwrap().join("outside.ics");
+            fs::write(&outside, "").unwrap();
+            
+            let error = resolve_calendar_path("../outside.ics", false).unwrap_err();
+            assert!(error.contains("outside the calendars directory"));
+            let error = resolve_calendar_path(&outside.to_string_lossy(), true).unwrap_err();
+            assert!(error.contains("outside the calendars directory"));
+            assert!(resolve_calendar_path("/etc/passwd", false).is_err());
+        });
+    }
+    
+    #[test]
+    fn calendar_paths_inside_the_dir_resolve() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", "");
+            assert_eq!(resolve_calendar_path("work.ics", false).unwrap(), dir.join("work.ics"));
+            // Writes may create a new .ics file, but nothing else
+            assert_eq!(resolve_calendar_path("new.ics", true).unwrap(), dir.join("new.ics"));
+            assert!(resolve_calendar_path("notes.txt", true).unwrap_err().contains(".ics"));
+        });
+    }
+    
+    // Leave a lock file for `calendar` as another writer would, stamped `timestamp`
+    fn write_lock_file(calendar: &Path, timestamp: i64) -> PathBuf {
+        let mut name = calendar.as_os_str().to_owned();
+        name.push(".lock");
+        let path = PathBuf::from(name);
+        fs::write(&path, format!("4242 {}\n", timestamp)).unwrap();
+        path
+    }
+    
+    #[test]
+    fn held_lock_makes_other_writers_busy() {
+        let scratch = TempDir::new();
+        let path = write_calendar(&scratch.0, "work.ics", "");
+        let lock = CalendarLock::acquire(&path).unwrap();
+        let Err(error) = CalendarLock::acquire(&path) else {
+            panic!("second writer acquired a held lock");
+        };
+        assert!(error.starts_with(BUSY_ERROR));
+        
+        // Dropping the guard removes the lock file
+        drop(lock);
+        assert!(CalendarLock::acquire(&path).is_ok());
+    }
+    
+    #[test]
+    fn fresh_lock_from_another_process_blocks_writes() {
+        let scratch = TempDir::new();
+        let path = write_calendar(&scratch.0, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:Task"]));
+        let lock = write_lock_file(&path, Utc::now().timestamp());
+        let before = fs::read_to_string(&path).unwrap();
+        
+        let error = modify_calendar_file(&path, &TodoCache::default(), |_| Ok(())).unwrap_err();
+        assert!(error.starts_with(BUSY_ERROR));
+        assert!(lock.exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+    }
+    
+    #[test]
+    fn stale_lock_is_reclaimed() {
+        let scratch = TempDir::new();
+        let path = write_calendar(&scratch.0, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:Task"]));
+        let lock = write_lock_file(&path, Utc::now().timestamp() - STALE_LOCK_SECS - 5);
+        
+        modify_calendar_file(&path, &TodoCache::default(), |_| Ok(())).unwrap();
+        assert!(!lock.exists());
+    }
+    
+    #[test]
+    fn saving_keeps_events_and_journals_verbatim() {
+        with_calendars_dir(|dir| {
+            let others = "BEGIN:VEVENT\r\nUID:event@test\r\nSUMMARY:Standup\r\nDTSTART:20250106T090000Z\r\nX-CUSTOM;FOO=bar:kept\r\nEND:VEVENT\r\n\
+                          BEGIN:VJOURNAL\r\nUID:journal@test\r\nSUMMARY:Notes\r\nEND:VJOURNAL\r\n";
+            let path = write_calendar(dir, "mixed.ics", &format!("{}{}", others, vtodo(&["UID:a@test", "SUMMARY:Original"])));
+            
+            let mut todos = parse_todos_from_file(&path).unwrap();
+            todos[0].title = "Edited".to_string();
+            let content = render_todos_into(&path, &todos).unwrap();
+            assert!(content.contains(others));
+            assert!(content.contains("SUMMARY:Edited\r\n"));
+            assert!(!content.contains("Original"));
+        });
+    }
+    
+    #[test]
+    fn calendar_name_and_color_survive_a_save() {
+        with_calendars_dir(|dir| {
+            let path = dir.join("apple.ics");
+            let content = format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Apple Inc.//EN\r\nX-WR-CALNAME:Groceries\r\nX-APPLE-CALENDAR-COLOR:#FF2968\r\n{}END:VCALENDAR\r\n",
+                vtodo(&["UID:a@test", "SUMMARY:Milk"])
+            );
+            fs::write(&path, content).unwrap();
+            
+            let todos = parse_todos_from_file(&path).unwrap();
+            let saved = render_todos_into(&path, &todos).unwrap();
+            assert!(saved.contains("PRODID:-//Apple Inc.//EN\r\n"));
+            assert!(saved.contains("X-WR-CALNAME:Groceries\r\n"));
+            assert!(saved.contains("X-APPLE-CALENDAR-COLOR:#FF2968\r\n"));
+            assert!(!saved.contains(DEFAULT_PRODID));
+        });
+    }
+    
+    #[test]
+    fn envelope_without_prodid_gets_the_default() {
+        let properties = envelope_properties(&["X-WR-CALNAME:Groceries".to_string()]);
+        assert_eq!(properties, ["VERSION:2.0", DEFAULT_PRODID, "X-WR-CALNAME:Groceries"]);
+        assert_eq!(envelope_properties(&[])[1], DEFAULT_PRODID);
+    }
+    
+    // A todo's VTODO as it would be written (UTC times)
+    fn render_vtodo(todo: &Todo) -> String {
+        let mut out = String::new();
+        vtodo_component(todo, None).render_into(&mut out);
+        out
+    }
+    
+    // A raw VTODO component with the given properties
+    fn vtodo_lines(properties: &[&str]) -> CalendarComponent {
+        CalendarComponent {
+            name: "VTODO".to_string(),
+            lines: vtodo(properties).lines().map(str::to_string).collect(),
+        }
+    }
+    
+    #[test]
+    fn completing_a_todo_sets_completed() {
+        let todo = Todo { completed: true, ..parse_vtodo(&["UID:a@test", "SUMMARY:Task"]) };
+        let rendered = render_vtodo(&todo);
+        assert!(rendered.contains("STATUS:COMPLETED\r\n"));
+        let stamp = rendered.lines().find_map(|line| line.strip_prefix("COMPLETED:")).unwrap();
+        assert!(parse_ical_datetime(stamp).is_some() && stamp.ends_with('Z'));
+        
+        let mut component = vtodo_lines(&["UID:a@test", "STATUS:NEEDS-ACTION"]);
+        set_component_completed(&mut component, true, "20250102T030405Z");
+        assert_eq!(component.property("COMPLETED").as_deref(), Some("20250102T030405Z"));
+    }
+    
+    #[test]
+    fn existing_completed_value_is_preserved() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Task", "STATUS:COMPLETED", "COMPLETED:20240102T030405Z"]);
+        assert_eq!(todo.completed_at.as_deref(), Some("2024-01-02T03:04:05"));
+        assert!(render_vtodo(&todo).contains("COMPLETED:20240102T030405Z\r\n"));
+        
+        let mut component = vtodo_lines(&["UID:a@test", "STATUS:COMPLETED", "COMPLETED:20240102T030405Z"]);
+        set_component_completed(&mut component, true, "20250102T030405Z");
+        assert_eq!(component.property("COMPLETED").as_deref(), Some("20240102T030405Z"));
+    }
+    
+    #[test]
+    fn reopening_a_todo_clears_completed() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Task", "STATUS:COMPLETED", "COMPLETED:20240102T030405Z"]);
+        let rendered = render_vtodo(&Todo { completed: false, ..todo });
+        assert!(rendered.contains("STATUS:NEEDS-ACTION\r\n"));
+        assert!(!rendered.contains("COMPLETED:"));
+        
+        let mut component = vtodo_lines(&["UID:a@test", "STATUS:COMPLETED", "COMPLETED:20240102T030405Z"]);
+        set_component_completed(&mut component, false, "20250102T030405Z");
+        assert_eq!(component.property("COMPLETED"), None);
+    }
+    
+    #[test]
+    fn duplicate_todo_appends_an_open_copy() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &vtodo(&[
+                "UID:a@test",
+                "SUMMARY:Report",
+                "DESCRIPTION:Quarterly numbers",
+                "PRIORITY:1",
+                "CATEGORIES:Work",
+                "DUE;VALUE=DATE:20250301",
+                "STATUS:COMPLETED",
+                "COMPLETED:20250201T100000Z",
+                "RRULE:FREQ=WEEKLY",
+                "X-2DO-SNOOZE-UNTIL:20990101T000000Z",
+                "X-TRASHED-AT:20250201T110000Z",
+            ]));
+            
+            let copy = with_cache(|cache| {
+                tauri::async_runtime::block_on(duplicate_todo("work.ics".to_string(), "a@test".to_string(), cache))
+            })
+            .unwrap();
+            assert_ne!(copy.id, "a@test");
+            assert_eq!(copy.title, "Report (copy)");
+            assert!(!copy.completed);
+            assert_eq!(copy.completed_at, None);
+            assert_eq!(copy.description, "Quarterly numbers");
+            assert_eq!(copy.priority, "high");
+            assert_eq!(copy.categories, ["Work"]);
+            assert_eq!(copy.due_date.as_deref(), Some("2025-03-01"));
+        // A fresh one-off todo: not recurring, snoozed, trashed or an occurrence
+        assert_eq!(copy.rrule, None);
+        assert_eq!(copy.snooze_until, None);
+        assert_eq!(copy.trashed_at, None);
+        assert_eq!(copy.recurrence_id, None);
+            
+            let todos = parse_todos_from_file(&path).unwrap();
+            assert_eq!(todos.len(), 2);
+            let source = todos.iter().find(|todo| todo.id == "a@test").unwrap();
+            assert_eq!(source.title, "Report");
+            assert!(source.completed);
+            assert!(todos.iter().any(|todo| todo.id == copy.id && !todo.completed));
+        });
+    }
+    
+    // A calendar with three open todos, a@test, b@test and c@test
+    fn write_three_todos(dir: &Path) -> PathBuf {
+        let components: String = ["a", "b", "c"]
+            .iter()
+            .map(|id| vtodo(&[&format!("UID:{}@test", id), &format!("SUMMARY:Task {}", id)]))
+            .collect();
+        write_calendar(dir, "work.ics", &components)
+    }
+    
+    fn uids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+    
+    #[test]
+    fn bulk_complete_marks_two_of_three() {
+        with_calendars_dir(|dir| {
+            let path = write_three_todos(dir);
+            let result = with_cache(|cache| {
+                tauri::async_runtime::block_on(bulk_complete(
+                    "work.ics".to_string(),
+                    uids(&["a@test", "c@test", "missing@test"]),
+                    true,
+                    cache,
+                ))
+            })
+            .unwrap();
+            assert_eq!((result.changed, result.skipped), (2, 1));
+            
+            let todos = parse_todos_from_file(&path).unwrap();
+            let completed: Vec<&str> = todos.iter().filter(|todo| todo.completed).map(|todo| todo.id.as_str()).collect();
+            assert_eq!(completed, ["a@test", "c@test"]);
+            assert!(todos.iter().all(|todo| todo.completed == todo.completed_at.is_some()));
+            
+            // And back again
+            with_cache(|cache| {
+                tauri::async_runtime::block_on(bulk_complete("work.ics".to_string(), uids(&["a@test"]), false, cache))
+            })
+            .unwrap();
+            let todos = parse_todos_from_file(&path).unwrap();
+            assert!(!todos[0].completed && todos[0].completed_at.is_none());
+        });
+    }
+    
+    #[test]
+    fn bulk_delete_removes_a_subset() {
+        with_calendars_dir(|dir| {
+            let path = write_three_todos(dir);
+            let result = with_cache(|cache| {
+                tauri::async_runtime::block_on(bulk_delete("work.ics".to_string(), uids(&["b@test", "missing@test"]), cache))
+            })
+            .unwrap();
+            assert_eq!((result.changed, result.skipped), (1, 1));
+            
+            let ids: Vec<String> = parse_todos_from_file(&path).unwrap().into_iter().map(|todo| todo.id).collect();
+            assert_eq!(ids, ["a@test", "c@test"]);
+        });
+    }
+    
+    #[test]
+    fn multiple_vcalendar_blocks_round_trip() {
+        with_calendars_dir(|dir| {
+            let path = dir.join("combined.ics");
+            let content = format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//first//EN\r\n{}END:VCALENDAR\r\n\
+                 BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//second//EN\r\nX-WR-CALNAME:Second\r\n{}END:VCALENDAR\r\n",
+                vtodo(&["UID:a@test", "SUMMARY:First"]),
+                vtodo(&["UID:b@test", "SUMMARY:Second"])
+            );
+            fs::write(&path, content).unwrap();
+            
+            let todos = parse_todos_from_file(&path).unwrap();
+            let blocks: Vec<(&str, usize)> = todos.iter().map(|todo| (todo.id.as_str(), todo.calendar_block)).collect();
+            assert_eq!(blocks, [("a@test", 0), ("b@test", 1)]);
+            
+            let saved = render_todos_into(&path, &todos).unwrap();
+            let calendars: Vec<&str> = saved.split("END:VCALENDAR").collect();
+            assert_eq!(calendars.len(), 3);
+            assert!(calendars[0].contains("PRODID:-//first//EN") && calendars[0].contains("UID:a@test"));
+            assert!(calendars[1].contains("X-WR-CALNAME:Second") && calendars[1].contains("UID:b@test"));
+            
+            let reparsed = parse_todos_from_content(&saved, "combined");
+            assert_eq!(reparsed.iter().map(|todo| todo.calendar_block).collect::<Vec<_>>(), [0, 1]);
+        });
+    }
+    
+    #[test]
+    fn gzipped_calendar_loads_like_its_plaintext() {
+        with_calendars_dir(|dir| {
+            let plain = write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:First", "DUE;VALUE=DATE:20250301"]),
+                vtodo(&["UID:b@test", "SUMMARY:Second"]),
+            ].concat());
+            let content = fs::read_to_string(&plain).unwrap();
+            let gzipped = dir.join("archive.ics.gz");
+            write_calendar_file(&gzipped, &content).unwrap();
+            
+            // Saved compressed, read back transparently
+            assert!(fs::read(&gzipped).unwrap().starts_with(&[0x1f, 0x8b]));
+            assert_eq!(read_calendar_file(&gzipped).unwrap(), content);
+            
+            let summary = |todos: Vec<Todo>| -> Vec<(String, String, Option<String>)> {
+                todos.into_iter().map(|todo| (todo.id, todo.title, todo.due_date)).collect()
+            };
+            assert_eq!(summary(parse_todos_from_file(&gzipped).unwrap()), summary(parse_todos_from_file(&plain).unwrap()));
+            
+            let calendars = tauri::async_runtime::block_on(list_calendars()).unwrap();
+            let archive = calendars.iter().find(|calendar| calendar.name == "archive").unwrap();
+            assert_eq!(archive.todo_count, 2);
+        });
+    }
+    
+    #[test]
+    fn parse_ics_string_reads_pasted_content() {
+        let content = format!(
+            "BEGIN:VCALENDAR\nVERSION:2.0\n{}{}END:VCALENDAR\n",
+            vtodo(&["UID:a@test", "SUMMARY:Buy milk", "DUE;VALUE=DATE:20250301"]),
+            vtodo(&["UID:b@test", "SUMMARY:Call Bob", "STATUS:COMPLETED"])
+        );
+        let todos = parse_ics_string(content, "pasted".to_string()).unwrap();
+        assert_eq!(todos.len(), 2);
+        assert_eq!((todos[0].title.as_str(), todos[0].due_date.as_deref()), ("Buy milk", Some("2025-03-01")));
+        assert!(!todos[0].completed && todos[1].completed);
+        assert!(todos.iter().all(|todo| todo.calendar_name == "pasted"));
+    }
+    
+    #[test]
+    fn parse_ics_string_without_todos_is_an_error() {
+        let error = parse_ics_string("BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n".to_string(), "pasted".to_string()).unwrap_err();
+        assert!(error.contains("No VTODO"));
+    }
+    
+    // Expand the recurrences in `work.ics` within [start, end]
+    fn expand(start: &str, end: &str) -> Vec<Todo> {
+        tauri::async_runtime::block_on(expand_recurrences("work.ics".to_string(), start.to_string(), end.to_string()))
+            .unwrap()
+    }
+    
+    fn occurrence_dates(todos: &[Todo]) -> Vec<&str> {
+        todos.iter().filter_map(|todo| todo.recurrence_id.as_deref()).collect()
+    }
+    
+    #[test]
+    fn weekly_rule_is_bounded_by_count() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:r@test", "SUMMARY:Review", "DUE;VALUE=DATE:20250106", "RRULE:FREQ=WEEKLY;COUNT=3"]),
+                vtodo(&["UID:once@test", "SUMMARY:One-off", "DUE;VALUE=DATE:20250108"]),
+            ].concat());
+            
+            let todos = expand("2025-01-01", "2025-12-31");
+            assert_eq!(occurrence_dates(&todos), ["2025-01-06", "2025-01-13", "2025-01-20"]);
+            let instances: Vec<&Todo> = todos.iter().filter(|todo| todo.recurrence_id.is_some()).collect();
+            assert!(instances.iter().all(|todo| todo.id == "r@test" && todo.due_date == todo.recurrence_id));
+            
+            // Non-recurring todos come back unchanged
+            let once = todos.iter().find(|todo| todo.id == "once@test").unwrap();
+            assert_eq!((once.due_date.as_deref(), once.recurrence_id.as_deref()), (Some("2025-01-08"), None));
+            
+            // The range clips occurrences outside it
+            assert_eq!(occurrence_dates(&expand("2025-01-10", "2025-01-15")), ["2025-01-13"]);
+        });
+    }
+    
+    #[test]
+    fn weekly_rule_is_bounded_by_until() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &vtodo(&[
+                "UID:r@test",
+                "SUMMARY:Review",
+                "DUE;VALUE=DATE:20250106",
+                "RRULE:FREQ=WEEKLY;INTERVAL=2;UNTIL=20250203T000000Z",
+            ]));
+            
+            let todos = expand("2025-01-01", "2025-12-31");
+            assert_eq!(occurrence_dates(&todos), ["2025-01-06", "2025-01-20", "2025-02-03"]);
+        });
+    }
+    
+    #[test]
+    fn rrule_round_trips_unchanged() {
+        let todo = parse_vtodo(&["UID:r@test", "SUMMARY:Gym", "RRULE:FREQ=WEEKLY;BYDAY=MO,WE"]);
+        assert_eq!(todo.rrule.as_deref(), Some("FREQ=WEEKLY;BYDAY=MO,WE"));
+        assert!(render_vtodo(&todo).contains("\r\nRRULE:FREQ=WEEKLY;BYDAY=MO,WE\r\n"));
+    }
+    
+    // Complete `uid` in `work.ics` through complete_recurring
+    fn complete_in_work(uid: &str) -> Result<Option<Todo>, String> {
+        with_cache(|cache| {
+            tauri::async_runtime::block_on(complete_recurring("work.ics".to_string(), uid.to_string(), cache))
+        })
+    }
+    
+    #[test]
+    fn completing_a_daily_todo_creates_the_next_one() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &vtodo(&[
+                "UID:r@test",
+                "SUMMARY:Water plants",
+                "DUE;VALUE=DATE:20250106",
+                "RRULE:FREQ=DAILY;COUNT=3",
+                "X-CUSTOM:kept",
+            ]));
+            
+            let next = complete_in_work("r@test").unwrap().unwrap();
+            assert_ne!(next.id, "r@test");
+            assert_eq!(next.due_date.as_deref(), Some("2025-01-07"));
+            assert_eq!(next.rrule.as_deref(), Some("FREQ=DAILY;COUNT=2"));
+            assert!(!next.completed);
+            
+            let todos = parse_todos_from_file(&path).unwrap();
+            assert_eq!(todos.len(), 2);
+            assert!(todos[0].completed && todos[0].rrule.is_none());
+            assert_eq!(todos[1].id, next.id);
+            assert_eq!(fs::read_to_string(&path).unwrap().matches("X-CUSTOM:kept").count(), 2);
+        });
+    }
+    
+    #[test]
+    fn completing_the_last_occurrence_ends_the_series() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:r@test", "SUMMARY:Last", "DUE;VALUE=DATE:20250106", "RRULE:FREQ=DAILY;COUNT=1"]),
+                vtodo(&["UID:once@test", "SUMMARY:One-off"]),
+            ].concat());
+            
+            assert!(complete_in_work("r@test").unwrap().is_none());
+            assert!(complete_in_work("once@test").unwrap().is_none());
+            let todos = parse_todos_from_file(&path).unwrap();
+            assert_eq!(todos.len(), 2);
+            assert!(todos.iter().all(|todo| todo.completed));
+        });
+    }
+    
+    #[test]
+    fn next_occurrence_respects_until() {
+        let todo = parse_vtodo(&["UID:r@test", "DUE;VALUE=DATE:20250106", "RRULE:FREQ=WEEKLY;UNTIL=20250110"]);
+        assert_eq!(next_occurrence(&todo).unwrap(), None);
+        let todo = parse_vtodo(&["UID:r@test", "DUE;VALUE=DATE:20250106", "RRULE:FREQ=MONTHLY;INTERVAL=2"]);
+        assert_eq!(next_occurrence(&todo).unwrap(), Some((NaiveDate::from_ymd_opt(2025, 3, 6).unwrap(), "FREQ=MONTHLY;INTERVAL=2".to_string())));
+    }
+    
+    #[test]
+    fn log_level_filter_comes_from_the_environment() {
+        use log::Log;
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::remove_var(LOG_LEVEL_ENV);
+        assert_eq!(log_level_from_env(), log::LevelFilter::Info);
+        
+        std::env::set_var(LOG_LEVEL_ENV, " warn ");
+        let logger = StderrLogger { level: log_level_from_env() };
+        let record_at = |level| log::Metadata::builder().level(level).build();
+        // Trace (e.g. per-field date parsing) is filtered out, warnings are kept
+        assert!(!logger.enabled(&record_at(log::Level::Trace)));
+        assert!(!logger.enabled(&record_at(log::Level::Info)));
+        assert!(logger.enabled(&record_at(log::Level::Warn)));
+        assert!(logger.enabled(&record_at(log::Level::Error)));
+        
+        std::env::set_var(LOG_LEVEL_ENV, "chatty");
+        assert_eq!(log_level_from_env(), log::LevelFilter::Info);
+        std::env::remove_var(LOG_LEVEL_ENV);
+    }
+    
+    #[test]
+    fn emoji_and_combining_marks_survive_escaping() {
+        for title in ["Ship 🚀 v2.0 — review", "Cafe\u{301}, naïve; 👩‍💻 done"] {
+            assert_eq!(unescape_ical_text(&escape_ical_text(title)), title);
+        }
+    }
+    
+    #[test]
+    fn control_characters_are_removed_when_escaping() {
+        assert_eq!(escape_ical_text("Bell\u{7} here\tand\r\nthere"), "Bell here\tand\\nthere");
+    }
+    
+    #[test]
+    fn escaped_backslashes_unescape_one_for_one() {
+        assert_eq!(unescape_ical_text("C:\\\\temp\\\\file"), "C:\\temp\\file");
+        assert_eq!(unescape_ical_text("\\\\\\\\"), "\\\\");
+        assert_eq!(unescape_ical_text("a\\;b\\,c\\nd\\Ne"), "a;b,c\nd\ne");
+    }
+    
+    #[test]
+    fn windows_path_round_trips() {
+        let path = "C:\\temp\\file";
+        assert_eq!(escape_ical_text(path), "C:\\\\temp\\\\file");
+        let todo = parse_vtodo(&["UID:p@test", "SUMMARY:Clean up", "DESCRIPTION:See C:\\\\temp\\\\file"]);
+        assert_eq!(todo.description, "See C:\\temp\\file");
+    }
+    
+    #[test]
+    fn archive_completed_moves_done_todos_verbatim() {
+        with_calendars_dir(|dir| {
+            let done = vtodo(&["UID:done@test", "SUMMARY:Filed taxes", "STATUS:COMPLETED", "X-CUSTOM;FOO=bar:kept"]);
+            let path = write_calendar(dir, "work.ics", &[vtodo(&["UID:open@test", "SUMMARY:Open"]), done.clone()].concat());
+            
+            let archived = with_cache(|cache| tauri::async_runtime::block_on(archive_completed("work.ics".to_string(), cache)));
+            assert_eq!(archived, Ok(1));
+            
+            let remaining = parse_todos_from_file(&path).unwrap();
+            assert_eq!(remaining.iter().map(|todo| todo.id.as_str()).collect::<Vec<_>>(), ["open@test"]);
+            let archive = fs::read_to_string(dir.join(".archive").join("work.ics")).unwrap();
+            assert!(archive.contains(&done));
+            
+            // Nothing left to archive
+            let archived = with_cache(|cache| tauri::async_runtime::block_on(archive_completed("work.ics".to_string(), cache)));
+            assert_eq!(archived, Ok(0));
+        });
+    }
+    
+    #[test]
+    fn archive_is_not_listed_as_a_calendar() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &vtodo(&["UID:done@test", "SUMMARY:Done", "STATUS:COMPLETED"]));
+            let archived = with_cache(|cache| tauri::async_runtime::block_on(archive_completed("work.ics".to_string(), cache)));
+            assert_eq!(archived, Ok(1));
+            
+            let calendars = tauri::async_runtime::block_on(list_calendars()).unwrap();
+            assert_eq!(calendars.iter().map(|calendar| calendar.name.as_str()).collect::<Vec<_>>(), ["work"]);
+        });
+    }
+    
+    #[test]
+    fn soft_deleted_todo_can_be_listed_and_restored() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:Keep"]),
+                vtodo(&["UID:b@test", "SUMMARY:Oops", "X-CUSTOM:kept"]),
+            ].concat());
+            let ids = |path: &Path| -> Vec<String> {
+                parse_todos_from_file(path).unwrap().into_iter().map(|todo| todo.id).collect()
+            };
+            
+            with_cache(|cache| {
+                tauri::async_runtime::block_on(delete_todo("work.ics".to_string(), "b@test".to_string(), true, cache))
+            })
+            .unwrap();
+            assert_eq!(ids(&path), ["a@test"]);
+            
+            let trash = tauri::async_runtime::block_on(list_trash("work.ics".to_string())).unwrap();
+            assert_eq!(trash.len(), 1);
+            assert_eq!(trash[0].id, "b@test");
+            assert!(trash[0].trashed_at.is_some());
+            // The trash lives in its own directory, out of the calendar listing
+            assert!(dir.join(".trash").join("work.ics").is_file());
+            let calendars = tauri::async_runtime::block_on(list_calendars()).unwrap();
+            assert_eq!(calendars.len(), 1);
+            
+            with_cache(|cache| {
+                tauri::async_runtime::block_on(restore_todo("work.ics".to_string(), "b@test".to_string(), cache))
+            })
+            .unwrap();
+            assert_eq!(ids(&path), ["a@test", "b@test"]);
+            assert!(tauri::async_runtime::block_on(list_trash("work.ics".to_string())).unwrap().is_empty());
+            let content = fs::read_to_string(&path).unwrap();
+            assert!(content.contains("X-CUSTOM:kept") && !content.contains("X-TRASHED-AT"));
+        });
+    }
+    
+    #[test]
+    fn hard_delete_skips_the_trash() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:Gone"]));
+            with_cache(|cache| {
+                tauri::async_runtime::block_on(delete_todo("work.ics".to_string(), "a@test".to_string(), false, cache))
+            })
+            .unwrap();
+            assert!(tauri::async_runtime::block_on(list_trash("work.ics".to_string())).unwrap().is_empty());
+            
+            let missing = with_cache(|cache| {
+                tauri::async_runtime::block_on(delete_todo("work.ics".to_string(), "a@test".to_string(), true, cache))
+            });
+            assert!(missing.unwrap_err().contains("Todo not found"));
+        });
+    }
+    
+    #[test]
+    fn trash_stamp_survives_a_rebuild() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Oops", "X-TRASHED-AT:20250301T120000Z"]);
+        assert_eq!(todo.trashed_at.as_deref(), Some("2025-03-01T12:00:00"));
+        assert!(render_vtodo(&todo).contains("X-TRASHED-AT:20250301T120000Z\r\n"));
+    }
+    
+    // Run repair_calendar on `name` in the calendars directory
+    fn repair(name: &str) -> RepairReport {
+        with_cache(|cache| tauri::async_runtime::block_on(repair_calendar(name.to_string(), cache))).unwrap()
+    }
+    
+    #[test]
+    fn repair_closes_an_unterminated_vtodo() {
+        with_calendars_dir(|dir| {
+            let path = dir.join("broken.ics");
+            let original = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\nUID:a@test\r\nSUMMARY:Half written\r\nEND:VCALENDAR\r\n";
+            fs::write(&path, original).unwrap();
+            
+            let report = repair("broken.ics");
+            assert!(report.changed);
+            assert_eq!(report.unterminated_blocks, 1);
+            assert_eq!(fs::read_to_string(report.backup_path.unwrap()).unwrap(), original);
+            
+            let repaired = fs::read_to_string(&path).unwrap();
+            assert!(repaired.contains("SUMMARY:Half written\r\nEND:VTODO\r\n"));
+            assert_eq!(parse_todos_from_file(&path).unwrap()[0].title, "Half written");
+        });
+    }
+    
+    #[test]
+    fn repair_wraps_orphan_vtodos_and_renames_duplicates() {
+        with_calendars_dir(|dir| {
+            let path = dir.join("orphans.ics");
+            fs::write(&path, [vtodo(&["UID:a@test", "SUMMARY:First"]), vtodo(&["UID:a@test", "SUMMARY:Second"])].concat()).unwrap();
+            
+            let report = repair("orphans.ics");
+            assert!(report.wrapped_orphans > 0);
+            assert_eq!(report.duplicate_uids, 1);
+            
+            let repaired = fs::read_to_string(&path).unwrap();
+            assert!(repaired.starts_with("BEGIN:VCALENDAR\r\n"));
+            let todos = parse_todos_from_file(&path).unwrap();
+            assert_eq!(todos.len(), 2);
+            assert_eq!(todos[0].id, "a@test");
+            assert_ne!(todos[1].id, "a@test");
+        });
+    }
+    
+    #[test]
+    fn repair_leaves_a_healthy_calendar_alone() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:Fine"]));
+            let before = fs::read_to_string(&path).unwrap();
+            let report = repair("work.ics");
+            assert!(!report.changed && report.backup_path.is_none());
+            assert_eq!(fs::read_to_string(&path).unwrap(), before);
+        });
+    }
+    
+    #[test]
+    fn backup_all_zips_the_calendars() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:Work"]));
+            write_calendar(dir, "home.ics", "");
+            fs::create_dir_all(dir.join("shared")).unwrap();
+            write_calendar(&dir.join("shared"), "team.ics", "");
+            fs::write(dir.join("work.ics.lock"), "1 0\n").unwrap();
+            fs::create_dir_all(dir.join(".backups")).unwrap();
+            fs::write(dir.join(".backups").join("old.ics"), "").unwrap();
+            
+            let zip_path = tauri::async_runtime::block_on(backup_all()).unwrap();
+            assert!(Path::new(&zip_path).starts_with(dir.join(".backups")));
+            
+            let mut archive = zip::ZipArchive::new(fs::File::open(&zip_path).unwrap()).unwrap();
+            let mut names: Vec<&str> = archive.file_names().collect();
+            names.sort_unstable();
+            assert_eq!(names, ["home.ics", "shared/team.ics", "work.ics"]);
+            
+            let mut content = String::new();
+            archive.by_name("work.ics").unwrap().read_to_string(&mut content).unwrap();
+            assert_eq!(content, fs::read_to_string(dir.join("work.ics")).unwrap());
+        });
+    }
+    
+    #[test]
+    fn load_todos_page_returns_the_requested_window() {
+        with_calendars_dir(|dir| {
+            let components: String = (0..50)
+                .map(|n| vtodo(&[&format!("UID:{}@test", n), &format!("SUMMARY:Task {}", n)]))
+                .collect();
+            write_calendar(dir, "big.ics", &components);
+            let page = |offset, limit| tauri::async_runtime::block_on(load_todos_page("big.ics".to_string(), offset, limit)).unwrap();
+            
+            let second = page(20, 20);
+            assert_eq!(second.total, 50);
+            let ids: Vec<String> = second.todos.iter().map(|todo| todo.id.clone()).collect();
+            assert_eq!(ids, (20..40).map(|n| format!("{}@test", n)).collect::<Vec<_>>());
+            
+            // The last page is short, and past the end is empty
+            assert_eq!(page(40, 20).todos.len(), 10);
+            let past_end = page(60, 20);
+            assert!(past_end.todos.is_empty());
+            assert_eq!(past_end.total, 50);
+        });
+    }
+    
+    #[test]
+    fn filter_combines_priority_and_due_before() {
+        let todos = [
+            parse_vtodo(&["UID:a@test", "SUMMARY:Urgent soon", "PRIORITY:1", "DUE;VALUE=DATE:20250301"]),
+            parse_vtodo(&["UID:b@test", "SUMMARY:Urgent later", "PRIORITY:1", "DUE;VALUE=DATE:20250401"]),
+            parse_vtodo(&["UID:c@test", "SUMMARY:Relaxed soon", "PRIORITY:9", "DUE;VALUE=DATE:20250301"]),
+            parse_vtodo(&["UID:d@test", "SUMMARY:Urgent undated", "PRIORITY:1"]),
+            parse_vtodo(&["UID:e@test", "SUMMARY:Urgent timed", "PRIORITY:1", "DUE:20250314T230000Z"]),
+        ];
+        let filter = TodoFilter {
+            priority: Some("HIGH".to_string()),
+            due_before: Some("2025-03-15T08:00:00".to_string()),
+            ..TodoFilter::default()
+        };
+        let matching: Vec<&str> = todos
+            .iter()
+            .filter(|todo| filter.matches(todo).unwrap())
+            .map(|todo| todo.id.as_str())
+            .collect();
+        assert_eq!(matching, ["a@test", "e@test"]);
+    }
+    
+    #[test]
+    fn filter_todos_applies_every_predicate() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:Write report", "CATEGORIES:Work", "STATUS:COMPLETED"]),
+                vtodo(&["UID:b@test", "SUMMARY:Review report", "CATEGORIES:Work"]),
+                vtodo(&["UID:c@test", "SUMMARY:Report taxes", "CATEGORIES:Home", "DUE;VALUE=DATE:20250301"]),
+            ].concat());
+            let filter = TodoFilter {
+                completed: Some(false),
+                category: Some("work".to_string()),
+                text: Some("REPORT".to_string()),
+                ..TodoFilter::default()
+            };
+            let matching = with_cache(|cache| tauri::async_runtime::block_on(filter_todos("work.ics".to_string(), filter, cache))).unwrap();
+            assert_eq!(matching.iter().map(|todo| todo.id.as_str()).collect::<Vec<_>>(), ["b@test"]);
+            
+            let invalid = TodoFilter { due_after: Some("soon".to_string()), ..TodoFilter::default() };
+            let error = with_cache(|cache| tauri::async_runtime::block_on(filter_todos("work.ics".to_string(), invalid, cache))).unwrap_err();
+            assert!(error.contains("Invalid filter date"));
+        });
+    }
+    
+    #[test]
+    fn queries_leave_out_trashed_todos() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:Report"]),
+                vtodo(&["UID:b@test", "SUMMARY:Old report", "X-TRASHED-AT:20250301T120000Z"]),
+            ].concat());
+            
+            let visible = visible_todos("work.ics", &TodoCache::default()).unwrap();
+            assert_eq!(visible.iter().map(|todo| todo.id.as_str()).collect::<Vec<_>>(), ["a@test"]);
+            let matching = with_cache(|cache| {
+                tauri::async_runtime::block_on(filter_todos("work.ics".to_string(), TodoFilter::default(), cache))
+            })
+            .unwrap();
+            assert_eq!(matching.len(), 1);
+        });
+    }
+    
+    #[test]
+    fn due_todos_are_collected_across_calendars() {
+        let scratch = TempDir::new();
+        write_calendar(&scratch.0, "work.ics", &[
+            vtodo(&["UID:a@test", "SUMMARY:Today", "DUE;VALUE=DATE:20250310"]),
+            vtodo(&["UID:b@test", "SUMMARY:Late", "DUE;VALUE=DATE:20250301"]),
+            vtodo(&["UID:c@test", "SUMMARY:Done", "DUE;VALUE=DATE:20250301", "STATUS:COMPLETED"]),
+            vtodo(&["UID:d@test", "SUMMARY:Later", "DUE;VALUE=DATE:20250320"]),
+        ].concat());
+        write_calendar(&scratch.0, "home.ics", &vtodo(&["UID:e@test", "SUMMARY:Timed", "DUE:20250310T180000Z"]));
+        write_calendar(&scratch.0, "someday.ics", &vtodo(&["UID:f@test", "SUMMARY:Undated"]));
+        
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let due = collect_due_todos(&scratch.0, today).unwrap();
+        let counts: Vec<(&str, usize, usize)> = due
+            .iter()
+            .map(|calendar| (calendar.name.as_str(), calendar.due_today, calendar.overdue))
+            .collect();
+        assert_eq!(counts, [("home", 1, 0), ("work", 1, 1)]);
+    }
+    
+    #[test]
+    fn multiple_alarms_round_trip_in_order() {
+        let todo = parse_vtodo(&[
+            "UID:a@test",
+            "SUMMARY:Dentist",
+            "BEGIN:VALARM",
+            "ACTION:DISPLAY",
+            "TRIGGER:-P1D",
+            "DESCRIPTION:Tomorrow",
+            "END:VALARM",
+            "BEGIN:VALARM",
+            "ACTION:AUDIO",
+            "TRIGGER:-PT1H",
+            "END:VALARM",
+        ]);
+        let alarms = |todo: &Todo| -> Vec<(String, String, Option<String>)> {
+            todo.alarms.iter().map(|a| (a.action.clone(), a.trigger.clone(), a.description.clone())).collect()
+        };
+        let expected = vec![
+            ("DISPLAY".to_string(), "-P1D".to_string(), Some("Tomorrow".to_string())),
+            ("AUDIO".to_string(), "-PT1H".to_string(), None),
+        ];
+        assert_eq!(alarms(&todo), expected);
+        assert_eq!(alarms(&vtodo_component(&todo, None).to_todo("test").unwrap()), expected);
+    }
+    
+    #[test]
+    fn alarm_triggers_must_be_durations_or_datetimes() {
+        let alarm = |trigger: &str| Alarm { action: "DISPLAY".to_string(), trigger: trigger.to_string(), description: None };
+        assert!(alarm("-PT15M").validate().is_ok());
+        assert!(alarm("20250301T090000Z").validate().is_ok());
+        assert!(alarm("an hour before").validate().is_err());
+    }
+    
+    #[test]
+    fn due_phrases_resolve_against_the_reference_date() {
+        // 2024-03-01 is a Friday
+        let due = |input: &str| parse_due(input.to_string(), Some("2024-03-01".to_string()));
+        assert_eq!(due("today").unwrap(), "2024-03-01");
+        assert_eq!(due(" Tomorrow ").unwrap(), "2024-03-02");
+        assert_eq!(due("in 3 days").unwrap(), "2024-03-04");
+        assert_eq!(due("in 2 weeks").unwrap(), "2024-03-15");
+        assert_eq!(due("in 1 month").unwrap(), "2024-04-01");
+        assert_eq!(due("next monday").unwrap(), "2024-03-04");
+        assert_eq!(due("friday").unwrap(), "2024-03-08");
+        assert_eq!(due("2024-12-24").unwrap(), "2024-12-24");
+    }
+    
+    #[test]
+    fn unrecognized_due_phrases_are_errors() {
+        let due = |input: &str| parse_due(input.to_string(), Some("2024-03-01".to_string()));
+        assert!(due("whenever").unwrap_err().contains("Unrecognized due date"));
+        assert!(due("in many days").is_err());
+        assert!(parse_due("today".to_string(), Some("March 1st".to_string())).is_err());
+    }
+    
+    #[test]
+    fn date_only_due_round_trips_as_all_day() {
+        for line in ["DUE;VALUE=DATE:20250301", "DUE:20250301"] {
+            let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Day", line]);
+            assert!(todo.all_day);
+            let component = vtodo_component(&todo, None);
+            assert!(component.lines.iter().any(|written| written == line));
+            assert!(component.to_todo("test").unwrap().all_day);
+        }
+    }
+    
+    #[test]
+    fn timed_due_round_trips_with_its_time() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Midnight", "DUE:20250301T000000Z"]);
+        assert!(!todo.all_day);
+        assert_eq!(todo.due_time.as_deref(), Some("00:00:00"));
+        let component = vtodo_component(&todo, None);
+        assert!(component.lines.iter().any(|line| line == "DUE:20250301T000000Z"));
+        assert!(!component.to_todo("test").unwrap().all_day);
+    }
+    
+    #[test]
+    fn canonicalizing_twice_is_idempotent() {
+        with_calendars_dir(|dir| {
+            let path = dir.join("messy.ics");
+            let long = "x".repeat(120);
+            fs::write(&path, format!(
+                "begin:VCALENDAR\nprodid:-//messy//EN\nversion:2.0\nX-WR-CALNAME:Messy\nBEGIN:VTODO\nsummary:Tidy up\nx-custom;foo=bar:kept\nDESCRIPTION:{}\nuid:a@test\nEND:VTODO\nEND:VCALENDAR\n",
+                long
+            ))
+            .unwrap();
+            let canonicalize = || {
+                with_cache(|cache| tauri::async_runtime::block_on(canonicalize_calendar("messy.ics".to_string(), cache))).unwrap();
+                fs::read_to_string(&path).unwrap()
+            };
+            
+            let once = canonicalize();
+            assert_eq!(canonicalize(), once);
+            
+            assert!(once.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//messy//EN\r\n"));
+            assert!(once.contains("X-CUSTOM;foo=bar:kept\r\n"));
+            assert!(once.split("\r\n").all(|line| line.len() <= MAX_LINE_OCTETS));
+            assert!(!once.replace("\r\n", "").contains('\n'));
+            let todo = &parse_todos_from_file(&path).unwrap()[0];
+            assert_eq!((todo.id.as_str(), todo.title.as_str()), ("a@test", "Tidy up"));
+            assert_eq!(todo.description, long);
+        });
+    }
+    
+    #[test]
+    fn saved_calendar_is_committed_to_its_repo() {
+        let scratch = TempDir::new();
+        let repo = git2::Repository::init(&scratch.0).unwrap();
+        let path = write_calendar(&scratch.0, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:Tracked"]));
+        
+        commit_calendar_change(&path).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message(), Some("Update work via 2DO"));
+        assert!(head.tree().unwrap().get_name("work.ics").is_some());
+        
+        // Nothing changed: no empty commit
+        commit_calendar_change(&path).unwrap();
+        assert_eq!(repo.head().unwrap().peel_to_commit().unwrap().id(), head.id());
+        
+        write_calendar(&scratch.0, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:Edited"]));
+        commit_calendar_change(&path).unwrap();
+        let next = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(next.parent_id(0).unwrap(), head.id());
+    }
+    
+    #[test]
+    fn calendar_outside_a_repo_is_not_committed() {
+        let scratch = TempDir::new();
+        let path = write_calendar(&scratch.0, "work.ics", "");
+        assert!(commit_calendar_change(&path).is_ok());
+        assert!(git2::Repository::open(&scratch.0).is_err());
+    }
+    
+    // Merge `source.ics` into `target.ics`, each holding a version of shared@test.
+    // Returns the merge result, the shared@test that was kept and the target's text.
+    fn merge_overlapping(policy: ConflictPolicy, target_modified: &str) -> (MergeResult, Todo, String) {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "target.ics", &vtodo(&[
+                "UID:shared@test",
+                "SUMMARY:Target",
+                &format!("LAST-MODIFIED:{}", target_modified),
+            ]));
+            write_calendar(dir, "source.ics", &[
+                vtodo(&["UID:shared@test", "SUMMARY:Source", "LAST-MODIFIED:20250102T000000Z", "X-SOURCE:kept"]),
+                vtodo(&["UID:new@test", "SUMMARY:New"]),
+            ].concat());
+            
+            let result = with_cache(|cache| {
+                tauri::async_runtime::block_on(merge_calendars(vec!["source.ics".to_string()], "target.ics".to_string(), policy, cache))
+            })
+            .unwrap();
+            let todos = parse_todos_from_file(&dir.join("target.ics")).unwrap();
+            assert_eq!(todos.iter().filter(|todo| todo.id == "new@test").count(), 1);
+            let shared = todos.into_iter().find(|todo| todo.id == "shared@test").unwrap();
+            (result, shared, fs::read_to_string(dir.join("target.ics")).unwrap())
+        })
+    }
+    
+    #[test]
+    fn merge_keep_newest_prefers_the_later_last_modified() {
+        let (result, shared, content) = merge_overlapping(ConflictPolicy::KeepNewest, "20250101T000000Z");
+        assert_eq!((result.merged, result.skipped), (2, 0));
+        assert_eq!(shared.title, "Source");
+        assert!(content.contains("X-SOURCE:kept"));
+        
+        let (result, shared, _) = merge_overlapping(ConflictPolicy::KeepNewest, "20250103T000000Z");
+        assert_eq!((result.merged, result.skipped), (1, 1));
+        assert_eq!(shared.title, "Target");
+    }
+    
+    #[test]
+    fn merge_keep_target_skips_the_source_version() {
+        let (result, shared, content) = merge_overlapping(ConflictPolicy::KeepTarget, "20250101T000000Z");
+        assert_eq!((result.merged, result.skipped), (1, 1));
+        assert_eq!(shared.title, "Target");
+        assert!(!content.contains("X-SOURCE"));
+    }
+    
+    #[test]
+    fn merge_keep_source_replaces_the_target_version() {
+        let (result, shared, content) = merge_overlapping(ConflictPolicy::KeepSource, "20250103T000000Z");
+        assert_eq!((result.merged, result.skipped), (2, 0));
+        assert_eq!(shared.title, "Source");
+        assert!(content.contains("X-SOURCE:kept"));
+    }
+    
+    #[test]
+    fn due_ascending_puts_undated_todos_last() {
+        let mut todos = vec![
+            parse_vtodo(&["UID:c@test", "SUMMARY:No due"]),
+            parse_vtodo(&["UID:b@test", "SUMMARY:Later", "DUE;VALUE=DATE:20250310"]),
+            parse_vtodo(&["UID:a@test", "SUMMARY:No due either"]),
+            parse_vtodo(&["UID:e@test", "SUMMARY:Soon", "DUE;VALUE=DATE:20250301"]),
+            parse_vtodo(&["UID:d@test", "SUMMARY:Also later", "DUE;VALUE=DATE:20250310"]),
+        ];
+        let ids = |todos: &[Todo]| todos.iter().map(|todo| todo.id.clone()).collect::<Vec<_>>();
+        
+        sort_todo_list(&mut todos, SortKey::Due, SortDirection::Asc);
+        assert_eq!(ids(&todos), ["e@test", "b@test", "d@test", "a@test", "c@test"]);
+        
+        // Descending still keeps undated todos at the end
+        sort_todo_list(&mut todos, SortKey::Due, SortDirection::Desc);
+        assert_eq!(&ids(&todos)[3..], ["a@test", "c@test"]);
+    }
+    
+    #[test]
+    fn priority_ascending_puts_high_first() {
+        let mut todos = vec![
+            parse_vtodo(&["UID:low@test", "SUMMARY:Low", "PRIORITY:9"]),
+            parse_vtodo(&["UID:high@test", "SUMMARY:High", "PRIORITY:1"]),
+            parse_vtodo(&["UID:medium@test", "SUMMARY:Medium", "PRIORITY:5"]),
+        ];
+        sort_todo_list(&mut todos, SortKey::Priority, SortDirection::Asc);
+        assert_eq!(todos.iter().map(|todo| todo.priority.as_str()).collect::<Vec<_>>(), ["high", "medium", "low"]);
+    }
+    
+    #[test]
+    fn uri_attachment_round_trips() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Read", "ATTACH;FMTTYPE=application/pdf:https://example.com/spec.pdf"]);
+        let attachment = &todo.attachments[0];
+        assert_eq!(attachment.uri.as_deref(), Some("https://example.com/spec.pdf"));
+        assert_eq!(attachment.mime.as_deref(), Some("application/pdf"));
+        assert!(attachment.data.is_none());
+        
+        let component = vtodo_component(&todo, None);
+        assert!(component.lines.iter().any(|line| line == "ATTACH;FMTTYPE=application/pdf:https://example.com/spec.pdf"));
+    }
+    
+    #[test]
+    fn inline_binary_attachment_round_trips() {
+        let data: Vec<u8> = (0..=255).collect();
+        let todo = Todo {
+            id: "a@test".to_string(),
+            attachments: vec![Attachment { uri: None, data: Some(data.clone()), mime: Some("application/octet-stream".to_string()) }],
+            ..Todo::new("Binary", "test")
+        };
+        
+        let component = vtodo_component(&todo, None);
+        assert!(component.lines.iter().any(|line| line.starts_with("ATTACH;") && line.contains("ENCODING=BASE64")));
+        // The long base64 value is folded
+        assert!(component.lines.iter().all(|line| line.len() <= MAX_LINE_OCTETS));
+        
+        let parsed = component.to_todo("test").unwrap();
+        assert_eq!(parsed.attachments[0].data.as_deref(), Some(&data[..]));
+        assert_eq!(parsed.attachments[0].mime.as_deref(), Some("application/octet-stream"));
+    }
+    
+    #[test]
+    fn every_comment_is_kept_in_order() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Plan", "COMMENT:First\\, really", "COMMENT:Second"]);
+        assert_eq!(todo.comments, ["First, really", "Second"]);
+        
+        let component = vtodo_component(&todo, None);
+        let comments: Vec<&str> = component.lines.iter().filter(|line| line.starts_with("COMMENT:")).map(String::as_str).collect();
+        assert_eq!(comments, ["COMMENT:First\\, really", "COMMENT:Second"]);
+    }
+    
+    #[test]
+    fn only_the_first_description_is_kept() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Plan", "DESCRIPTION:Original", "DESCRIPTION:Duplicate"]);
+        assert_eq!(todo.description, "Original");
+    }
+    
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+    
+    #[test]
+    fn add_args_build_a_todo() {
+        let (calendar, todo) = todo_from_add_args(&args(&[
+            "--calendar", "Inbox",
+            "--title", "Buy milk",
+            "--due", "2024-03-01",
+            "--priority", "HIGH",
+            "--description", "Semi-skimmed",
+            "--category", "Errands",
+        ]))
+        .unwrap();
+        assert_eq!(calendar, "Inbox.ics");
+        assert_eq!(todo.calendar_name, "Inbox");
+        assert_eq!(todo.title, "Buy milk");
+        assert_eq!(todo.due_date.as_deref(), Some("2024-03-01"));
+        assert_eq!(todo.priority, "high");
+        assert_eq!(todo.description, "Semi-skimmed");
+        assert_eq!(todo.categories, ["Errands"]);
+        
+        // An explicit extension is kept, and optional flags default
+        let (calendar, todo) = todo_from_add_args(&args(&["--title", "Call", "--calendar", "work.ics"])).unwrap();
+        assert_eq!((calendar.as_str(), todo.priority.as_str(), todo.due_date), ("work.ics", "medium", None));
+    }
+    
+    #[test]
+    fn invalid_add_args_are_rejected() {
+        let error = |list: &[&str]| todo_from_add_args(&args(list)).unwrap_err();
+        assert!(error(&["--title", "No calendar"]).contains("Missing --calendar"));
+        assert!(error(&["--calendar", "Inbox", "--title", " "]).contains("Missing --title"));
+        assert!(error(&["--calendar", "Inbox", "--title", "T", "--priority", "urgent"]).contains("Invalid priority"));
+        assert!(error(&["--calendar", "Inbox", "--title", "T", "--due"]).contains("Missing value"));
+        assert!(error(&["--calendar", "Inbox", "--title", "T", "--colour", "red"]).contains("Unknown argument"));
+    }
+    
+    #[test]
+    fn cli_add_appends_the_todo() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "Inbox.ics", "");
+            assert_eq!(run_cli(&args(&["add", "--calendar", "Inbox", "--title", "From the shell"])), Some(0));
+            assert_eq!(run_cli(&args(&["add", "--calendar", "Inbox"])), Some(1));
+            assert_eq!(run_cli(&args(&["--some-gui-flag"])), None);
+            
+            let todos = parse_todos_from_file(&dir.join("Inbox.ics")).unwrap();
+            assert_eq!(todos.len(), 1);
+            assert_eq!(todos[0].title, "From the shell");
+            assert!(!todos[0].id.is_empty());
+        });
+    }
+    
+    #[test]
+    fn stdio_dispatcher_runs_commands() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:Existing"]));
+            let cache = TodoCache::default();
+            
+            let calendars = dispatch_stdio_command("list_calendars", &serde_json::Value::Null, &cache).unwrap();
+            assert_eq!(calendars[0]["name"], "work");
+            
+            let todo = serde_json::to_value(Todo::new("Piped", "work")).unwrap();
+            let added = dispatch_stdio_command("add_todo", &serde_json::json!({ "calendar_path": "work.ics", "todo": todo }), &cache).unwrap();
+            assert_eq!(added["title"], "Piped");
+            
+            let loaded = dispatch_stdio_command("load_todos", &serde_json::json!({ "calendar_path": "work.ics" }), &cache).unwrap();
+            assert_eq!(loaded.as_array().unwrap().len(), 2);
+            
+            let due = dispatch_stdio_command("parse_due", &serde_json::json!({ "input": "tomorrow", "reference": "2024-03-01" }), &cache).unwrap();
+            assert_eq!(due, "2024-03-02");
+        });
+    }
+    
+    #[test]
+    fn stdio_dispatcher_reports_errors() {
+        let cache = TodoCache::default();
+        let error = dispatch_stdio_command("launch_rockets", &serde_json::Value::Null, &cache).unwrap_err();
+        assert!(error.contains("Unknown command"));
+        let error = dispatch_stdio_command("parse_due", &serde_json::json!({ "input": 42 }), &cache).unwrap_err();
+        assert!(error.contains("Invalid argument 'input'"));
+    }
+    
+    #[test]
+    fn settings_default_when_the_file_is_missing_or_invalid() {
+        with_calendars_dir(|dir| {
+            let defaults = serde_json::to_value(Settings::default()).unwrap();
+            assert_eq!(serde_json::to_value(load_settings()).unwrap(), defaults);
+            
+            fs::write(dir.parent().unwrap().join(SETTINGS_FILE_NAME), "{ not json").unwrap();
+            assert_eq!(serde_json::to_value(load_settings()).unwrap(), defaults);
+            
+            // Missing fields take their defaults
+            fs::write(dir.parent().unwrap().join(SETTINGS_FILE_NAME), r#"{"default_priority":"low"}"#).unwrap();
+            let settings = load_settings();
+            assert_eq!(settings.default_priority, "low");
+            assert!(settings.show_completed);
+        });
+    }
+    
+    #[test]
+    fn settings_round_trip() {
+        with_calendars_dir(|dir| {
+            let settings = Settings {
+                default_calendar: Some("work".to_string()),
+                default_priority: "high".to_string(),
+                show_completed: false,
+                notifications_enabled: false,
+                ..Settings::default()
+            };
+            save_settings(settings.clone()).unwrap();
+            assert!(dir.parent().unwrap().join(SETTINGS_FILE_NAME).is_file());
+            assert_eq!(serde_json::to_value(load_settings()).unwrap(), serde_json::to_value(settings).unwrap());
+            
+            let invalid = Settings { default_priority: "urgent".to_string(), ..Settings::default() };
+            assert!(save_settings(invalid).unwrap_err().contains("Invalid default priority"));
+        });
+    }
+    
+    #[test]
+    fn confidential_class_round_trips() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Salary review", "CLASS:confidential"]);
+        assert_eq!(todo.class.as_deref(), Some("CONFIDENTIAL"));
+        let component = vtodo_component(&todo, None);
+        assert!(component.lines.iter().any(|line| line == "CLASS:CONFIDENTIAL"));
+        assert_eq!(component.to_todo("test").unwrap().class.as_deref(), Some("CONFIDENTIAL"));
+    }
+    
+    #[test]
+    fn unknown_class_becomes_public() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Team lunch", "CLASS:X-FRIENDS-ONLY"]);
+        assert_eq!(todo.class.as_deref(), Some("PUBLIC"));
+    }
+    
+    #[test]
+    fn geo_pair_round_trips() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Visit", "GEO:37.386013;-122.082932"]);
+        assert_eq!(todo.geo, Some((37.386013, -122.082932)));
+        let component = vtodo_component(&todo, None);
+        assert!(component.lines.iter().any(|line| line == "GEO:37.386013;-122.082932"));
+        assert_eq!(component.to_todo("test").unwrap().geo, todo.geo);
+    }
+    
+    #[test]
+    fn out_of_range_geo_is_rejected() {
+        assert!(parse_geo("91.0;0.0").unwrap_err().contains("Latitude out of range"));
+        assert!(parse_geo("0.0;-180.5").unwrap_err().contains("Longitude out of range"));
+        assert!(parse_geo("37.38,-122.08").is_err());
+        assert_eq!(parse_vtodo(&["UID:a@test", "SUMMARY:Nowhere", "GEO:120;10"]).geo, None);
+        
+        // Never written either
+        let todo = Todo { id: "a@test".to_string(), geo: Some((0.0, 200.0)), ..Todo::new("Bad", "test") };
+        assert!(!vtodo_component(&todo, None).lines.iter().any(|line| line.starts_with("GEO")));
+    }
+    
+    // The scanner vtodo_blocks replaced: collect every line, then walk indices,
+    // handing each block to `visit`
+    fn collected_vtodo_blocks<'a>(content: &'a str, mut visit: impl FnMut(usize, Vec<&'a str>)) {
+        let lines: Vec<&str> = split_lines(content).collect();
+        let mut calendar_blocks: usize = 0;
+        let mut i = 0;
+        while i < lines.len() {
+            if lines[i].trim() == "BEGIN:VCALENDAR" {
+                calendar_blocks += 1;
+            }
+            if lines[i].trim() == "BEGIN:VTODO" {
+                let start = i + 1;
+                while i < lines.len() && lines[i].trim() != "END:VTODO" {
+                    i += 1;
+                }
+                visit(calendar_blocks.saturating_sub(1), lines[start..i].to_vec());
+            }
+            i += 1;
+        }
+    }
+    
+    fn synthetic_calendar(todos: usize) -> String {
+        let mut content = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\n");
+        for i in 0..todos {
+            content.push_str(&format!(
+                "BEGIN:VTODO\r\nUID:todo-{i}@test\r\nSUMMARY:Task number {i} with a folded\r\n  summary line\r\nDESCRIPTION:Line one\\nLine two\r\nPRIORITY:{}\r\nDUE;VALUE=DATE:2026{:02}{:02}\r\nCATEGORIES:Work,Home\r\nSTATUS:NEEDS-ACTION\r\nEND:VTODO\r\n",
+                i % 10,
+                i % 12 + 1,
+                i % 28 + 1,
+            ));
+        }
+        content.push_str("END:VCALENDAR\r\n");
+        content
+    }
+    
+    #[test]
+    fn vtodo_blocks_match_collected_scanner() {
+        let mut content = synthetic_calendar(50);
+        // A second VCALENDAR block, bare CR line endings and an unterminated VTODO
+        content.push_str("BEGIN:VCALENDAR\rBEGIN:VTODO\rUID:cr@test\rSUMMARY:Mac\rEND:VTODO\rEND:VCALENDAR\r");
+        content.push_str("BEGIN:VCALENDAR\nBEGIN:VTODO\nUID:open@test\nSUMMARY:Unterminated\n");
+        
+        let lazy: Vec<_> = vtodo_blocks(&content).collect();
+        let mut collected = Vec::new();
+        collected_vtodo_blocks(&content, |calendar_block, block| collected.push((calendar_block, block)));
+        assert_eq!(lazy, collected);
+        assert_eq!(lazy.len(), 52);
+        assert_eq!(lazy[50].0, 1);
+        assert_eq!(lazy[51].0, 2);
+        
+        let parsed: Vec<_> = lazy
+            .iter()
+            .map(|(_, block)| serde_json::to_value(parse_vtodo_from_lines(block, "cal").unwrap()).unwrap())
+            .collect();
+        let expected: Vec<_> = collected
+            .iter()
+            .map(|(_, block)| serde_json::to_value(parse_vtodo_from_lines(block, "cal").unwrap()).unwrap())
+            .collect();
+        assert_eq!(parsed, expected);
+    }
+    
+    #[test]
+    fn modification_token_matches_the_file_metadata() {
+        let scratch = TempDir::new();
+        let path = write_calendar(&scratch.0, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:Task"]));
+        let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_123);
+        set_mtime(&path, modified);
+        
+        let token = modified_token(file_modified_time(&path).unwrap());
+        assert_eq!(token, modified_token(fs::metadata(&path).unwrap().modified().unwrap()));
+        // The token handed to the frontend is what a save compares against
+        assert!(check_not_modified(&path, Some(&token)).is_ok());
+    }
+    
+    #[test]
+    fn new_york_output_timezone_writes_tzid_and_vtimezone() {
+        with_calendars_dir(|dir| {
+            save_settings(Settings { output_timezone: Some("America/New_York".to_string()), ..Settings::default() }).unwrap();
+            let path = write_calendar(dir, "work.ics", "");
+            let todo = Todo {
+                id: "a@test".to_string(),
+                due_date: Some("2025-01-15".to_string()),
+                due_time: Some("14:00:00".to_string()),
+                all_day: false,
+                ..Todo::new("Call", "work")
+            };
+            
+            let content = render_todos_into(&path, &[todo]).unwrap();
+            assert!(content.contains("DUE;TZID=America/New_York:20250115T090000\r\n"));
+            assert!(content.contains("BEGIN:VTIMEZONE\r\nTZID:America/New_York\r\n"));
+            assert_eq!(content.matches("BEGIN:VTIMEZONE").count(), 1);
+            
+            // Reading it back converts to UTC again
+            let todos = parse_todos_from_content(&content, "work");
+            assert_eq!(todos[0].due_time.as_deref(), Some("14:00:00"));
+        });
+    }
+    
+    #[test]
+    fn utc_is_written_without_an_output_timezone() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", "");
+            let todo = Todo { id: "a@test".to_string(), due_date: Some("2025-01-15".to_string()), due_time: Some("14:00:00".to_string()), all_day: false, ..Todo::new("Call", "work") };
+            let content = render_todos_into(&path, &[todo]).unwrap();
+            assert!(content.contains("DUE:20250115T140000Z\r\n"));
+            assert!(!content.contains("VTIMEZONE"));
+        });
+    }
+    
+    // A local date `days` from today as YYYYMMDD, for DUE;VALUE=DATE values
+    fn ical_day(days: i64) -> String {
+        (chrono::Local::now().date_naive() + chrono::Duration::days(days)).format("%Y%m%d").to_string()
+    }
+    
+    #[test]
+    fn tray_count_is_open_todos_due_today() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:Today", &format!("DUE;VALUE=DATE:{}", ical_day(0))]),
+                vtodo(&["UID:b@test", "SUMMARY:Overdue", &format!("DUE;VALUE=DATE:{}", ical_day(-2))]),
+                vtodo(&["UID:c@test", "SUMMARY:Done", &format!("DUE;VALUE=DATE:{}", ical_day(0)), "STATUS:COMPLETED"]),
+            ].concat());
+            write_calendar(dir, "home.ics", &vtodo(&["UID:d@test", "SUMMARY:Also today", &format!("DUE;VALUE=DATE:{}", ical_day(0))]));
+            assert_eq!(count_due_today(), Ok(2));
+        });
+    }
+    
+    #[test]
+    fn import_copies_into_the_calendars_dir_with_a_unique_name() {
+        with_calendars_dir(|dir| {
+            let downloads = TempDir::new();
+            let source = write_calendar(&downloads.0, "Team Tasks.ics", &vtodo(&["UID:a@test", "SUMMARY:Imported"]));
+            let import = |name: Option<&str>| {
+                tauri::async_runtime::block_on(import_ics(source.to_string_lossy().to_string(), name.map(str::to_string)))
+            };
+            
+            let first = import(None).unwrap();
+            assert_eq!(first.todo_count, 1);
+            assert!(Path::new(&first.path).starts_with(dir));
+            assert_eq!(parse_todos_from_file(Path::new(&first.path)).unwrap()[0].title, "Imported");
+            
+            // Importing again picks the next free name
+            let second = import(None).unwrap();
+            assert_ne!(second.path, first.path);
+            assert!(second.name.ends_with("-1"));
+            
+            let named = import(Some("Renamed")).unwrap();
+            assert_eq!(named.name, "Renamed");
+            assert!(source.exists());
+        });
+    }
+    
+    #[test]
+    fn import_rejects_files_without_todos() {
+        with_calendars_dir(|_| {
+            let downloads = TempDir::new();
+            let source = write_calendar(&downloads.0, "events.ics", "BEGIN:VEVENT\r\nUID:e@test\r\nEND:VEVENT\r\n");
+            let error = tauri::async_runtime::block_on(import_ics(source.to_string_lossy().to_string(), None)).unwrap_err();
+            assert!(error.contains("No VTODO"));
+        });
+    }
+    
+    #[test]
+    fn exported_todo_parses_back_to_one_todo() {
+        with_calendars_dir(|dir| {
+            let timezone = |tzid: &str| format!("BEGIN:VTIMEZONE\r\nTZID:{}\r\nEND:VTIMEZONE\r\n", tzid);
+            write_calendar(dir, "work.ics", &[
+                timezone("Europe/Paris"),
+                timezone("Asia/Tokyo"),
+                vtodo(&["UID:a@test", "SUMMARY:Share me", "DUE;TZID=Europe/Paris:20250301T090000", "X-CUSTOM:kept"]),
+                vtodo(&["UID:b@test", "SUMMARY:Other"]),
+            ].concat());
+            
+            let exported = tauri::async_runtime::block_on(export_todo("work.ics".to_string(), "a@test".to_string())).unwrap();
+            let todos = parse_ics_string(exported.clone(), "shared".to_string()).unwrap();
+            assert_eq!(todos.len(), 1);
+            assert_eq!(todos[0].id, "a@test");
+            assert!(exported.starts_with("BEGIN:VCALENDAR\r\n") && exported.ends_with("END:VCALENDAR\r\n"));
+            assert!(exported.contains("X-CUSTOM:kept\r\n"));
+            assert!(exported.contains("TZID:Europe/Paris") && !exported.contains("Asia/Tokyo"));
+            
+            let missing = tauri::async_runtime::block_on(export_todo("work.ics".to_string(), "zzz@test".to_string()));
+            assert!(missing.unwrap_err().contains("Todo not found"));
+        });
+    }
+    
+    #[test]
+    fn open_count_skips_completed_and_cancelled() {
+        let content = format!(
+            "BEGIN:VCALENDAR\r\n{}END:VCALENDAR\r\n",
+            [
+                vtodo(&["UID:a@test", "SUMMARY:Open"]),
+                vtodo(&["UID:b@test", "SUMMARY:Done", "STATUS:COMPLETED"]),
+                vtodo(&["UID:c@test", "SUMMARY:Dropped", "STATUS:cancelled"]),
+                vtodo(&["UID:d@test", "SUMMARY:Started", "STATUS:IN-PROCESS"]),
+            ]
+            .concat()
+        );
+        assert_eq!(todo_counts(&content), (4, 2));
+        
+        let scratch = TempDir::new();
+        let path = scratch.0.join("work.ics");
+        fs::write(&path, &content).unwrap();
+        let info = calendar_file_info(&path, true).unwrap();
+        assert_eq!((info.todo_count, info.open_count), (4, 2));
+    }
+    
+    #[test]
+    fn due_before_start_is_rejected() {
+        let todo = Todo {
+            start_date: Some("2025-03-10".to_string()),
+            due_date: Some("2025-03-09".to_string()),
+            ..Todo::new("Backwards", "test")
+        };
+        assert!(todo.validate().unwrap_err().contains("due before it starts"));
+        
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", "");
+            let preview = tauri::async_runtime::block_on(preview_save("work.ics".to_string(), vec![todo]));
+            assert!(preview.is_err());
+        });
+    }
+    
+    #[test]
+    fn due_equal_to_start_is_allowed() {
+        let same_day = Todo {
+            start_date: Some("2025-03-10".to_string()),
+            due_date: Some("2025-03-10".to_string()),
+            ..Todo::new("Same day", "test")
+        };
+        assert!(same_day.validate().is_ok());
+        
+        let same_instant = Todo {
+            start_date: Some("2025-03-10T09:00:00".to_string()),
+            due_date: Some("2025-03-10".to_string()),
+            due_time: Some("09:00:00".to_string()),
+            all_day: false,
+            ..Todo::new("Same time", "test")
+        };
+        assert!(same_instant.validate().is_ok());
+        let earlier = Todo { due_time: Some("08:59:59".to_string()), ..same_instant };
+        assert!(earlier.validate().is_err());
+    }
+    
+    #[test]
+    fn new_uids_use_the_configured_domain() {
+        assert!(new_uid("example.org").ends_with("@example.org"));
+        assert!(new_uid("  ").ends_with(&format!("@{}", DEFAULT_UID_DOMAIN)));
+        assert_ne!(new_uid("example.org"), new_uid("example.org"));
+        
+        with_calendars_dir(|dir| {
+            save_settings(Settings { uid_domain: "example.org".to_string(), ..Settings::default() }).unwrap();
+            let path = write_calendar(dir, "work.ics", "");
+            let added = append_todo(&path, Todo::new("Fresh", "work"), &TodoCache::default()).unwrap();
+            assert!(added.id.ends_with("@example.org"));
+            
+            // Existing UIDs are kept as they are
+            let existing = Todo { id: "legacy-1234".to_string(), ..Todo::new("Old", "work") };
+            assert_eq!(append_todo(&path, existing, &TodoCache::default()).unwrap().id, "legacy-1234");
+        });
+    }
+    
+    #[test]
+    fn organizer_and_attendees_round_trip() {
+        let lines = [
+            "ORGANIZER;CN=Alice:mailto:alice@example.com",
+            "ATTENDEE;CN=Bob;PARTSTAT=ACCEPTED;ROLE=REQ-PARTICIPANT:mailto:bob@example.com",
+            "ATTENDEE;CN=\"Carol, PhD\";PARTSTAT=DECLINED;RSVP=TRUE:mailto:carol@example.com",
+        ];
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Plan offsite", lines[0], lines[1], lines[2]]);
+        let organizer = todo.organizer.as_ref().unwrap();
+        assert_eq!((organizer.address.as_str(), organizer.cn.as_deref()), ("mailto:alice@example.com", Some("Alice")));
+        let partstats: Vec<Option<&str>> = todo.attendees.iter().map(|a| a.partstat.as_deref()).collect();
+        assert_eq!(partstats, [Some("ACCEPTED"), Some("DECLINED")]);
+        assert_eq!(todo.attendees[1].cn.as_deref(), Some("Carol, PhD"));
+        
+        // Long lines are folded, so compare the unfolded text
+        let written = vtodo_component(&todo, None).lines.join("\r\n").replace("\r\n ", "");
+        for line in lines {
+            assert!(written.contains(&format!("{}\r\n", line)), "missing {}", line);
+        }
+    }
+    
+    #[test]
+    fn due_within_a_week_includes_today_and_day_seven() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:day7@test", "SUMMARY:Day seven", &format!("DUE;VALUE=DATE:{}", ical_day(7))]),
+                vtodo(&["UID:today@test", "SUMMARY:Today", &format!("DUE;VALUE=DATE:{}", ical_day(0))]),
+                vtodo(&["UID:day8@test", "SUMMARY:Too late", &format!("DUE;VALUE=DATE:{}", ical_day(8))]),
+                vtodo(&["UID:past@test", "SUMMARY:Overdue", &format!("DUE;VALUE=DATE:{}", ical_day(-1))]),
+                vtodo(&["UID:done@test", "SUMMARY:Done", &format!("DUE;VALUE=DATE:{}", ical_day(1)), "STATUS:COMPLETED"]),
+                vtodo(&["UID:timed@test", "SUMMARY:Timed", &format!("DUE;TZID=Europe/London:{}T120000", ical_day(3))]),
+            ].concat());
+            
+            let due = with_cache(|cache| tauri::async_runtime::block_on(list_due_within("work.ics".to_string(), 7, cache))).unwrap();
+            let ids: Vec<&str> = due.iter().map(|todo| todo.id.as_str()).collect();
+            assert_eq!(ids, ["today@test", "timed@test", "day7@test"]);
+        });
+    }
+    
+    #[test]
+    fn todos_are_grouped_by_each_category() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:Both", "CATEGORIES:Work,Home", "DUE;VALUE=DATE:20250310"]),
+                vtodo(&["UID:b@test", "SUMMARY:Work only", "CATEGORIES:Work", "DUE;VALUE=DATE:20250301"]),
+                vtodo(&["UID:c@test", "SUMMARY:None"]),
+            ].concat());
+            
+            let groups = with_cache(|cache| tauri::async_runtime::block_on(group_by_category("work.ics".to_string(), cache))).unwrap();
+            let summary: Vec<(&str, Vec<&str>)> = groups
+                .iter()
+                .map(|(category, todos)| (category.as_str(), todos.iter().map(|todo| todo.id.as_str()).collect()))
+                .collect();
+            assert_eq!(summary, [
+                ("Home", vec!["a@test"]),
+                (UNCATEGORIZED, vec!["c@test"]),
+                ("Work", vec!["b@test", "a@test"]),
+            ]);
+        });
+    }
+    
+    #[test]
+    fn completed_last_is_a_stable_partition() {
+        let todo = |id: &str, completed: bool| Todo { id: id.to_string(), completed, ..Todo::new(id, "test") };
+        let mut todos = vec![
+            todo("done-1", true),
+            todo("open-1", false),
+            todo("done-2", true),
+            todo("open-2", false),
+            todo("open-3", false),
+        ];
+        partition_completed_last(&mut todos);
+        let ids: Vec<&str> = todos.iter().map(|todo| todo.id.as_str()).collect();
+        assert_eq!(ids, ["open-1", "open-2", "open-3", "done-1", "done-2"]);
+    }
+    
+    #[test]
+    fn saving_an_unchanged_todo_keeps_its_dtstamp() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:Untouched", "DTSTAMP:20200101T000000Z"]),
+                vtodo(&["UID:b@test", "SUMMARY:Edited", "DTSTAMP:20200101T000000Z"]),
+            ].concat());
+            let mut todos = parse_todos_from_file(&path).unwrap();
+            todos[1].title = "Edited again".to_string();
+            
+            let saved = render_todos_into(&path, &todos).unwrap();
+            let stamps: Vec<String> = parse_todos_from_content(&saved, "work").into_iter().filter_map(|todo| todo.dtstamp).collect();
+            assert_eq!(stamps[0], "2020-01-01T00:00:00");
+            assert_ne!(stamps[1], "2020-01-01T00:00:00");
+        });
+    }
+    
+    // Fuzzy search `work.ics`, returning (UID, score) pairs in rank order
+    fn fuzzy_search(query: &str) -> Vec<(String, i64)> {
+        with_cache(|cache| tauri::async_runtime::block_on(search_todos_fuzzy("work.ics".to_string(), query.to_string(), cache)))
+            .unwrap()
+            .into_iter()
+            .map(|hit| (hit.todo.id, hit.score))
+            .collect()
+    }
+    
+    #[test]
+    fn fuzzy_search_tolerates_transposed_letters() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:desc@test", "SUMMARY:Board meeting", "DESCRIPTION:Bring the quarterly report"]),
+                vtodo(&["UID:title@test", "SUMMARY:Quarterly report"]),
+                vtodo(&["UID:other@test", "SUMMARY:Water plants"]),
+            ].concat());
+            
+            // Title matches rank above description-only matches
+            let hits = fuzzy_search("reprot");
+            assert_eq!(hits.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(), ["title@test", "desc@test"]);
+            assert!(hits[0].1 > hits[1].1);
+            
+            assert_eq!(fuzzy_search("quarterly")[0].0, "title@test");
+            assert!(fuzzy_search("zzqx").is_empty());
+            assert!(fuzzy_search("  ").is_empty());
+        });
+    }
+    
+    #[test]
+    fn typo_distance_counts_swaps_as_one_edit() {
+        assert_eq!(typo_distance("report", "reprot"), 1);
+        assert_eq!(typo_distance("report", "repot"), 1);
+        assert_eq!(typo_distance("report", "rpeotr"), 2);
+    }
+    
+    #[test]
+    fn calendar_color_can_be_read_set_and_cleared() {
+        with_calendars_dir(|dir| {
+            let path = dir.join("work.ics");
+            fs::write(&path, format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nX-APPLE-CALENDAR-COLOR:#FF2968\r\n{}END:VCALENDAR\r\n",
+                vtodo(&["UID:a@test", "SUMMARY:Keep me", "X-CUSTOM:kept"])
+            ))
+            .unwrap();
+            let color = || tauri::async_runtime::block_on(get_calendar_color("work.ics".to_string())).unwrap();
+            let set = |hex: &str| {
+                with_cache(|cache| tauri::async_runtime::block_on(set_calendar_color("work.ics".to_string(), hex.to_string(), cache)))
+            };
+            assert_eq!(color().as_deref(), Some("#FF2968"));
+            
+            set("#1e90ffcc").unwrap();
+            assert_eq!(color().as_deref(), Some("#1E90FFCC"));
+            let content = fs::read_to_string(&path).unwrap();
+            assert_eq!(content.matches(CALENDAR_COLOR_PROPERTY).count(), 1);
+            assert!(content.contains(&vtodo(&["UID:a@test", "SUMMARY:Keep me", "X-CUSTOM:kept"])));
+            assert_eq!(calendar_file_info(&path, true).unwrap().color.as_deref(), Some("#1E90FFCC"));
+            
+            set("").unwrap();
+            assert_eq!(color(), None);
+        });
+    }
+    
+    #[test]
+    fn invalid_calendar_colors_are_rejected() {
+        assert!(is_hex_color("#A1B2C3") && is_hex_color("#a1b2c3ff"));
+        for bad in ["A1B2C3", "#A1B2C", "#GGGGGG", "red"] {
+            assert!(!is_hex_color(bad), "{} accepted", bad);
+        }
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", "");
+            let error = with_cache(|cache| {
+                tauri::async_runtime::block_on(set_calendar_color("work.ics".to_string(), "blue".to_string(), cache))
+            })
+            .unwrap_err();
+            assert!(error.contains("Invalid color"));
+        });
+    }
+    
+    #[test]
+    fn resources_round_trip_as_a_list() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Workshop", "RESOURCES:Projector,Easel\\, large"]);
+        assert_eq!(todo.resources, ["Projector", "Easel, large"]);
+        let component = vtodo_component(&todo, None);
+        assert!(component.lines.iter().any(|line| line == "RESOURCES:Projector,Easel\\, large"));
+        assert_eq!(component.to_todo("test").unwrap().resources, todo.resources);
+    }
+    
+    #[test]
+    fn language_and_altrep_parameters_round_trip() {
+        let summary = "SUMMARY;LANGUAGE=en-US:Book flights";
+        let description = "DESCRIPTION;ALTREP=\"cid:part1.0001@example.org\";LANGUAGE=en-US:See the itinerary";
+        let todo = parse_vtodo(&["UID:a@test", summary, description]);
+        assert_eq!(todo.title, "Book flights");
+        assert_eq!(todo.summary_params, [("LANGUAGE".to_string(), "en-US".to_string())]);
+        assert_eq!(todo.description_params[0], ("ALTREP".to_string(), "cid:part1.0001@example.org".to_string()));
+        
+        let written = vtodo_component(&todo, None).lines.join("\r\n").replace("\r\n ", "");
+        assert!(written.contains(&format!("{}\r\n", summary)));
+        assert!(written.contains(&format!("{}\r\n", description)));
+    }
+    
+    #[test]
+    fn recompute_counts_sees_modified_files() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:One"]));
+            let counts = || -> Vec<(String, usize)> {
+                tauri::async_runtime::block_on(recompute_counts())
+                    .unwrap()
+                    .into_iter()
+                    .map(|calendar| (calendar.name, calendar.todo_count))
+                    .collect()
+            };
+            assert_eq!(tauri::async_runtime::block_on(list_calendars()).unwrap()[0].todo_count, 1);
+            
+            write_calendar(dir, "work.ics", &[vtodo(&["UID:a@test", "SUMMARY:One"]), vtodo(&["UID:b@test", "SUMMARY:Two"])].concat());
+            assert_eq!(counts(), [("work".to_string(), 2)]);
+            
+            // Calendars created since the scan are included, deleted ones dropped
+            tauri::async_runtime::block_on(create_calendar("Home".to_string())).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(counts(), [("Home".to_string(), 0)]);
+        });
+    }
+    
+    #[test]
+    fn request_method_survives_a_save() {
+        with_calendars_dir(|dir| {
+            let path = dir.join("invite.ics");
+            fs::write(&path, format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nMETHOD:REQUEST\r\n{}END:VCALENDAR\r\n",
+                vtodo(&["UID:a@test", "SUMMARY:Review the proposal"])
+            ))
+            .unwrap();
+            let content = fs::read_to_string(&path).unwrap();
+            assert_eq!(calendar_method(&content).as_deref(), Some("REQUEST"));
+            assert_eq!(calendar_file_info(&path, true).unwrap().method.as_deref(), Some("REQUEST"));
+            
+            let saved = render_todos_into(&path, &parse_todos_from_file(&path).unwrap()).unwrap();
+            assert!(saved.contains("\r\nMETHOD:REQUEST\r\n"));
+            assert!(!saved.contains("PUBLISH"));
+        });
+    }
+    
+    #[test]
+    fn plain_calendars_have_no_method() {
+        assert_eq!(calendar_method("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nEND:VCALENDAR\r\n"), None);
+        // A METHOD inside a component isn't the calendar's
+        assert_eq!(calendar_method("BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nMETHOD:PUBLISH\r\nEND:VTODO\r\nEND:VCALENDAR\r\n"), None);
+    }
+    
+    #[test]
+    fn progress_fraction_is_clamped() {
+        assert_eq!(progress_fraction(0, 0), 1.0);
+        assert_eq!(progress_fraction(50, 200), 0.25);
+        assert_eq!(progress_fraction(200, 200), 1.0);
+        assert_eq!(progress_fraction(300, 200), 1.0);
+    }
+    
+    #[test]
+    fn progress_is_reported_every_interval() {
+        let content = synthetic_calendar(PROGRESS_INTERVAL * 2 + 10);
+        let mut reports = Vec::new();
+        let todos = parse_todos_with_progress(&content, "big", |parsed, fraction| reports.push((parsed, fraction)));
+        assert_eq!(todos.len(), PROGRESS_INTERVAL * 2 + 10);
+        
+        let counts: Vec<usize> = reports.iter().map(|(parsed, _)| *parsed).collect();
+        assert_eq!(counts, [PROGRESS_INTERVAL, PROGRESS_INTERVAL * 2]);
+        // Every block is the same size, so the fractions track the share of VTODOs read
+        let expected = PROGRESS_INTERVAL as f64 / (PROGRESS_INTERVAL * 2 + 10) as f64;
+        assert!((reports[0].1 - expected).abs() < 0.01);
+        assert!(reports[0].1 < reports[1].1 && reports[1].1 < 1.0);
+    }
+    
+    #[test]
+    fn overdue_todos_are_rescheduled() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:late@test", "SUMMARY:Late", "DUE;VALUE=DATE:20200301"]),
+                vtodo(&["UID:timed@test", "SUMMARY:Late call", "DUE:20200302T153000Z"]),
+                vtodo(&["UID:future@test", "SUMMARY:Future", "DUE;VALUE=DATE:20990101"]),
+                vtodo(&["UID:done@test", "SUMMARY:Done", "DUE;VALUE=DATE:20200301", "STATUS:COMPLETED"]),
+            ].concat());
+            
+            let moved = with_cache(|cache| {
+                tauri::async_runtime::block_on(reschedule_overdue("work.ics".to_string(), Some("2030-06-01".to_string()), cache))
+            });
+            assert_eq!(moved, Ok(2));
+            
+            let todos = parse_todos_from_file(&path).unwrap();
+            let due: Vec<(Option<&str>, Option<&str>)> = todos.iter().map(|t| (t.due_date.as_deref(), t.due_time.as_deref())).collect();
+            assert_eq!(due, [
+                (Some("2030-06-01"), None),
+                (Some("2030-06-01"), Some("15:30:00")),
+                (Some("2099-01-01"), None),
+                (Some("2020-03-01"), None),
+            ]);
+            let content = fs::read_to_string(&path).unwrap();
+            assert_eq!(content.matches("LAST-MODIFIED:").count(), 2);
+        });
+    }
+    
+    #[test]
+    fn snoozed_todo_is_hidden_until_it_wakes() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:Later", "DUE;VALUE=DATE:20250301"]),
+                vtodo(&["UID:b@test", "SUMMARY:Now"]),
+            ].concat());
+            let snooze = |until: Option<&str>| {
+                with_cache(|cache| {
+                    tauri::async_runtime::block_on(snooze_todo("work.ics".to_string(), "a@test".to_string(), until.map(str::to_string), cache))
+                })
+            };
+            let active = || -> Vec<String> {
+                with_cache(|cache| tauri::async_runtime::block_on(list_active("work.ics".to_string(), cache)))
+                    .unwrap()
+                    .into_iter()
+                    .map(|todo| todo.id)
+                    .collect()
+            };
+            
+            snooze(Some("2099-01-01")).unwrap();
+            assert!(fs::read_to_string(&path).unwrap().contains(&format!("{}:20990101T000000Z", SNOOZE_PROPERTY)));
+            let todo = &parse_todos_from_file(&path).unwrap()[0];
+            assert_eq!(todo.snooze_until.as_deref(), Some("2099-01-01T00:00:00"));
+            assert_eq!(todo.due_date.as_deref(), Some("2025-03-01"));
+            assert_eq!(active(), ["b@test"]);
+            
+            // A snooze in the past has expired
+            snooze(Some("2000-01-01T08:00:00")).unwrap();
+            assert_eq!(active(), ["a@test", "b@test"]);
+            
+            snooze(None).unwrap();
+            assert!(!fs::read_to_string(&path).unwrap().contains(SNOOZE_PROPERTY));
+            assert!(snooze(Some("someday")).unwrap_err().contains("Invalid snooze time"));
+        });
+    }
+    
+    #[test]
+    fn description_metrics_count_words_and_reading_time() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &vtodo(&[
+                "UID:a@test",
+                "SUMMARY:Notes",
+                "DESCRIPTION:Pack the blue\\nsuitcase\\, then",
+                "  call a taxi",
+            ]));
+            let metrics = tauri::async_runtime::block_on(describe_todo("work.ics".to_string(), "a@test".to_string())).unwrap();
+            assert_eq!(metrics.words, 8);
+            assert_eq!(metrics.characters, "Pack the blue\nsuitcase, then call a taxi".chars().count());
+            assert_eq!(metrics.reading_minutes, 1);
+        });
+    }
+    
+    #[test]
+    fn reading_time_rounds_up() {
+        assert_eq!(text_metrics("").reading_minutes, 0);
+        assert_eq!(text_metrics(&"word ".repeat(READING_WORDS_PER_MINUTE)).reading_minutes, 1);
+        assert_eq!(text_metrics(&"word ".repeat(READING_WORDS_PER_MINUTE + 1)).reading_minutes, 2);
+    }
+    
+    #[test]
+    fn saved_properties_follow_the_canonical_order() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &vtodo(&[
+                "X-CUSTOM:extra",
+                "DTSTAMP:20250101T120000Z",
+                "LAST-MODIFIED:20250101T110000Z",
+                "CREATED:20250101T100000Z",
+                "DUE;VALUE=DATE:20250310",
+                "DTSTART;VALUE=DATE:20250301",
+                "CATEGORIES:Work",
+                "PRIORITY:1",
+                "STATUS:NEEDS-ACTION",
+                "DESCRIPTION:Details",
+                "SUMMARY:Everything",
+                "UID:a@test",
+            ]));
+            let saved = render_todos_into(&path, &parse_todos_from_file(&path).unwrap()).unwrap();
+            let todo = &saved[saved.find("BEGIN:VTODO").unwrap()..];
+            let names: Vec<String> = todo.lines().filter_map(property_base_name).collect();
+            assert_eq!(names, [
+                "BEGIN", "UID", "SUMMARY", "DESCRIPTION", "STATUS", "PRIORITY", "CATEGORIES", "DTSTART", "DUE", "CREATED",
+                "DTSTAMP", "END", "END",
+            ]);
+        });
+    }
+    
+    #[test]
+    fn unlisted_properties_rank_after_the_canonical_ones() {
+        let rank = |line: &str| property_rank(line, &COMPONENT_PROPERTY_ORDER);
+        assert!(rank("CREATED:20250101T100000Z") < rank("LAST-MODIFIED:20250101T110000Z"));
+        assert!(rank("LAST-MODIFIED:20250101T110000Z") < rank("DTSTAMP:20250101T120000Z"));
+        assert_eq!(rank("X-CUSTOM:extra"), COMPONENT_PROPERTY_ORDER.len());
+        assert_eq!(rank("X-OTHER:extra"), rank("X-CUSTOM:extra"));
+    }
+    
+    #[test]
+    fn event_is_converted_into_a_todo() {
+        with_calendars_dir(|dir| {
+            let event = "BEGIN:VEVENT\r\nUID:event@test\r\nSUMMARY:Dentist\r\nDESCRIPTION:Bring insurance card\r\nDTSTART;VALUE=DATE:20250312\r\nEND:VEVENT\r\n";
+            let source = write_calendar(dir, "events.ics", event);
+            let target = write_calendar(dir, "tasks.ics", "");
+            let convert = |remove: Option<bool>| {
+                with_cache(|cache| {
+                    tauri::async_runtime::block_on(convert_event_to_todo(
+                        "events.ics".to_string(),
+                        "event@test".to_string(),
+                        "tasks.ics".to_string(),
+                        remove,
+                        cache,
+                    ))
+                })
+            };
+            
+            let todo = convert(None).unwrap();
+            assert_ne!(todo.id, "event@test");
+            assert_eq!((todo.title.as_str(), todo.description.as_str()), ("Dentist", "Bring insurance card"));
+            assert_eq!(todo.due_date.as_deref(), Some("2025-03-12"));
+            assert!(todo.all_day && !todo.completed);
+            assert_eq!(todo.calendar_name, "tasks");
+            assert!(fs::read_to_string(&source).unwrap().contains(event));
+            assert_eq!(parse_todos_from_file(&target).unwrap()[0].id, todo.id);
+            
+            // With `remove` the event is taken out of its calendar
+            convert(Some(true)).unwrap();
+            assert!(!fs::read_to_string(&source).unwrap().contains("VEVENT"));
+            assert_eq!(parse_todos_from_file(&target).unwrap().len(), 2);
+            assert!(convert(None).unwrap_err().contains("Event not found"));
+        });
+    }
+    
+    #[test]
+    fn listed_count_follows_the_show_completed_setting() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:Open"]),
+                vtodo(&["UID:b@test", "SUMMARY:Done", "STATUS:COMPLETED"]),
+            ].concat());
+            let listed = || {
+                let calendars = tauri::async_runtime::block_on(list_calendars()).unwrap();
+                (calendars[0].todo_count, calendars[0].open_count, calendars[0].display_count)
+            };
+            assert_eq!(listed(), (2, 1, 2));
+            
+            save_settings(Settings { show_completed: false, ..Settings::default() }).unwrap();
+            assert_eq!(listed(), (2, 1, 1));
+        });
+    }
+    
+    #[test]
+    fn todos_are_delivered_in_batches() {
+        let mut batches = Vec::new();
+        let total = for_each_batch(0..250, STREAM_BATCH_SIZE, |offset, batch| batches.push((offset, batch)));
+        assert_eq!(total, 250);
+        let shape: Vec<(usize, usize)> = batches.iter().map(|(offset, batch)| (*offset, batch.len())).collect();
+        assert_eq!(shape, [(0, 100), (100, 100), (200, 50)]);
+        assert_eq!(batches[2].1.first(), Some(&200));
+        
+        // Exact multiples don't produce an empty trailing batch
+        let mut sizes = Vec::new();
+        assert_eq!(for_each_batch(0..200, 100, |_, batch| sizes.push(batch.len())), 200);
+        assert_eq!(sizes, [100, 100]);
+    }
+    
+    #[test]
+    fn empty_input_emits_no_batches() {
+        let mut emitted = 0;
+        assert_eq!(for_each_batch(std::iter::empty::<Todo>(), STREAM_BATCH_SIZE, |_, _| emitted += 1), 0);
+        assert_eq!(emitted, 0);
+        // A zero batch size still makes progress
+        assert_eq!(for_each_batch(0..3, 0, |_, batch| assert_eq!(batch.len(), 1)), 3);
+    }
+    
+    #[test]
+    fn date_styles_survive_an_edit_and_save() {
+        with_calendars_dir(|dir| {
+            let styles = [
+                "DUE;VALUE=DATE:20250301",
+                "DUE:20250302",
+                "DUE:20250303T090000Z",
+                "DUE:20250304T090000",
+                "DUE;TZID=Asia/Tokyo:20250305T090000",
+            ];
+            let components: Vec<String> = styles
+                .iter()
+                .enumerate()
+                .map(|(i, due)| vtodo(&[&format!("UID:{}@test", i), "SUMMARY:Before", "DTSTART:20250201", due]))
+                .collect();
+            let path = write_calendar(dir, "work.ics", &components.concat());
+            
+            let todos: Vec<Todo> = parse_todos_from_file(&path)
+                .unwrap()
+                .into_iter()
+                .map(|todo| Todo { title: "After".to_string(), ..todo })
+                .collect();
+            let saved = render_todos_into(&path, &todos).unwrap();
+            for due in styles {
+                assert!(saved.contains(&format!("\r\n{}\r\n", due)), "{} was rewritten", due);
+            }
+            assert_eq!(saved.matches("\r\nDTSTART:20250201\r\n").count(), styles.len());
+            assert_eq!(saved.matches("SUMMARY:After").count(), styles.len());
+        });
+    }
+    
+    #[test]
+    fn new_dates_use_the_default_styles() {
+        let date = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        assert_eq!(date_property("DUE", date, true, None), "DUE;VALUE=DATE:20250301");
+        assert_eq!(date_property("DUE", date, false, None), "DUE:20250301T090000Z");
+    }
+    
+    #[test]
+    fn preview_save_renders_without_writing() {
+        with_calendars_dir(|dir| {
+            let path = dir.join("work.ics");
+            let original = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nEND:VCALENDAR\r\n";
+            fs::write(&path, original).unwrap();
+            let todo = Todo {
+                id: "a@test".to_string(),
+                due_date: Some("2025-03-01".to_string()),
+                created_at: Some("2025-01-01T08:00:00".to_string()),
+                ..Todo::new("Preview me", "work")
+            };
+            
+            let rendered = tauri::async_runtime::block_on(preview_save("work.ics".to_string(), vec![todo])).unwrap();
+            // New todos are stamped with the current time
+            assert_eq!(rendered.matches("\r\nDTSTAMP:").count(), 1);
+            let stable: Vec<&str> = rendered.split("\r\n").filter(|line| !line.starts_with("DTSTAMP:")).collect();
+            assert_eq!(stable, [
+                "BEGIN:VCALENDAR",
+                "VERSION:2.0",
+                "PRODID:-//test//EN",
+                "BEGIN:VTODO",
+                "UID:a@test",
+                "SUMMARY:Preview me",
+                "STATUS:NEEDS-ACTION",
+                "PRIORITY:5",
+                "DUE;VALUE=DATE:20250301",
+                "CREATED:20250101T080000Z",
+                "END:VTODO",
+                "END:VCALENDAR",
+                "",
+            ]);
+            assert_eq!(fs::read_to_string(&path).unwrap(), original);
+        });
+    }
+    
+    #[test]
+    fn compare_reports_added_removed_and_changed_todos() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "before.ics", &[
+                vtodo(&["UID:same@test", "SUMMARY:Same", "CREATED:20241201T000000Z", "DTSTAMP:20250101T000000Z"]),
+                vtodo(&["UID:edited@test", "SUMMARY:Edited", "PRIORITY:5"]),
+                vtodo(&["UID:gone@test", "SUMMARY:Gone"]),
+            ].concat());
+            write_calendar(dir, "after.ics", &[
+                vtodo(&["UID:same@test", "SUMMARY:Same", "CREATED:20241201T000000Z", "DTSTAMP:20250601T000000Z"]),
+                vtodo(&["UID:edited@test", "SUMMARY:Edited", "PRIORITY:1"]),
+                vtodo(&["UID:new@test", "SUMMARY:New"]),
+            ].concat());
+            
+            let diff = tauri::async_runtime::block_on(compare_calendars("before.ics".to_string(), "after.ics".to_string())).unwrap();
+            assert_eq!(diff.added.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(), ["new@test"]);
+            assert_eq!(diff.removed.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(), ["gone@test"]);
+            assert_eq!(diff.changed.len(), 1);
+            assert_eq!(diff.changed[0].uid, "edited@test");
+            assert_eq!(diff.changed[0].fields, ["priority"]);
+        });
+    }
+    
+    // Titles of the todos in each flush of a SaveQueue
+    type Flushes = std::sync::Arc<Mutex<Vec<Vec<String>>>>;
+    
+    // Queue three saves of `work.ics` in quick succession, recording every flush
+    fn queue_three_saves(queue: &SaveQueue) -> Flushes {
+        let flushes = Flushes::default();
+        for title in ["First", "Second", "Third"] {
+            let flushes = std::sync::Arc::clone(&flushes);
+            let todos = vec![Todo::new(title, "work")];
+            queue
+                .push(PathBuf::from("work.ics"), todos, std::time::Duration::from_millis(100), move |_, todos| {
+                    flushes.lock().unwrap().push(todos.into_iter().map(|todo| todo.title).collect());
+                })
+                .unwrap();
+        }
+        flushes
+    }
+    
+    #[test]
+    fn quick_successive_saves_are_written_once() {
+        let queue = SaveQueue::default();
+        let flushes = queue_three_saves(&queue);
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        assert_eq!(*flushes.lock().unwrap(), [vec!["Third".to_string()]]);
+        assert!(queue.take_all().is_empty());
+    }
+    
+    #[test]
+    fn taken_saves_are_not_flushed_again() {
+        let queue = SaveQueue::default();
+        let flushes = queue_three_saves(&queue);
+        let pending = queue.take_all();
+        assert_eq!(pending[Path::new("work.ics")][0].title, "Third");
+        
+        // The timers find nothing left to write
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        assert!(flushes.lock().unwrap().is_empty());
+    }
+    
+    #[test]
+    fn duration_is_parsed_and_written_back() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Focus block", "DURATION:PT1H30M"]);
+        assert_eq!(todo.duration.as_deref(), Some("PT1H30M"));
+        assert_eq!(parse_ical_duration("PT1H30M"), Some(chrono::Duration::minutes(90)));
+        assert!(vtodo_component(&todo, None).lines.iter().any(|line| line == "DURATION:PT1H30M"));
+        
+        // Malformed durations are dropped on read and rejected on save
+        assert_eq!(parse_vtodo(&["UID:b@test", "SUMMARY:Bad", "DURATION:1H30M"]).duration, None);
+        let invalid = Todo { duration: Some("PT1X".to_string()), ..Todo::new("Bad", "test") };
+        assert!(invalid.validate().unwrap_err().contains("Invalid DURATION"));
+    }
+    
+    #[test]
+    fn due_is_derived_from_start_plus_duration() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Focus block", "DTSTART:20250301T230000Z", "DURATION:PT1H30M"]);
+        assert_eq!(todo.due_date, None);
+        let due = todo.due_from_duration().unwrap();
+        assert_eq!(due.format("%Y-%m-%dT%H:%M:%S").to_string(), "2025-03-02T00:30:00");
+        assert_eq!(todo.due_day(), NaiveDate::from_ymd_opt(2025, 3, 2));
+        
+        // An explicit DUE wins
+        let due_set = parse_vtodo(&["UID:b@test", "SUMMARY:Both", "DTSTART;VALUE=DATE:20250301", "DURATION:P5D", "DUE;VALUE=DATE:20250303"]);
+        assert_eq!(due_set.due_day(), NaiveDate::from_ymd_opt(2025, 3, 3));
+    }
+    
+    #[test]
+    fn metadata_reports_size_counts_and_writability() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:Open"]),
+                vtodo(&["UID:b@test", "SUMMARY:Done", "STATUS:COMPLETED"]),
+            ].concat());
+            let metadata = || tauri::async_runtime::block_on(get_calendar_metadata("work.ics".to_string())).unwrap();
+            
+            let meta = metadata();
+            assert_eq!(meta.size_bytes, fs::metadata(&path).unwrap().len());
+            assert_eq!((meta.todo_count, meta.open_count), (2, 1));
+            assert!(meta.writable);
+            let modified = file_modified_time(&path).unwrap().duration_since(std::time::UNIX_EPOCH).unwrap();
+            assert_eq!(meta.last_modified, modified.as_secs().to_string());
+            
+            let mut permissions = fs::metadata(&path).unwrap().permissions();
+            permissions.set_readonly(true);
+            fs::set_permissions(&path, permissions).unwrap();
+            assert!(!metadata().writable);
+        });
+    }
+    
+    // Categories of each todo in `path`, in file order
+    fn categories_by_todo(path: &Path) -> Vec<Vec<String>> {
+        parse_todos_from_file(path).unwrap().into_iter().map(|todo| todo.categories).collect()
+    }
+    
+    #[test]
+    fn category_casings_are_merged() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:One", "CATEGORIES:Work"]),
+                vtodo(&["UID:b@test", "SUMMARY:Two", "CATEGORIES:work,Home"]),
+                vtodo(&["UID:c@test", "SUMMARY:Three", "CATEGORIES:WORK,work", "X-CUSTOM:kept"]),
+            ].concat());
+            let normalize = |mapping: &[(&str, &str)]| {
+                let mapping = mapping.iter().map(|(from, to)| (from.to_string(), to.to_string())).collect();
+                with_cache(|cache| tauri::async_runtime::block_on(normalize_categories("work.ics".to_string(), mapping, cache))).unwrap()
+            };
+            
+            // The first spelling seen wins by default
+            assert_eq!(normalize(&[]), 2);
+            assert_eq!(categories_by_todo(&path), [vec!["Work"], vec!["Work", "Home"], vec!["Work"]]);
+            assert!(fs::read_to_string(&path).unwrap().contains("X-CUSTOM:kept"));
+            
+            assert_eq!(normalize(&[("work", "Office")]), 3);
+            assert_eq!(categories_by_todo(&path), [vec!["Office"], vec!["Office", "Home"], vec!["Office"]]);
+            assert_eq!(normalize(&[]), 0);
+        });
+    }
+    
+    #[test]
+    fn due_dates_are_described_relative_to_the_reference() {
+        let relative = |due: &str| format_due_relative(due.to_string(), Some("2025-03-10".to_string())).unwrap();
+        assert_eq!(relative("2025-03-07"), "3 days ago");
+        assert_eq!(relative("2025-03-09"), "yesterday");
+        assert_eq!(relative("2025-03-10"), "today");
+        assert_eq!(relative("2025-03-10T23:30:00"), "today");
+        assert_eq!(relative("2025-03-11"), "tomorrow");
+        assert_eq!(relative("2025-03-15T08:00:00"), "in 5 days");
+    }
+    
+    #[test]
+    fn relative_due_defaults_to_today_and_rejects_garbage() {
+        let today = chrono::Local::now().date_naive().format("%Y-%m-%d").to_string();
+        assert_eq!(format_due_relative(today, None).unwrap(), "today");
+        assert!(format_due_relative("next week".to_string(), None).is_err());
+        assert!(format_due_relative("2025-03-10".to_string(), Some("10/03/2025".to_string())).is_err());
+    }
+    
+    #[test]
+    fn todos_loaded_after_a_rename_carry_the_new_name() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "old.ics", &[vtodo(&["UID:a@test", "SUMMARY:One"]), vtodo(&["UID:b@test", "SUMMARY:Two"])].concat());
+            let stale = parse_todos_from_file(&dir.join("old.ics")).unwrap();
+            
+            let renamed = with_cache(|cache| {
+                tauri::async_runtime::block_on(rename_calendar("old.ics".to_string(), "New".to_string(), cache))
+            })
+            .unwrap();
+            assert_eq!(renamed.name, "New");
+            assert!(!dir.join("old.ics").exists());
+            
+            let names = |todos: &[Todo]| todos.iter().map(|todo| todo.calendar_name.clone()).collect::<Vec<_>>();
+            let loaded = with_cache(|cache| cached_todos("New.ics", &cache, |_, _| {})).unwrap();
+            assert_eq!(names(&loaded), ["New", "New"]);
+            assert_eq!(names(&fix_calendar_names("New.ics".to_string(), stale).unwrap()), ["New", "New"]);
+        });
+    }
+    
+    #[test]
+    fn moved_todo_takes_the_target_name() {
+        with_calendars_dir(|dir| {
+            let source = write_calendar(dir, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:Move me"]));
+            let target = write_calendar(dir, "home.ics", "");
+            let moved = with_cache(|cache| {
+                tauri::async_runtime::block_on(move_todo("work.ics".to_string(), "a@test".to_string(), "home.ics".to_string(), cache))
+            })
+            .unwrap();
+            assert_eq!(moved.calendar_name, "home");
+            assert!(parse_todos_from_file(&source).unwrap().is_empty());
+            assert_eq!(parse_todos_from_file(&target).unwrap()[0].calendar_name, "home");
+        });
+    }
+    
+    #[test]
+    fn bulk_priority_updates_the_listed_todos() {
+        with_calendars_dir(|dir| {
+            let path = write_three_todos(dir);
+            let set_priority = |uids: Vec<String>, priority: &str| {
+                with_cache(|cache| {
+                    tauri::async_runtime::block_on(bulk_set_priority("work.ics".to_string(), uids, priority.to_string(), cache))
+                })
+            };
+            
+            assert_eq!(set_priority(uids(&["a@test", "c@test", "missing@test"]), "high"), Ok(2));
+            let priorities: Vec<String> = parse_todos_from_file(&path).unwrap().into_iter().map(|todo| todo.priority).collect();
+            assert_eq!(priorities, ["high", "medium", "high"]);
+            let content = fs::read_to_string(&path).unwrap();
+            assert_eq!(content.matches("PRIORITY:1\r\n").count(), 2);
+            assert_eq!(content.matches("LAST-MODIFIED:").count(), 2);
+            
+            assert!(set_priority(uids(&["a@test"]), "urgent").unwrap_err().contains("Invalid priority"));
+        });
+    }
+    
+    #[test]
+    fn bulk_tag_adds_and_removes_categories() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:One", "CATEGORIES:Work,Later"]),
+                vtodo(&["UID:b@test", "SUMMARY:Two", "CATEGORIES:later,Urgent"]),
+                vtodo(&["UID:c@test", "SUMMARY:Three", "CATEGORIES:Later"]),
+            ].concat());
+            let tag = |add: &[&str], remove: &[&str]| {
+                with_cache(|cache| {
+                    tauri::async_runtime::block_on(bulk_tag(
+                        "work.ics".to_string(),
+                        uids(&["a@test", "b@test"]),
+                        uids(add),
+                        uids(remove),
+                        cache,
+                    ))
+                })
+            };
+            
+            assert_eq!(tag(&["Q3"], &["LATER"]), Ok(2));
+            assert_eq!(categories_by_todo(&path), [vec!["Work", "Q3"], vec!["Urgent", "Q3"], vec!["Later"]]);
+            // Tags already present aren't duplicated
+            assert_eq!(tag(&["q3"], &[]), Ok(0));
+        });
+    }
+    
+    #[test]
+    fn contact_round_trips_with_altrep() {
+        let line = "CONTACT;ALTREP=\"ldap://example.com:6666/o=ABC\":Jim Dolittle\\, ABC Industries";
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Call back", line]);
+        assert_eq!(todo.contact.as_deref(), Some("Jim Dolittle, ABC Industries"));
+        assert_eq!(todo.contact_params, [("ALTREP".to_string(), "ldap://example.com:6666/o=ABC".to_string())]);
+        
+        let written = vtodo_component(&todo, None).lines.join("\r\n").replace("\r\n ", "");
+        assert!(written.contains(&format!("{}\r\n", line)));
+        let reparsed = vtodo_component(&todo, None).to_todo("test").unwrap();
+        assert_eq!((reparsed.contact, reparsed.contact_params), (todo.contact, todo.contact_params));
+    }
+    
+    #[test]
+    fn vcalendar_1_0_is_loaded_with_a_warning() {
+        let content = format!("BEGIN:VCALENDAR\r\nVERSION:1.0\r\n{}END:VCALENDAR\r\n", vtodo(&["UID:a@test", "SUMMARY:Old"]));
+        let warnings = calendar_warnings(&content);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("VERSION:1.0"));
+        assert_eq!(parse_todos_from_content(&content, "old").len(), 1);
+        
+        let lunar = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nCALSCALE:CHINESE\r\nEND:VCALENDAR\r\n";
+        assert!(calendar_warnings(lunar)[0].contains("CALSCALE:CHINESE"));
+        let standard = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nCALSCALE:gregorian\r\nEND:VCALENDAR\r\n";
+        assert!(calendar_warnings(standard).is_empty());
+    }
+    
+    #[test]
+    fn subtasks_of_missing_parents_are_orphans() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:parent@test", "SUMMARY:Parent"]),
+                vtodo(&["UID:child@test", "SUMMARY:Child", "RELATED-TO:parent@test"]),
+                vtodo(&["UID:orphan@test", "SUMMARY:Orphan", "RELATED-TO;RELTYPE=PARENT:deleted@test"]),
+                vtodo(&["UID:sibling@test", "SUMMARY:Sibling", "RELATED-TO;RELTYPE=SIBLING:deleted@test"]),
+            ].concat());
+            let orphans = with_cache(|cache| tauri::async_runtime::block_on(find_orphans("work.ics".to_string(), cache))).unwrap();
+            assert_eq!(orphans.iter().map(|todo| todo.id.as_str()).collect::<Vec<_>>(), ["orphan@test"]);
+            assert_eq!(orphans[0].parent_uid.as_deref(), Some("deleted@test"));
+        });
+    }
+    
+    // outline_todos for `work.ics`
+    fn outline() -> String {
+        with_cache(|cache| tauri::async_runtime::block_on(outline_todos("work.ics".to_string(), cache))).unwrap()
+    }
+    
+    #[test]
+    fn outline_indents_subtasks_under_their_parent() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:late@test", "SUMMARY:Second step", "RELATED-TO:root@test", "DUE;VALUE=DATE:20250320"]),
+                vtodo(&["UID:root@test", "SUMMARY:Project"]),
+                vtodo(&["UID:early@test", "SUMMARY:First step", "RELATED-TO:root@test", "DUE;VALUE=DATE:20250310", "STATUS:COMPLETED"]),
+            ].concat());
+            assert_eq!(
+                outline(),
+                "[ ] Project\n  [x] First step (due 2025-03-10)\n  [ ] Second step (due 2025-03-20)\n"
+            );
+        });
+    }
+    
+    #[test]
+    fn outline_survives_parent_cycles() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:A", "RELATED-TO:b@test"]),
+                vtodo(&["UID:b@test", "SUMMARY:B", "RELATED-TO:a@test"]),
+                vtodo(&["UID:self@test", "SUMMARY:Self", "RELATED-TO:self@test"]),
+            ].concat());
+            let outline = outline();
+            assert_eq!(outline.lines().count(), 3);
+            assert!(outline.contains("[ ] Self\n"));
+            assert!(outline.contains("[ ] A\n  [ ] B\n") || outline.contains("[ ] B\n  [ ] A\n"));
+        });
+    }
+    
+    #[test]
+    fn datetime_created_keeps_its_time() {
+        let todo = parse_vtodo(&["UID:a@test", "SUMMARY:Timed", "CREATED:20250301T101530Z"]);
+        assert_eq!(todo.created_at.as_deref(), Some("2025-03-01T10:15:30"));
+        let component = vtodo_component(&todo, None);
+        assert!(component.lines.iter().any(|line| line == "CREATED:20250301T101530Z"));
+        
+        // The frontend's toISOString() form isn't truncated to a date either
+        let from_js = Todo { created_at: Some("2025-03-01T10:15:30.000Z".to_string()), ..todo };
+        assert!(vtodo_component(&from_js, None).lines.iter().any(|line| line == "CREATED:20250301T101530Z"));
+        
+        let date_only = parse_vtodo(&["UID:b@test", "SUMMARY:Dated", "CREATED:20250301"]);
+        assert!(vtodo_component(&date_only, None).lines.iter().any(|line| line == "CREATED:20250301"));
+    }
+    
+    #[test]
+    fn added_todos_get_a_full_precision_created() {
+        with_calendars_dir(|dir| {
+            let path = write_calendar(dir, "work.ics", "");
+            let added = append_todo(&path, Todo { created_at: None, ..Todo::new("Fresh", "work") }, &TodoCache::default()).unwrap();
+            assert_eq!(added.created_at.as_deref().map(str::len), Some(19));
+            let dated = append_todo(&path, Todo { created_at: Some("2025-03-01".to_string()), ..Todo::new("Dated", "work") }, &TodoCache::default());
+            assert_eq!(dated.unwrap().created_at.as_deref(), Some("2025-03-01T00:00:00"));
+            assert!(fs::read_to_string(&path).unwrap().contains("CREATED:20250301T000000Z"));
+        });
+    }
+    
+    // Wait up to a few seconds for `condition`, as watcher events arrive on another thread
+    fn wait_for(condition: impl Fn() -> bool) -> bool {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !condition() {
+            if std::time::Instant::now() > deadline {
+                return false;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        true
+    }
+    
+    #[test]
+    fn stopping_the_watcher_stops_change_events() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let dir = TempDir::new();
+        let events = std::sync::Arc::new(AtomicUsize::new(0));
+        let watcher = CalendarWatcher::default();
+        let counter = std::sync::Arc::clone(&events);
+        watcher
+            .watch(&dir.0, move |changed| {
+                assert!(changed.path.ends_with("work.ics"));
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        
+        write_calendar(&dir.0, "work.ics", &vtodo(&["UID:a@test", "SUMMARY:One"]));
+        fs::write(dir.0.join("notes.txt"), "not a calendar").unwrap();
+        assert!(wait_for(|| events.load(Ordering::SeqCst) > 0));
+        
+        watcher.stop();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        events.store(0, Ordering::SeqCst);
+        write_calendar(&dir.0, "work.ics", &vtodo(&["UID:b@test", "SUMMARY:Two"]));
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        assert_eq!(events.load(Ordering::SeqCst), 0);
+    }
+    
+    #[test]
+    fn auto_reload_setting_is_persisted() {
+        with_calendars_dir(|_| {
+            assert_eq!(is_auto_reload_enabled(), Settings::default().auto_reload);
+            save_settings(Settings { auto_reload: false, ..Settings::default() }).unwrap();
+            assert!(!is_auto_reload_enabled());
+            save_settings(Settings { auto_reload: true, ..Settings::default() }).unwrap();
+            assert!(is_auto_reload_enabled());
+        });
+    }
+    
+    #[test]
+    fn distinct_categories_are_sorted_and_deduplicated() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:One", "CATEGORIES:Work,Home"]),
+                vtodo(&["UID:b@test", "SUMMARY:Two", "CATEGORIES:Errands,Work"]),
+                vtodo(&["UID:c@test", "SUMMARY:Three", "CATEGORIES:Health"]),
+                vtodo(&["UID:d@test", "SUMMARY:Four"]),
+            ].concat());
+            let categories = with_cache(|cache| tauri::async_runtime::block_on(list_categories("work.ics".to_string(), cache))).unwrap();
+            assert_eq!(categories, ["Errands", "Health", "Home", "Work"]);
+        });
+    }
+    
+    #[test]
+    fn category_counts_are_summed_across_calendars() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:a@test", "SUMMARY:One", "CATEGORIES:Shared,Work"]),
+                vtodo(&["UID:b@test", "SUMMARY:Two", "CATEGORIES:Shared,Shared"]),
+            ].concat());
+            write_calendar(dir, "home.ics", &vtodo(&["UID:c@test", "SUMMARY:Three", "CATEGORIES:Shared,Home"]));
+            fs::write(dir.join("broken.ics.gz"), "not gzip").unwrap();
+            
+            let totals = tauri::async_runtime::block_on(list_all_categories()).unwrap();
+            let expected = [("Home", 1), ("Shared", 3), ("Work", 1)].map(|(name, count)| (name.to_string(), count));
+            assert_eq!(totals, expected);
+        });
+    }
+    
+    #[test]
+    fn failed_create_falls_back_to_a_user_directory() {
+        let scratch = TempDir::new();
+        // A directory can't be created beneath a plain file, whoever runs the test
+        let read_only = scratch.0.join("install");
+        fs::write(&read_only, "").unwrap();
+        let preferred = read_only.join("calendars");
+        let fallback = scratch.0.join("user").join("2do").join("calendars");
+        
+        let (dir, source) = create_calendars_dir(preferred.clone(), vec![preferred.clone(), fallback.clone()]).unwrap();
+        assert_eq!((dir, source), (fallback.clone(), CalendarsSource::Fallback));
+        assert!(fallback.is_dir());
+        
+        let writable = scratch.0.join("portable").join("calendars");
+        assert_eq!(create_calendars_dir(writable.clone(), vec![fallback]).unwrap(), (writable, CalendarsSource::Discovered));
+    }
+    
+    #[test]
+    fn no_writable_location_is_a_clear_error() {
+        let scratch = TempDir::new();
+        let file = scratch.0.join("file");
+        fs::write(&file, "").unwrap();
+        let error = create_calendars_dir(file.join("a"), vec![file.join("b")]).unwrap_err();
+        assert!(error.starts_with("No writable calendars directory found"));
+        assert!(error.contains(CALENDARS_DIR_ENV));
+    }
+    
+    #[test]
+    fn health_check_reports_good_and_malformed_calendars() {
+        with_calendars_dir(|dir| {
+            write_calendar(dir, "good.ics", &vtodo(&["UID:a@test", "SUMMARY:Fine"]));
+            fs::write(dir.join("bad.ics"), [
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n",
+                &vtodo(&["UID:dup@test", "SUMMARY:One"]),
+                &vtodo(&["UID:dup@test", "SUMMARY:Two", "DTSTART;VALUE=DATE:20250310", "DUE;VALUE=DATE:20250301"]),
+                "BEGIN:VTODO\r\nSUMMARY:No UID and never closed\r\n",
+            ]
+            .concat())
+            .unwrap();
+            fs::write(dir.join("garbled.ics.gz"), "not gzip").unwrap();
+            
+            let report = tauri::async_runtime::block_on(check_all_calendars()).unwrap();
+            let health = |name: &str| report.iter().find(|h| h.name == name).unwrap();
+            
+            let good = health("good");
+            assert!(good.loaded && good.issues.is_empty());
+            assert_eq!((good.vtodos, good.parsed, good.failed, good.invalid), (1, 1, 0, 0));
+            
+            let bad = health("bad");
+            assert!(bad.loaded);
+            assert_eq!((bad.vtodos, bad.parsed, bad.invalid), (3, 3, 1));
+            for issue in ["unterminated block", "without a UID", "duplicate UID"] {
+                assert!(bad.issues.iter().any(|i| i.contains(issue)), "missing {} in {:?}", issue, bad.issues);
+            }
+            
+            let garbled = health("garbled");
+            assert!(!garbled.loaded && garbled.error.is_some());
+        });
+    }
+    
+    #[test]
+    fn external_calendar_is_read_from_outside_the_calendars_dir() {
+        with_calendars_dir(|dir| {
+            let elsewhere = TempDir::new();
+            let path = write_calendar(&elsewhere.0, "Nextcloud Tasks.ics", &vtodo(&["UID:a@test", "SUMMARY:Synced"]));
+            assert!(!path.starts_with(dir));
+            // The managed commands stay confined to the calendars directory
+            assert!(resolve_calendar_path(&path.to_string_lossy(), false).is_err());
+            
+            let loaded = tauri::async_runtime::block_on(load_external_calendar(path.to_string_lossy().to_string())).unwrap();
+            assert_eq!(loaded.todos.len(), 1);
+            assert_eq!(loaded.todos[0].calendar_name, "Nextcloud Tasks");
+            assert_eq!(loaded.modified, modified_token(file_modified_time(&path).unwrap()));
+        });
+    }
+    
+    #[test]
+    fn external_calendar_must_be_an_absolute_parseable_ics() {
+        let elsewhere = TempDir::new();
+        let load = |path: &Path| tauri::async_runtime::block_on(load_external_calendar(path.to_string_lossy().to_string()));
+        assert!(load(Path::new("relative.ics")).unwrap_err().contains("must be absolute"));
+        
+        let text = elsewhere.0.join("notes.txt");
+        fs::write(&text, "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n").unwrap();
+        assert!(load(&text).is_err());
+        
+        let garbage = elsewhere.0.join("garbage.ics");
+        fs::write(&garbage, "just some text").unwrap();
+        assert!(load(&garbage).unwrap_err().contains("Not a valid iCalendar file"));
+    }
+    
+    #[test]
+    fn today_agenda_spans_calendars_by_priority_then_title() {
+        with_calendars_dir(|dir| {
+            let today = format!("DUE;VALUE=DATE:{}", ical_day(0));
+            write_calendar(dir, "work.ics", &[
+                vtodo(&["UID:report@test", "SUMMARY:report", "PRIORITY:5", &today]),
+                vtodo(&["UID:call@test", "SUMMARY:Call", "PRIORITY:1", &today]),
+                vtodo(&["UID:done@test", "SUMMARY:Done", "PRIORITY:1", "STATUS:COMPLETED", &today]),
+                vtodo(&["UID:later@test", "SUMMARY:Later", "PRIORITY:1", &format!("DUE;VALUE=DATE:{}", ical_day(1))]),
+            ].concat());
+            write_calendar(dir, "home.ics", &[
+                vtodo(&["UID:bins@test", "SUMMARY:Bins", "PRIORITY:5", &today]),
+                vtodo(&["UID:late@test", "SUMMARY:Overdue", "PRIORITY:1", &format!("DUE;VALUE=DATE:{}", ical_day(-1))]),
+            ].concat());
+            
+            let agenda = tauri::async_runtime::block_on(today_agenda()).unwrap();
+            let entries: Vec<(&str, &str)> = agenda.iter().map(|t| (t.title.as_str(), t.calendar_name.as_str())).collect();
+            assert_eq!(entries, [("Call", "work"), ("Bins", "home"), ("report", "work")]);
+        });
+    }
 }
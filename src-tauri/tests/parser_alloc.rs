@@ -0,0 +1,93 @@
+// Peak heap usage of the lazy VTODO scanner against collecting every line first.
+// This is its own test binary so the counting allocator neither slows down the
+// unit tests nor gets skewed by them. Run with:
+// cargo test --release --test parser_alloc -- --ignored
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Tracks live and peak heap usage
+struct PeakAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let now = ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(now, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAlloc = PeakAlloc;
+
+// Peak bytes allocated while running `f`, above what was live beforehand
+fn peak_allocation<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.load(Ordering::SeqCst);
+    PEAK.store(before, Ordering::SeqCst);
+    let result = f();
+    (result, PEAK.load(Ordering::SeqCst) - before)
+}
+
+// The scan vtodo_blocks replaced: collect every line, then copy out each block
+fn collected_vtodo_block_lines(content: &str) -> usize {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut total = 0;
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].trim() == "BEGIN:VTODO" {
+            let start = i + 1;
+            while i < lines.len() && lines[i].trim() != "END:VTODO" {
+                i += 1;
+            }
+            total += lines[start..i].to_vec().len();
+        }
+        i += 1;
+    }
+    total
+}
+
+fn synthetic_calendar(todos: usize) -> String {
+    let mut content = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\n");
+    for i in 0..todos {
+        content.push_str(&format!(
+            "BEGIN:VTODO\r\nUID:todo-{i}@test\r\nSUMMARY:Task number {i} with a folded\r\n  summary line\r\nDESCRIPTION:Line one\\nLine two\r\nPRIORITY:{}\r\nDUE;VALUE=DATE:2026{:02}{:02}\r\nCATEGORIES:Work,Home\r\nSTATUS:NEEDS-ACTION\r\nEND:VTODO\r\n",
+            i % 10,
+            i % 12 + 1,
+            i % 28 + 1,
+        ));
+    }
+    content.push_str("END:VCALENDAR\r\n");
+    content
+}
+
+#[test]
+#[ignore]
+fn vtodo_blocks_peak_allocation() {
+    let mut content = synthetic_calendar(1);
+    let todos = synthetic_calendar(1000);
+    while content.len() < 50 * 1024 * 1024 {
+        content.push_str(&todos);
+    }
+
+    let (lazy, lazy_peak) = peak_allocation(|| d0_lib::bench::vtodo_block_lines(&content));
+    let (collected, collected_peak) = peak_allocation(|| collected_vtodo_block_lines(&content));
+
+    assert_eq!(lazy, collected);
+    assert!(
+        lazy_peak * 10 < collected_peak,
+        "lazy peak {} bytes, collected peak {} bytes",
+        lazy_peak,
+        collected_peak
+    );
+}